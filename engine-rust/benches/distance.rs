@@ -0,0 +1,122 @@
+// Distance kernels against the auto-vectorized loops they replaced, plus a
+// flat cosine scan.
+//
+//   cargo bench --bench distance
+//   VPACK_SIMD=scalar cargo bench --bench distance   # pin the portable kernels
+//
+// `baseline` is the old cosine: one zipped loop over both vectors, computing
+// the dot product and both norms on every call. `simd` is `cosine_similarity`
+// on the runtime-selected kernel, and `prepared` is what a cosine pack now
+// does per stored vector: a single dot product against unit-length rows.
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use serde_json::json;
+use std::collections::HashMap;
+use vpack_engine::math::{cosine_similarity, dot_product};
+use vpack_engine::{
+    BuildOptions, Chunk, ChunkMetadata, EmbeddedChunk, IndexType, QueryOptions, VPackIndex,
+};
+
+type Kernel = fn(&[f32], &[f32]) -> f32;
+
+const DIMENSIONS: [usize; 3] = [384, 768, 1536];
+const CHUNKS: usize = 20_000;
+const SCAN_DIMENSIONS: usize = 384;
+
+fn random_vectors(count: usize, dimensions: usize) -> Vec<Vec<f32>> {
+    let mut state = 0x9e37_79b9_7f4a_7c15_u64;
+    let mut next = move || {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        (state >> 40) as f32 / (1u64 << 24) as f32 - 0.5
+    };
+    (0..count)
+        .map(|_| (0..dimensions).map(|_| next()).collect())
+        .collect()
+}
+
+fn baseline_cosine(a: &[f32], b: &[f32]) -> f32 {
+    let (mut dot, mut norm_a, mut norm_b) = (0.0f32, 0.0f32, 0.0f32);
+    for (x, y) in a.iter().zip(b.iter()) {
+        dot += x * y;
+        norm_a += x * x;
+        norm_b += y * y;
+    }
+    let denom = norm_a.sqrt() * norm_b.sqrt();
+    if denom == 0.0 {
+        0.0
+    } else {
+        dot / denom
+    }
+}
+
+fn kernels(c: &mut Criterion) {
+    let cases: [(&str, Kernel); 3] = [
+        ("baseline", baseline_cosine),
+        ("simd", cosine_similarity),
+        ("prepared", dot_product),
+    ];
+
+    let mut group = c.benchmark_group("cosine");
+    for dimensions in DIMENSIONS {
+        let vectors = random_vectors(2, dimensions);
+        for (name, kernel) in cases {
+            group.bench_function(BenchmarkId::new(name, dimensions), |b| {
+                b.iter(|| kernel(black_box(&vectors[0]), black_box(&vectors[1])))
+            });
+        }
+    }
+    group.finish();
+}
+
+fn flat_scan(c: &mut Criterion) {
+    let chunks = random_vectors(CHUNKS, SCAN_DIMENSIONS)
+        .into_iter()
+        .enumerate()
+        .map(|(i, vector)| EmbeddedChunk {
+            chunk: Chunk {
+                id: format!("chunk-{i:06}"),
+                text: format!("text {i}"),
+                metadata: ChunkMetadata {
+                    source_plugin: "@vpack/source-fs".to_string(),
+                    source_id: format!("doc-{}", i / 10),
+                    source_url: None,
+                    created_at: None,
+                    updated_at: None,
+                    pack_name: "@bench/distance".to_string(),
+                    chunker_plugin: "@vpack/chunker-fixed".to_string(),
+                    extra: HashMap::new(),
+                },
+            },
+            vector,
+        })
+        .collect();
+    let manifest = json!({
+        "vpack": "1.0",
+        "name": "@bench/distance",
+        "version": "1.0.0",
+        "plugins": [
+            { "kind": "embedder", "use": "@vpack/embedder-xenova", "model": "Xenova/all-MiniLM-L6-v2", "dimensions": SCAN_DIMENSIONS }
+        ]
+    });
+    let options = BuildOptions {
+        index_type: IndexType::Flat,
+        ..BuildOptions::default()
+    };
+    let index = VPackIndex::build_with_options(chunks, manifest, options).unwrap();
+    let query: Vec<f32> = (0..SCAN_DIMENSIONS).map(|i| (i as f32).sin()).collect();
+
+    c.bench_function("scan/cosine", |b| {
+        b.iter(|| {
+            black_box(
+                index
+                    .query(black_box(&query), QueryOptions::default())
+                    .unwrap(),
+            )
+        })
+    });
+}
+
+criterion_group!(benches, kernels, flat_scan);
+criterion_main!(benches);
@@ -0,0 +1,233 @@
+/* vpack-engine C ABI. Generated by cbindgen from src/ffi.rs; do not edit. */
+
+#ifndef VPACK_ENGINE_H
+#define VPACK_ENGINE_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+/**
+ * Status returned by every fallible function. The values are stable.
+ */
+typedef enum VPackStatus {
+  VPACK_STATUS_OK = 0,
+  VPACK_STATUS_DIMENSION_MISMATCH = 1,
+  VPACK_STATUS_MODEL_MISMATCH = 2,
+  VPACK_STATUS_EMPTY_INDEX = 3,
+  VPACK_STATUS_MODEL_HASH_MISMATCH = 4,
+  VPACK_STATUS_SERIALIZE_FAILED = 5,
+  VPACK_STATUS_UNKNOWN_MODEL = 6,
+  VPACK_STATUS_DESERIALIZE_FAILED = 7,
+  VPACK_STATUS_MANIFEST_INVALID = 8,
+  VPACK_STATUS_DUPLICATE_CHUNK_ID = 9,
+  VPACK_STATUS_PATCH_BASE_MISMATCH = 10,
+  VPACK_STATUS_PATCH_FAILED = 11,
+  VPACK_STATUS_INVALID_QUERY = 12,
+  /**
+   * A null pointer, invalid UTF-8 or malformed options JSON.
+   */
+  VPACK_STATUS_INVALID_ARGUMENT = 100,
+  /**
+   * The engine panicked. The handle passed in should not be used again.
+   */
+  VPACK_STATUS_PANIC = 101,
+} VPackStatus;
+
+/**
+ * Opaque index handle.
+ */
+typedef struct VPackIndex VPackIndex;
+
+/**
+ * Opaque result set returned by `vpack_index_query`.
+ */
+typedef struct VPackResults VPackResults;
+
+/**
+ * One query result. Strings are NUL-terminated UTF-8 owned by the result
+ * set and valid until `vpack_results_free`.
+ */
+typedef struct VPackResult {
+  const char *chunk_id;
+  const char *text;
+  /**
+   * Chunk metadata as a JSON object.
+   */
+  const char *metadata_json;
+  float score;
+  float similarity;
+  uint32_t rank;
+} VPackResult;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * Load an index from `.vpack` bytes. The bytes are copied.
+ *
+ * # Safety
+ * `bytes` must point to `len` readable bytes and `out` must be writable.
+ */
+VPackStatus vpack_index_from_bytes(const uint8_t *bytes, size_t len, VPackIndex **out);
+
+/**
+ * Open a `.vpack` file through a memory map. The file must not change
+ * while the index is open.
+ *
+ * # Safety
+ * `path` must be a NUL-terminated string and `out` must be writable.
+ */
+VPackStatus vpack_index_open(const char *path, VPackIndex **out);
+
+/**
+ * Like `vpack_index_from_bytes`, checking the pack against LoadOptions
+ * JSON, e.g. `{"model": "...", "modelHash": "sha256:..."}`. A pack pinning
+ * another model fails with VPACK_STATUS_MODEL_MISMATCH or
+ * VPACK_STATUS_MODEL_HASH_MISMATCH. `options_json` may be null.
+ *
+ * # Safety
+ * As for `vpack_index_from_bytes`; `options_json` must be null or a
+ * NUL-terminated string.
+ */
+VPackStatus vpack_index_from_bytes_with_options(const uint8_t *bytes,
+                                                size_t len,
+                                                const char *options_json,
+                                                VPackIndex **out);
+
+/**
+ * Like `vpack_index_open`, checking the pack against LoadOptions JSON as
+ * `vpack_index_from_bytes_with_options` does.
+ *
+ * # Safety
+ * As for `vpack_index_open`; `options_json` must be null or a
+ * NUL-terminated string.
+ */
+VPackStatus vpack_index_open_with_options(const char *path,
+                                          const char *options_json,
+                                          VPackIndex **out);
+
+/**
+ * Release an index. Null is ignored.
+ *
+ * # Safety
+ * `index` must come from this library and not be used afterwards.
+ */
+void vpack_index_free(VPackIndex *index);
+
+/**
+ * Content chunks in the index. 0 for a null index.
+ *
+ * # Safety
+ * `index` must be null or a live index handle.
+ */
+size_t vpack_index_chunk_count(const VPackIndex *index);
+
+/**
+ * Vector dimensions of the index. 0 for a null index.
+ *
+ * # Safety
+ * `index` must be null or a live index handle.
+ */
+size_t vpack_index_dimensions(const VPackIndex *index);
+
+/**
+ * The manifest as JSON, released with `vpack_string_free`.
+ *
+ * # Safety
+ * `index` must be a live index handle and `out` must be writable.
+ */
+VPackStatus vpack_index_manifest_json(const VPackIndex *index, char **out);
+
+/**
+ * Query with a vector of `len` floats. `options_json` is a QueryOptions
+ * object as JSON, or null for the defaults.
+ *
+ * # Safety
+ * `vector` must point to `len` floats, `options_json` must be null or a
+ * NUL-terminated string, and `out` must be writable.
+ */
+VPackStatus vpack_index_query(const VPackIndex *index,
+                              const float *vector,
+                              size_t len,
+                              const char *options_json,
+                              VPackResults **out);
+
+/**
+ * Like `vpack_index_query`, but the results are a JSON array of
+ * QueryResult objects, released with `vpack_string_free`.
+ *
+ * # Safety
+ * As for `vpack_index_query`.
+ */
+VPackStatus vpack_index_query_json(const VPackIndex *index,
+                                   const float *vector,
+                                   size_t len,
+                                   const char *options_json,
+                                   char **out);
+
+#if defined(VPACK_FEATURE_EMBED)
+/**
+ * Embed `text` with the model pinned in the manifest and query with it.
+ *
+ * # Safety
+ * `text` must be a NUL-terminated string; otherwise as for
+ * `vpack_index_query`.
+ */
+VPackStatus vpack_index_query_text(const VPackIndex *index,
+                                   const char *text,
+                                   const char *options_json,
+                                   VPackResults **out);
+#endif
+
+/**
+ * Number of results in the set. 0 for null.
+ *
+ * # Safety
+ * `results` must be null or a live result set.
+ */
+size_t vpack_results_len(const VPackResults *results);
+
+/**
+ * The result at `i`, best first, or null when out of range. The pointer is
+ * valid until `vpack_results_free`.
+ *
+ * # Safety
+ * `results` must be null or a live result set.
+ */
+const VPackResult *vpack_results_get(const VPackResults *results, size_t i);
+
+/**
+ * Release a result set. Null is ignored.
+ *
+ * # Safety
+ * `results` must come from this library and not be used afterwards.
+ */
+void vpack_results_free(VPackResults *results);
+
+/**
+ * Release a string returned by this library. Null is ignored.
+ *
+ * # Safety
+ * `text` must come from this library and not be used afterwards.
+ */
+void vpack_string_free(char *text);
+
+/**
+ * `VPackError::code()` of the last failure on this thread, e.g.
+ * `"DIMENSION_MISMATCH"`, or null if nothing failed yet. Valid until the
+ * next failure on this thread.
+ */
+const char *vpack_last_error_code(void);
+
+/**
+ * Message of the last failure on this thread, or null. Valid until the
+ * next failure on this thread.
+ */
+const char *vpack_last_error_message(void);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* VPACK_ENGINE_H */
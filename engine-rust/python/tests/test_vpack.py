@@ -0,0 +1,116 @@
+# Tests for the `vpack` extension module. Run from engine-rust/:
+#   maturin develop --extras test && pytest python/tests
+
+import numpy as np
+import pytest
+
+import vpack
+
+DIMENSIONS = 3
+
+
+def make_manifest():
+    return {
+        "vpack": "1.0",
+        "name": "@test/fixture",
+        "version": "1.0.0",
+        "plugins": [
+            {"kind": "source", "use": "@vpack/source-fs", "path": "./docs"},
+            {"kind": "chunker", "use": "@vpack/chunker-fixed", "size": 512, "overlap": 64},
+            {
+                "kind": "embedder",
+                "use": "@vpack/embedder-xenova",
+                "model": "Xenova/all-MiniLM-L6-v2",
+                "dimensions": DIMENSIONS,
+                "provider": "huggingface",
+            },
+        ],
+    }
+
+
+def make_chunks(count):
+    return [
+        {
+            "id": f"chunk-{i:05}",
+            "text": f"text {i}",
+            "metadata": {
+                "source_plugin": "@vpack/source-fs",
+                "source_id": f"doc-{i}",
+                "source_url": None,
+                "created_at": None,
+                "updated_at": None,
+                "pack_name": "@test/fixture",
+                "chunker_plugin": "@vpack/chunker-fixed",
+            },
+        }
+        for i in range(count)
+    ]
+
+
+VECTORS = np.array([[1.0, 0.0, 0.0], [0.7, 0.7, 0.0], [0.0, 0.0, 1.0]], dtype=np.float32)
+
+
+@pytest.fixture
+def index():
+    return vpack.VPackIndex.build(make_chunks(3), make_manifest(), vectors=VECTORS)
+
+
+def test_build_serialize_round_trip(index, tmp_path):
+    assert len(index) == 3
+    assert index.dimensions() == DIMENSIONS
+    data = index.serialize()
+    assert vpack.VPackIndex.deserialize(data).serialize() == data
+
+    path = tmp_path / "fixture.vpack"
+    path.write_bytes(data)
+    loaded = vpack.load(path)
+    assert loaded.manifest()["name"] == "@test/fixture"
+
+
+def test_query_with_numpy_vectors(index):
+    results = index.query(np.array([0.0, 0.0, 1.0], dtype=np.float32), top_k=2)
+    assert [r["chunk"]["id"] for r in results] == ["chunk-00002", "chunk-00000"]
+    assert results[0]["rank"] == 0
+    assert results[0]["score"] == pytest.approx(1.0)
+
+    # Strided views and plain lists are accepted too.
+    strided = np.array([[0.0, 9.0], [0.0, 9.0], [1.0, 9.0]], dtype=np.float32)[:, 0]
+    assert index.query(strided, top_k=1)[0]["chunk"]["id"] == "chunk-00002"
+    assert index.query([1.0, 0.0, 0.0], top_k=1)[0]["chunk"]["id"] == "chunk-00000"
+
+
+def test_query_options_are_snake_case(index):
+    results = index.query(
+        [1.0, 0.0, 0.0],
+        min_score=0.5,
+        filter={"field": "source_id", "op": "neq", "value": "doc-0"},
+    )
+    assert [r["chunk"]["id"] for r in results] == ["chunk-00001"]
+
+
+def test_errors_are_typed(index):
+    with pytest.raises(vpack.DimensionMismatchError) as raised:
+        index.query(np.zeros(2, dtype=np.float32))
+    assert raised.value.code == "DIMENSION_MISMATCH"
+    assert isinstance(raised.value, vpack.VPackError)
+
+    with pytest.raises(vpack.InvalidQueryError):
+        index.query([1.0, 0.0, 0.0], temporal_weight=2.0)
+
+    with pytest.raises(vpack.DeserializeError) as raised:
+        vpack.VPackIndex.deserialize(b"not a pack")
+    assert raised.value.code == "DESERIALIZE_FAILED"
+
+
+def test_load_checks_the_expected_model(index, tmp_path):
+    data = index.serialize()
+    path = tmp_path / "fixture.vpack"
+    path.write_bytes(data)
+    assert len(vpack.load(path, model="Xenova/all-MiniLM-L6-v2")) == 3
+
+    with pytest.raises(vpack.ModelMismatchError) as raised:
+        vpack.load(path, model="BAAI/bge-small-en-v1.5")
+    assert raised.value.code == "MODEL_MISMATCH"
+
+    with pytest.raises(vpack.ModelMismatchError):
+        vpack.VPackIndex.deserialize(data, model="BAAI/bge-small-en-v1.5")
@@ -0,0 +1,318 @@
+// changelog.rs — changelog chunks and the CHANGELOG section (RFC-0004 §3)
+//
+// Chunks whose metadata has `chunk_type: "changelog"` are kept out of the
+// content rows: they have no row in CHUNKS, VECTORS or the ANN index. They are
+// stored, vectors included, in their own section, ordered by `built_at` and
+// then id. There are few of them, so queries score them exactly.
+//
+// A chunk with `ttl_days` expires `ttl_days` days after its `built_at`
+// (RFC-0004 §8.1). Expiry is decided when a pack is loaded and only hides the
+// chunk from queries. The section keeps it, so re-serializing a loaded pack
+// still gives the same bytes. Dropping expired chunks is left to the build.
+
+use crate::chunk::{ChunkMetadata, EmbeddedChunk};
+use crate::error::VPackError;
+use crate::filter::CompiledFilter;
+use crate::math::DistanceMetric;
+use crate::serialize::{decode_metadata, encode_metadata, PackMetadata};
+use serde::{Deserialize, Serialize};
+
+/// `chunk_type` of changelog chunks.
+pub const CHANGELOG_CHUNK_TYPE: &str = "changelog";
+
+pub(crate) const MILLIS_PER_DAY: i64 = 86_400_000;
+
+/// True for chunks that belong in the CHANGELOG section.
+pub fn is_changelog(metadata: &ChunkMetadata) -> bool {
+    metadata.extra.get("chunk_type").and_then(|v| v.as_str()) == Some(CHANGELOG_CHUNK_TYPE)
+}
+
+/// The pack's changelog chunks in section order.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Changelog {
+    entries: Vec<EmbeddedChunk>,
+    /// Parallel to `entries`.
+    expired: Vec<bool>,
+}
+
+/// One CHANGELOG section entry.
+#[derive(Serialize, Deserialize)]
+struct ChangelogRecord {
+    id: String,
+    text: String,
+    metadata: PackMetadata,
+    vector: Vec<f32>,
+}
+
+impl Changelog {
+    pub(crate) fn new(mut entries: Vec<EmbeddedChunk>) -> Self {
+        entries.sort_by(|a, b| order_key(a).cmp(&order_key(b)));
+        let expired = vec![false; entries.len()];
+        Self { entries, expired }
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Every entry, expired or not.
+    pub(crate) fn entries(&self) -> &[EmbeddedChunk] {
+        &self.entries
+    }
+
+    /// Entries that have not expired.
+    pub(crate) fn live(&self) -> impl Iterator<Item = &EmbeddedChunk> {
+        self.entries
+            .iter()
+            .zip(&self.expired)
+            .filter(|(_, expired)| !**expired)
+            .map(|(entry, _)| entry)
+    }
+
+    pub(crate) fn get(&self, i: usize) -> &EmbeddedChunk {
+        &self.entries[i]
+    }
+
+    pub(crate) fn contains(&self, id: &str) -> bool {
+        self.entries.iter().any(|entry| entry.chunk.id == id)
+    }
+
+    /// Add `entry` in order, replacing an entry with the same id.
+    pub(crate) fn insert(&mut self, entry: EmbeddedChunk) {
+        self.remove(|chunk| chunk.chunk.id == entry.chunk.id);
+        let at = self
+            .entries
+            .partition_point(|existing| order_key(existing) < order_key(&entry));
+        self.entries.insert(at, entry);
+        self.expired.insert(at, false);
+    }
+
+    /// Remove the entries `matches` selects. Returns how many were removed.
+    pub(crate) fn remove(&mut self, matches: impl Fn(&EmbeddedChunk) -> bool) -> usize {
+        let before = self.entries.len();
+        let mut i = 0;
+        while i < self.entries.len() {
+            if matches(&self.entries[i]) {
+                self.entries.remove(i);
+                self.expired.remove(i);
+            } else {
+                i += 1;
+            }
+        }
+        before - self.entries.len()
+    }
+
+    /// Mark entries whose TTL ran out before `now_ms` (Unix milliseconds).
+    pub(crate) fn expire(&mut self, now_ms: i64) {
+        for (entry, expired) in self.entries.iter().zip(&mut self.expired) {
+            *expired = expires_at(&entry.chunk.metadata).is_some_and(|at| now_ms > at);
+        }
+    }
+
+    /// Exact scores of the live entries passing `filter`, best first. The
+    /// index is the entry's position in `entries()`.
+    pub(crate) fn search(
+        &self,
+        query_vector: &[f32],
+        metric: DistanceMetric,
+        filter: Option<&CompiledFilter>,
+    ) -> Vec<(f32, usize)> {
+        let mut scored: Vec<(f32, usize)> = (0..self.entries.len())
+            .filter(|&i| !self.expired[i])
+            .filter(|&i| filter.is_none_or(|f| f.matches(&self.entries[i].chunk.metadata)))
+            .map(|i| (metric.score(query_vector, &self.entries[i].vector), i))
+            .collect();
+        scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+        scored
+    }
+
+    /// Check entry vectors against the pack's dimensions.
+    pub(crate) fn validate(&self, dimensions: usize) -> Result<(), VPackError> {
+        match self
+            .entries
+            .iter()
+            .find(|entry| entry.vector.len() != dimensions)
+        {
+            Some(entry) => Err(VPackError::DimensionMismatch {
+                expected: dimensions,
+                got: entry.vector.len(),
+            }),
+            None => Ok(()),
+        }
+    }
+
+    /// CHANGELOG section bytes: bincode entries, expired ones included.
+    pub(crate) fn encode(&self) -> Result<Vec<u8>, VPackError> {
+        let records = self
+            .entries
+            .iter()
+            .map(|entry| {
+                Ok(ChangelogRecord {
+                    id: entry.chunk.id.clone(),
+                    text: entry.chunk.text.clone(),
+                    metadata: encode_metadata(&entry.chunk.metadata)?,
+                    vector: entry.vector.clone(),
+                })
+            })
+            .collect::<Result<Vec<_>, VPackError>>()?;
+        Ok(bincode::serialize(&records)?)
+    }
+
+    pub(crate) fn decode(data: &[u8]) -> Result<Self, VPackError> {
+        let records: Vec<ChangelogRecord> = bincode::deserialize(data)?;
+        let entries = records
+            .into_iter()
+            .map(|record| {
+                Ok(EmbeddedChunk {
+                    chunk: crate::chunk::Chunk {
+                        id: record.id,
+                        text: record.text,
+                        metadata: decode_metadata(record.metadata)?,
+                    },
+                    vector: record.vector,
+                })
+            })
+            .collect::<Result<Vec<_>, VPackError>>()?;
+        Ok(Self::new(entries))
+    }
+}
+
+/// Split changelog chunks from content chunks.
+pub(crate) fn partition(chunks: Vec<EmbeddedChunk>) -> (Vec<EmbeddedChunk>, Changelog) {
+    let (changelog, content) = chunks
+        .into_iter()
+        .partition(|chunk| is_changelog(&chunk.chunk.metadata));
+    (content, Changelog::new(changelog))
+}
+
+/// The system clock in Unix milliseconds.
+#[cfg(not(all(target_arch = "wasm32", feature = "wasm")))]
+pub(crate) fn now_ms() -> i64 {
+    use std::time::{SystemTime, UNIX_EPOCH};
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_millis() as i64)
+}
+
+/// `SystemTime` panics on wasm32-unknown-unknown; ask the JS host instead.
+#[cfg(all(target_arch = "wasm32", feature = "wasm"))]
+pub(crate) fn now_ms() -> i64 {
+    js_sys::Date::now() as i64
+}
+
+/// Section order: `built_at`, then id.
+fn order_key(entry: &EmbeddedChunk) -> (Option<&str>, &str) {
+    let built_at = entry.chunk.metadata.extra.get("built_at");
+    (built_at.and_then(|v| v.as_str()), &entry.chunk.id)
+}
+
+/// `built_at + ttl_days` in Unix milliseconds. None when the chunk has no
+/// TTL or its `built_at` does not parse.
+fn expires_at(metadata: &ChunkMetadata) -> Option<i64> {
+    let ttl_days = metadata.extra.get("ttl_days")?.as_f64()?;
+    let built_at = parse_timestamp(metadata.extra.get("built_at")?.as_str()?)?;
+    Some(built_at + (ttl_days * MILLIS_PER_DAY as f64) as i64)
+}
+
+/// ISO 8601 date or date-time in Unix milliseconds: `YYYY-MM-DD`, optionally
+/// followed by `THH:MM[:SS[.fff]]` and `Z` or a `±HH:MM` offset. A date
+/// without an offset is UTC, as with `Date.parse`.
+pub(crate) fn parse_timestamp(text: &str) -> Option<i64> {
+    let number = |s: &str| -> Option<i64> {
+        s.bytes()
+            .all(|b| b.is_ascii_digit())
+            .then(|| s.parse().ok())
+            .flatten()
+    };
+    let date = text.get(..10)?;
+    let (year, month, day) = (
+        number(date.get(..4)?)?,
+        number(date.get(5..7)?)?,
+        number(date.get(8..10)?)?,
+    );
+    if &date[4..5] != "-"
+        || &date[7..8] != "-"
+        || !(1..=12).contains(&month)
+        || !(1..=31).contains(&day)
+    {
+        return None;
+    }
+    let mut millis = days_from_civil(year, month, day) * MILLIS_PER_DAY;
+
+    let rest = &text[10..];
+    if rest.is_empty() {
+        return Some(millis);
+    }
+    let rest = rest.strip_prefix(['T', ' '])?;
+    let (time, offset) = match rest.find(['Z', '+', '-']) {
+        Some(at) => rest.split_at(at),
+        None => (rest, ""),
+    };
+    let mut fields = time.splitn(3, ':');
+    let hours = number(fields.next()?)?;
+    let minutes = number(fields.next()?)?;
+    let (seconds, fraction) = match fields.next() {
+        Some(seconds) => match seconds.split_once('.') {
+            Some((whole, fraction)) => (number(whole)?, fraction),
+            None => (number(seconds)?, ""),
+        },
+        None => (0, ""),
+    };
+    let fraction_ms = match fraction {
+        "" => 0,
+        digits => number(&format!("{digits:0<3}")[..3])?,
+    };
+    millis += ((hours * 60 + minutes) * 60 + seconds) * 1000 + fraction_ms;
+
+    match offset {
+        "" | "Z" => Some(millis),
+        _ => {
+            let sign = if offset.starts_with('-') { -1 } else { 1 };
+            let digits = offset[1..].replace(':', "");
+            if digits.len() != 4 {
+                return None;
+            }
+            let minutes = number(&digits[..2])? * 60 + number(&digits[2..])?;
+            Some(millis - sign * minutes * 60_000)
+        }
+    }
+}
+
+/// Days since 1970-01-01 in the proleptic Gregorian calendar.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_iso_timestamps() {
+        assert_eq!(parse_timestamp("1970-01-01"), Some(0));
+        assert_eq!(
+            parse_timestamp("1970-01-02T00:00:00Z"),
+            Some(MILLIS_PER_DAY)
+        );
+        assert_eq!(parse_timestamp("2000-03-01"), Some(951_868_800_000));
+        assert_eq!(
+            parse_timestamp("2026-02-27T06:00:00.250Z"),
+            Some(1_772_172_000_250)
+        );
+        assert_eq!(
+            parse_timestamp("2026-02-27T08:00:00+02:00"),
+            parse_timestamp("2026-02-27T06:00:00Z")
+        );
+        assert_eq!(
+            parse_timestamp("2026-02-27T01:30-04:30"),
+            parse_timestamp("2026-02-27T06:00Z")
+        );
+        assert_eq!(parse_timestamp("yesterday"), None);
+        assert_eq!(parse_timestamp("2026-13-01"), None);
+    }
+}
@@ -1,7 +1,7 @@
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct ChunkMetadata {
     pub source_plugin: String,
     pub source_id: String,
@@ -14,7 +14,7 @@ pub struct ChunkMetadata {
     pub extra: HashMap<String, serde_json::Value>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Chunk {
     /// Deterministic ID: sha256(source_id + char_offset)
     pub id: String,
@@ -23,7 +23,7 @@ pub struct Chunk {
     pub metadata: ChunkMetadata,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct EmbeddedChunk {
     #[serde(flatten)]
     pub chunk: Chunk,
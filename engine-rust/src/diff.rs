@@ -0,0 +1,118 @@
+// diff.rs — ChunkDiff between two packs (RFC-0004 §5)
+//
+// Chunks are matched by their deterministic ids. A chunk in both packs is
+// modified when its text, metadata or vector changed. Deleted chunks that were
+// not compacted away yet are ignored. Every list is in chunk-ID order, so the
+// same two packs always give the same diff. Vectors are the stored ones, so
+// a cosine pack with f32 vectors reports them at unit length.
+
+use crate::chunk::{Chunk, ChunkMetadata, EmbeddedChunk};
+use crate::error::VPackError;
+use crate::index::VPackIndex;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ChunkDiff {
+    /// Chunks in the new pack whose id is not in the old one.
+    pub added: Vec<EmbeddedChunk>,
+    /// Chunks in both packs whose text, metadata or vector changed.
+    pub modified: Vec<ModifiedChunk>,
+    /// Chunks in the old pack whose id is not in the new one.
+    pub removed: Vec<RemovedChunk>,
+    /// `version` from the old pack's manifest.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub base_version: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ModifiedChunk {
+    /// The old version. Its vector is not included.
+    pub before: Chunk,
+    pub after: EmbeddedChunk,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RemovedChunk {
+    pub id: String,
+    pub text: String,
+    pub metadata: ChunkMetadata,
+}
+
+/// The rows a diff refers to, each list in chunk-ID order.
+#[derive(Debug, Default)]
+pub(crate) struct RowDiff {
+    /// Rows of the new pack.
+    pub(crate) added: Vec<usize>,
+    /// (old row, new row).
+    pub(crate) modified: Vec<(usize, usize)>,
+    /// Rows of the old pack.
+    pub(crate) removed: Vec<usize>,
+}
+
+/// What changed from `old` to `new`.
+pub fn diff(old: &VPackIndex, new: &VPackIndex) -> Result<ChunkDiff, VPackError> {
+    let rows = diff_rows(old, new)?;
+    let embedded = |index: &VPackIndex, row: usize| {
+        Ok(EmbeddedChunk {
+            chunk: index.chunks.to_chunk(row)?,
+            vector: index.vectors.get(row).to_vec(),
+        })
+    };
+    Ok(ChunkDiff {
+        added: rows
+            .added
+            .iter()
+            .map(|&row| embedded(new, row))
+            .collect::<Result<_, VPackError>>()?,
+        modified: rows
+            .modified
+            .iter()
+            .map(|&(before, after)| {
+                Ok(ModifiedChunk {
+                    before: old.chunks.to_chunk(before)?,
+                    after: embedded(new, after)?,
+                })
+            })
+            .collect::<Result<_, VPackError>>()?,
+        removed: rows
+            .removed
+            .iter()
+            .map(|&row| {
+                let chunk = old.chunks.to_chunk(row)?;
+                Ok(RemovedChunk {
+                    id: chunk.id,
+                    text: chunk.text,
+                    metadata: chunk.metadata,
+                })
+            })
+            .collect::<Result<_, VPackError>>()?,
+        base_version: old.manifest()["version"].as_str().map(str::to_string),
+    })
+}
+
+pub(crate) fn diff_rows(old: &VPackIndex, new: &VPackIndex) -> Result<RowDiff, VPackError> {
+    let old_rows: BTreeMap<&str, usize> = old.live_rows().into_iter().collect();
+    let new_rows: BTreeMap<&str, usize> = new.live_rows().into_iter().collect();
+
+    let mut diff = RowDiff::default();
+    for (id, &new_row) in &new_rows {
+        match old_rows.get(id) {
+            None => diff.added.push(new_row),
+            Some(&old_row) => {
+                let changed = old.chunks.text(old_row)? != new.chunks.text(new_row)?
+                    || old.chunks.metadata(old_row) != new.chunks.metadata(new_row)
+                    || old.vectors.get(old_row) != new.vectors.get(new_row);
+                if changed {
+                    diff.modified.push((old_row, new_row));
+                }
+            }
+        }
+    }
+    diff.removed = old_rows
+        .iter()
+        .filter(|(id, _)| !new_rows.contains_key(*id))
+        .map(|(_, &row)| row)
+        .collect();
+    Ok(diff)
+}
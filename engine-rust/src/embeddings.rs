@@ -1,38 +1,48 @@
 use crate::error::VPackError;
-use fastembed::{EmbeddingModel, TextEmbedding, TextInitOptions};
+use crate::hash::{same_hash, sha256_file, to_hex};
+use fastembed::{get_cache_dir, EmbeddingModel, TextEmbedding, TextInitOptions};
 use once_cell::sync::Lazy;
 use serde::Deserialize;
 use serde_json::Value;
 use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 use std::sync::Mutex;
 
 static EMBEDDERS: Lazy<Mutex<HashMap<String, TextEmbedding>>> =
     Lazy::new(|| Mutex::new(HashMap::new()));
 
+/// Weights hashes already computed this process, keyed by file path.
+static WEIGHT_HASHES: Lazy<Mutex<HashMap<PathBuf, String>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
 #[derive(Debug, Deserialize)]
 pub struct FastembedConfig {
     pub model: String,
+    /// `sha256:<hex>` of the ONNX weights (RFC-0001 §8.3). When set, the
+    /// local weights are hashed before the first embedding and must match.
+    pub model_hash: Option<String>,
     pub dimensions: Option<usize>,
     pub provider: Option<String>,
     pub batch_size: Option<usize>,
     pub max_length: Option<usize>,
+    /// Model cache directory. Defaults to fastembed's (`FASTEMBED_CACHE_DIR`).
+    pub cache_dir: Option<String>,
 }
 
 pub fn embed_texts(config: Value, texts: Vec<String>) -> Result<Vec<Vec<f32>>, VPackError> {
-    let config: FastembedConfig = serde_json::from_value(config)
-        .map_err(|err| VPackError::UnknownModel(err.to_string()))?;
-
-    if let Some(provider) = &config.provider {
-        if provider != "fastembed" {
-            return Err(VPackError::UnknownModel(format!(
-                "Embedding provider '{}' is not supported by fastembed",
-                provider
-            )));
+    let config = parse_config(config)?;
+    let model = resolve_model(&config.model)?;
+    let mut guard = get_or_init_embedder(&config, model.clone())?;
+    if let Some(expected) = &config.model_hash {
+        let got = weights_hash(&cache_dir(&config), &model)?;
+        if !same_hash(expected, &got) {
+            return Err(VPackError::ModelHashMismatch {
+                model: config.model.clone(),
+                expected: expected.clone(),
+                got,
+            });
         }
     }
-
-    let model = resolve_model(&config.model)?;
-    let mut guard = get_or_init_embedder(&config.model, model, config.max_length)?;
     let embedder = guard
         .get_mut(&config.model)
         .ok_or_else(|| VPackError::UnknownModel("embedder cache missing".to_string()))?;
@@ -42,7 +52,7 @@ pub fn embed_texts(config: Value, texts: Vec<String>) -> Result<Vec<Vec<f32>>, V
 
     for batch in texts.chunks(batch_size) {
         let batch_vecs = embedder
-            .embed(batch.to_vec(), Some(batch_size))
+            .embed(batch, Some(batch_size))
             .map_err(|err| VPackError::UnknownModel(err.to_string()))?;
         vectors.extend(batch_vecs);
     }
@@ -61,26 +71,92 @@ pub fn embed_texts(config: Value, texts: Vec<String>) -> Result<Vec<Vec<f32>>, V
     Ok(vectors)
 }
 
+/// `sha256:<hex>` of the local ONNX weights for `config.model` — the value to
+/// pin as `model_hash` in the manifest. Downloads the model if it is not
+/// cached yet.
+pub fn model_hash(config: Value) -> Result<String, VPackError> {
+    let config = parse_config(config)?;
+    let model = resolve_model(&config.model)?;
+    let cache_dir = cache_dir(&config);
+    if weights_path(&cache_dir, &model).is_none() {
+        drop(get_or_init_embedder(&config, model.clone())?);
+    }
+    weights_hash(&cache_dir, &model)
+}
+
+fn parse_config(config: Value) -> Result<FastembedConfig, VPackError> {
+    let config: FastembedConfig = serde_json::from_value(config)
+        .map_err(|err| VPackError::UnknownModel(err.to_string()))?;
+
+    if let Some(provider) = &config.provider {
+        if provider != "fastembed" {
+            return Err(VPackError::UnknownModel(format!(
+                "Embedding provider '{}' is not supported by fastembed",
+                provider
+            )));
+        }
+    }
+    Ok(config)
+}
+
+fn cache_dir(config: &FastembedConfig) -> PathBuf {
+    PathBuf::from(config.cache_dir.clone().unwrap_or_else(get_cache_dir))
+}
+
 fn get_or_init_embedder(
-    key: &str,
+    config: &FastembedConfig,
     model: EmbeddingModel,
-    max_length: Option<usize>,
 ) -> Result<std::sync::MutexGuard<'static, HashMap<String, TextEmbedding>>, VPackError> {
     let mut guard = EMBEDDERS
         .lock()
         .map_err(|_| VPackError::UnknownModel("embedder cache lock poisoned".to_string()))?;
-    if !guard.contains_key(key) {
-        let mut options = TextInitOptions::new(model);
-        if let Some(max_len) = max_length {
+    if !guard.contains_key(&config.model) {
+        let mut options = TextInitOptions::new(model).with_cache_dir(cache_dir(config));
+        if let Some(max_len) = config.max_length {
             options = options.with_max_length(max_len);
         }
         let embedder = TextEmbedding::try_new(options)
             .map_err(|err| VPackError::UnknownModel(err.to_string()))?;
-        guard.insert(key.to_string(), embedder);
+        guard.insert(config.model.clone(), embedder);
     }
     Ok(guard)
 }
 
+/// Hash the cached weights file of `model`, once per process.
+fn weights_hash(cache_dir: &Path, model: &EmbeddingModel) -> Result<String, VPackError> {
+    let path = weights_path(cache_dir, model).ok_or_else(|| {
+        VPackError::UnknownModel(format!(
+            "weights for {model:?} not found in {}",
+            cache_dir.display()
+        ))
+    })?;
+    let mut hashes = WEIGHT_HASHES
+        .lock()
+        .map_err(|_| VPackError::UnknownModel("weights hash cache lock poisoned".to_string()))?;
+    if let Some(hash) = hashes.get(&path) {
+        return Ok(hash.clone());
+    }
+    let digest = sha256_file(&path)
+        .map_err(|err| VPackError::UnknownModel(format!("{}: {err}", path.display())))?;
+    let hash = format!("sha256:{}", to_hex(&digest));
+    hashes.insert(path, hash.clone());
+    Ok(hash)
+}
+
+/// Location of the ONNX weights in the Hugging Face cache layout fastembed
+/// downloads into: `models--{org}--{name}/snapshots/{revision}/{model_file}`,
+/// with the revision read from `refs/main`.
+fn weights_path(cache_dir: &Path, model: &EmbeddingModel) -> Option<PathBuf> {
+    let info = TextEmbedding::get_model_info(model).ok()?;
+    let repo = cache_dir.join(format!("models--{}", info.model_code.replace('/', "--")));
+    let revision = std::fs::read_to_string(repo.join("refs").join("main")).ok()?;
+    let path = repo
+        .join("snapshots")
+        .join(revision.trim())
+        .join(&info.model_file);
+    path.is_file().then_some(path)
+}
+
 fn resolve_model(model_id: &str) -> Result<EmbeddingModel, VPackError> {
     let model = match model_id {
         "sentence-transformers/all-MiniLM-L6-v2" => EmbeddingModel::AllMiniLML6V2,
@@ -100,3 +176,27 @@ fn resolve_model(model_id: &str) -> Result<EmbeddingModel, VPackError> {
     };
     Ok(model)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hashes_weights_in_the_hugging_face_cache_layout() {
+        let cache = std::env::temp_dir().join(format!("vpack-weights-{}", std::process::id()));
+        let repo = cache.join("models--Qdrant--all-MiniLM-L6-v2-onnx");
+        std::fs::create_dir_all(repo.join("refs")).unwrap();
+        std::fs::create_dir_all(repo.join("snapshots/abc123")).unwrap();
+        std::fs::write(repo.join("refs/main"), "abc123\n").unwrap();
+        std::fs::write(repo.join("snapshots/abc123/model.onnx"), b"weights").unwrap();
+
+        let hash = weights_hash(&cache, &EmbeddingModel::AllMiniLML6V2).unwrap();
+        let expected = format!("sha256:{}", to_hex(&crate::hash::sha256(b"weights")));
+        assert_eq!(hash, expected);
+        let bare_upper = expected["sha256:".len()..].to_uppercase();
+        assert!(same_hash(&bare_upper, &hash));
+        assert!(weights_path(&cache, &EmbeddingModel::BGESmallENV15).is_none());
+
+        std::fs::remove_dir_all(&cache).unwrap();
+    }
+}
@@ -32,6 +32,23 @@ pub enum VPackError {
 
     #[error("invalid .vpack file: {0}")]
     InvalidFormat(String),
+
+    #[error("invalid manifest: {0}")]
+    ManifestInvalid(String),
+
+    #[error("chunk id '{0}' is already in the index")]
+    DuplicateChunkId(String),
+
+    /// The patch was made against another artifact. Download the full pack.
+    #[error("patch base mismatch: patch applies to {expected}, local pack is {got}")]
+    PatchBaseMismatch { expected: String, got: String },
+
+    /// The patch cannot be applied and nothing was changed. Download the full pack.
+    #[error("patch cannot be applied: {0}")]
+    PatchFailed(String),
+
+    #[error("invalid query options: {0}")]
+    InvalidQuery(String),
 }
 
 impl VPackError {
@@ -44,6 +61,11 @@ impl VPackError {
             VPackError::Serialize(_) => "SERIALIZE_FAILED",
             VPackError::UnknownModel(_) => "UNKNOWN_MODEL",
             VPackError::InvalidFormat(_) => "DESERIALIZE_FAILED",
+            VPackError::ManifestInvalid(_) => "MANIFEST_INVALID",
+            VPackError::DuplicateChunkId(_) => "DUPLICATE_CHUNK_ID",
+            VPackError::PatchBaseMismatch { .. } => "PATCH_BASE_MISMATCH",
+            VPackError::PatchFailed(_) => "PATCH_FAILED",
+            VPackError::InvalidQuery(_) => "INVALID_QUERY",
         }
     }
 }
@@ -0,0 +1,498 @@
+// ffi.rs — C ABI for the Python, Go and Ruby adapters (RFC-0001 §9.3)
+//
+// The header is include/vpack_engine.h, generated by cbindgen from this file
+// (see cbindgen.toml). Conventions:
+//
+// - Indexes and result sets are opaque handles, released with their
+//   `*_free` function. Strings returned to the caller are released with
+//   `vpack_string_free`.
+// - Fallible functions return a VPackStatus. On failure, the stable
+//   `VPackError::code()` string and the message are kept per thread and read
+//   with `vpack_last_error_code` / `vpack_last_error_message`.
+// - Panics never cross the boundary; they are reported as VPACK_STATUS_PANIC.
+
+use std::cell::RefCell;
+use std::ffi::{c_char, CStr, CString};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::ptr;
+
+use crate::error::VPackError;
+use crate::index::VPackIndex;
+use crate::query::{QueryOptions, QueryResult};
+use crate::serialize::{deserialize, deserialize_with_options, LoadOptions};
+
+/// Status returned by every fallible function. The values are stable.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VPackStatus {
+    Ok = 0,
+    DimensionMismatch = 1,
+    ModelMismatch = 2,
+    EmptyIndex = 3,
+    ModelHashMismatch = 4,
+    SerializeFailed = 5,
+    UnknownModel = 6,
+    DeserializeFailed = 7,
+    ManifestInvalid = 8,
+    DuplicateChunkId = 9,
+    PatchBaseMismatch = 10,
+    PatchFailed = 11,
+    InvalidQuery = 12,
+    /// A null pointer, invalid UTF-8 or malformed options JSON.
+    InvalidArgument = 100,
+    /// The engine panicked. The handle passed in should not be used again.
+    Panic = 101,
+}
+
+impl VPackStatus {
+    fn from_code(code: &str) -> Self {
+        match code {
+            "DIMENSION_MISMATCH" => Self::DimensionMismatch,
+            "MODEL_MISMATCH" => Self::ModelMismatch,
+            "EMPTY_INDEX" => Self::EmptyIndex,
+            "MODEL_HASH_MISMATCH" => Self::ModelHashMismatch,
+            "SERIALIZE_FAILED" => Self::SerializeFailed,
+            "UNKNOWN_MODEL" => Self::UnknownModel,
+            "DESERIALIZE_FAILED" => Self::DeserializeFailed,
+            "MANIFEST_INVALID" => Self::ManifestInvalid,
+            "DUPLICATE_CHUNK_ID" => Self::DuplicateChunkId,
+            "PATCH_BASE_MISMATCH" => Self::PatchBaseMismatch,
+            "PATCH_FAILED" => Self::PatchFailed,
+            "INVALID_QUERY" => Self::InvalidQuery,
+            "PANIC" => Self::Panic,
+            _ => Self::InvalidArgument,
+        }
+    }
+}
+
+/// Opaque index handle.
+pub struct VPackFfiIndex {
+    inner: VPackIndex,
+}
+
+/// One query result. Strings are NUL-terminated UTF-8 owned by the result
+/// set and valid until `vpack_results_free`.
+#[repr(C)]
+pub struct VPackResult {
+    pub chunk_id: *const c_char,
+    pub text: *const c_char,
+    /// Chunk metadata as a JSON object.
+    pub metadata_json: *const c_char,
+    pub score: f32,
+    pub similarity: f32,
+    pub rank: u32,
+}
+
+/// Opaque result set returned by `vpack_index_query`.
+pub struct VPackResults {
+    items: Vec<VPackResult>,
+    /// Backing storage for the pointers in `items`.
+    _strings: Vec<CString>,
+}
+
+struct LastError {
+    code: CString,
+    message: CString,
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<LastError>> = const { RefCell::new(None) };
+}
+
+/// Failure detail kept for `vpack_last_error_*`.
+struct FfiError {
+    code: &'static str,
+    message: String,
+}
+
+impl From<VPackError> for FfiError {
+    fn from(err: VPackError) -> Self {
+        Self {
+            code: err.code(),
+            message: err.to_string(),
+        }
+    }
+}
+
+fn invalid_argument(message: impl Into<String>) -> FfiError {
+    FfiError {
+        code: "INVALID_ARGUMENT",
+        message: message.into(),
+    }
+}
+
+/// Interior NULs cannot occur in engine messages, but never fail on them.
+fn c_string(text: impl Into<Vec<u8>>) -> CString {
+    let mut bytes = text.into();
+    bytes.retain(|&b| b != 0);
+    CString::new(bytes).unwrap_or_default()
+}
+
+fn set_last_error(err: FfiError) {
+    LAST_ERROR.with(|last| {
+        *last.borrow_mut() = Some(LastError {
+            code: c_string(err.code),
+            message: c_string(err.message),
+        });
+    });
+}
+
+/// Run `body`, turning errors and panics into a status.
+fn guard(body: impl FnOnce() -> Result<(), FfiError>) -> VPackStatus {
+    let err = match catch_unwind(AssertUnwindSafe(body)) {
+        Ok(Ok(())) => return VPackStatus::Ok,
+        Ok(Err(err)) => err,
+        Err(_) => FfiError {
+            code: "PANIC",
+            message: "vpack-engine panicked".to_string(),
+        },
+    };
+    let status = VPackStatus::from_code(err.code);
+    set_last_error(err);
+    status
+}
+
+unsafe fn str_arg<'a>(text: *const c_char, name: &str) -> Result<&'a str, FfiError> {
+    if text.is_null() {
+        return Err(invalid_argument(format!("{name} is null")));
+    }
+    CStr::from_ptr(text)
+        .to_str()
+        .map_err(|_| invalid_argument(format!("{name} is not valid UTF-8")))
+}
+
+unsafe fn options_arg(options_json: *const c_char) -> Result<QueryOptions, FfiError> {
+    if options_json.is_null() {
+        return Ok(QueryOptions::default());
+    }
+    serde_json::from_str(str_arg(options_json, "options_json")?)
+        .map_err(|err| invalid_argument(format!("invalid options JSON: {err}")))
+}
+
+unsafe fn load_options_arg(options_json: *const c_char) -> Result<LoadOptions, FfiError> {
+    if options_json.is_null() {
+        return Ok(LoadOptions::default());
+    }
+    serde_json::from_str(str_arg(options_json, "options_json")?)
+        .map_err(|err| invalid_argument(format!("invalid options JSON: {err}")))
+}
+
+unsafe fn index_arg<'a>(index: *const VPackFfiIndex) -> Result<&'a VPackIndex, FfiError> {
+    index
+        .as_ref()
+        .map(|index| &index.inner)
+        .ok_or_else(|| invalid_argument("index is null"))
+}
+
+unsafe fn write_out<T>(out: *mut *mut T, value: T) -> Result<(), FfiError> {
+    if out.is_null() {
+        return Err(invalid_argument("out pointer is null"));
+    }
+    *out = Box::into_raw(Box::new(value));
+    Ok(())
+}
+
+fn results_from(results: Vec<QueryResult>) -> Result<VPackResults, FfiError> {
+    let mut strings = Vec::with_capacity(results.len() * 3);
+    let mut items = Vec::with_capacity(results.len());
+    for result in results {
+        let metadata = serde_json::to_string(&result.chunk.metadata)
+            .map_err(|err| invalid_argument(format!("metadata is not JSON: {err}")))?;
+        let mut keep = |text: String| {
+            let text = c_string(text);
+            // The heap buffer does not move when the CString does.
+            let pointer = text.as_ptr();
+            strings.push(text);
+            pointer
+        };
+        items.push(VPackResult {
+            chunk_id: keep(result.chunk.id),
+            text: keep(result.chunk.text),
+            metadata_json: keep(metadata),
+            score: result.score,
+            similarity: result.similarity,
+            rank: result.rank as u32,
+        });
+    }
+    Ok(VPackResults {
+        items,
+        _strings: strings,
+    })
+}
+
+/// Load an index from `.vpack` bytes. The bytes are copied.
+///
+/// # Safety
+/// `bytes` must point to `len` readable bytes and `out` must be writable.
+#[no_mangle]
+pub unsafe extern "C" fn vpack_index_from_bytes(
+    bytes: *const u8,
+    len: usize,
+    out: *mut *mut VPackFfiIndex,
+) -> VPackStatus {
+    guard(|| {
+        if bytes.is_null() {
+            return Err(invalid_argument("bytes is null"));
+        }
+        let index = deserialize(std::slice::from_raw_parts(bytes, len))?;
+        write_out(out, VPackFfiIndex { inner: index })
+    })
+}
+
+/// Open a `.vpack` file through a memory map. The file must not change
+/// while the index is open.
+///
+/// # Safety
+/// `path` must be a NUL-terminated string and `out` must be writable.
+#[no_mangle]
+pub unsafe extern "C" fn vpack_index_open(
+    path: *const c_char,
+    out: *mut *mut VPackFfiIndex,
+) -> VPackStatus {
+    guard(|| {
+        let index = VPackIndex::open_mmap(str_arg(path, "path")?)?;
+        write_out(out, VPackFfiIndex { inner: index })
+    })
+}
+
+/// Like `vpack_index_from_bytes`, checking the pack against LoadOptions
+/// JSON, e.g. `{"model": "...", "modelHash": "sha256:..."}`. A pack pinning
+/// another model fails with VPACK_STATUS_MODEL_MISMATCH or
+/// VPACK_STATUS_MODEL_HASH_MISMATCH. `options_json` may be null.
+///
+/// # Safety
+/// As for `vpack_index_from_bytes`; `options_json` must be null or a
+/// NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn vpack_index_from_bytes_with_options(
+    bytes: *const u8,
+    len: usize,
+    options_json: *const c_char,
+    out: *mut *mut VPackFfiIndex,
+) -> VPackStatus {
+    guard(|| {
+        if bytes.is_null() {
+            return Err(invalid_argument("bytes is null"));
+        }
+        let options = load_options_arg(options_json)?;
+        let index = deserialize_with_options(std::slice::from_raw_parts(bytes, len), &options)?;
+        write_out(out, VPackFfiIndex { inner: index })
+    })
+}
+
+/// Like `vpack_index_open`, checking the pack against LoadOptions JSON as
+/// `vpack_index_from_bytes_with_options` does.
+///
+/// # Safety
+/// As for `vpack_index_open`; `options_json` must be null or a
+/// NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn vpack_index_open_with_options(
+    path: *const c_char,
+    options_json: *const c_char,
+    out: *mut *mut VPackFfiIndex,
+) -> VPackStatus {
+    guard(|| {
+        let options = load_options_arg(options_json)?;
+        let index = VPackIndex::open_mmap_with_options(str_arg(path, "path")?, &options)?;
+        write_out(out, VPackFfiIndex { inner: index })
+    })
+}
+
+/// Release an index. Null is ignored.
+///
+/// # Safety
+/// `index` must come from this library and not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn vpack_index_free(index: *mut VPackFfiIndex) {
+    if !index.is_null() {
+        drop(Box::from_raw(index));
+    }
+}
+
+/// Content chunks in the index. 0 for a null index.
+///
+/// # Safety
+/// `index` must be null or a live index handle.
+#[no_mangle]
+pub unsafe extern "C" fn vpack_index_chunk_count(index: *const VPackFfiIndex) -> usize {
+    index_arg(index).map_or(0, VPackIndex::chunk_count)
+}
+
+/// Vector dimensions of the index. 0 for a null index.
+///
+/// # Safety
+/// `index` must be null or a live index handle.
+#[no_mangle]
+pub unsafe extern "C" fn vpack_index_dimensions(index: *const VPackFfiIndex) -> usize {
+    index_arg(index).map_or(0, VPackIndex::dimensions)
+}
+
+/// The manifest as JSON, released with `vpack_string_free`.
+///
+/// # Safety
+/// `index` must be a live index handle and `out` must be writable.
+#[no_mangle]
+pub unsafe extern "C" fn vpack_index_manifest_json(
+    index: *const VPackFfiIndex,
+    out: *mut *mut c_char,
+) -> VPackStatus {
+    guard(|| {
+        let json = serde_json::to_string(index_arg(index)?.manifest())
+            .map_err(|err| invalid_argument(format!("manifest is not JSON: {err}")))?;
+        write_string(out, json)
+    })
+}
+
+/// Query with a vector of `len` floats. `options_json` is a QueryOptions
+/// object as JSON, or null for the defaults.
+///
+/// # Safety
+/// `vector` must point to `len` floats, `options_json` must be null or a
+/// NUL-terminated string, and `out` must be writable.
+#[no_mangle]
+pub unsafe extern "C" fn vpack_index_query(
+    index: *const VPackFfiIndex,
+    vector: *const f32,
+    len: usize,
+    options_json: *const c_char,
+    out: *mut *mut VPackResults,
+) -> VPackStatus {
+    guard(|| {
+        let results = query(index, vector, len, options_json)?;
+        write_out(out, results_from(results)?)
+    })
+}
+
+/// Like `vpack_index_query`, but the results are a JSON array of
+/// QueryResult objects, released with `vpack_string_free`.
+///
+/// # Safety
+/// As for `vpack_index_query`.
+#[no_mangle]
+pub unsafe extern "C" fn vpack_index_query_json(
+    index: *const VPackFfiIndex,
+    vector: *const f32,
+    len: usize,
+    options_json: *const c_char,
+    out: *mut *mut c_char,
+) -> VPackStatus {
+    guard(|| {
+        let results = query(index, vector, len, options_json)?;
+        let json = serde_json::to_string(&results)
+            .map_err(|err| invalid_argument(format!("results are not JSON: {err}")))?;
+        write_string(out, json)
+    })
+}
+
+/// Embed `text` with the model pinned in the manifest and query with it.
+///
+/// # Safety
+/// `text` must be a NUL-terminated string; otherwise as for
+/// `vpack_index_query`.
+#[cfg(feature = "embed")]
+#[no_mangle]
+pub unsafe extern "C" fn vpack_index_query_text(
+    index: *const VPackFfiIndex,
+    text: *const c_char,
+    options_json: *const c_char,
+    out: *mut *mut VPackResults,
+) -> VPackStatus {
+    guard(|| {
+        let index = index_arg(index)?;
+        let results = index.query_text(str_arg(text, "text")?, options_arg(options_json)?)?;
+        write_out(out, results_from(results)?)
+    })
+}
+
+unsafe fn query(
+    index: *const VPackFfiIndex,
+    vector: *const f32,
+    len: usize,
+    options_json: *const c_char,
+) -> Result<Vec<QueryResult>, FfiError> {
+    let index = index_arg(index)?;
+    if vector.is_null() {
+        return Err(invalid_argument("vector is null"));
+    }
+    let vector = std::slice::from_raw_parts(vector, len);
+    Ok(index.query(vector, options_arg(options_json)?)?)
+}
+
+unsafe fn write_string(out: *mut *mut c_char, text: String) -> Result<(), FfiError> {
+    if out.is_null() {
+        return Err(invalid_argument("out pointer is null"));
+    }
+    *out = c_string(text).into_raw();
+    Ok(())
+}
+
+/// Number of results in the set. 0 for null.
+///
+/// # Safety
+/// `results` must be null or a live result set.
+#[no_mangle]
+pub unsafe extern "C" fn vpack_results_len(results: *const VPackResults) -> usize {
+    results.as_ref().map_or(0, |results| results.items.len())
+}
+
+/// The result at `i`, best first, or null when out of range. The pointer is
+/// valid until `vpack_results_free`.
+///
+/// # Safety
+/// `results` must be null or a live result set.
+#[no_mangle]
+pub unsafe extern "C" fn vpack_results_get(
+    results: *const VPackResults,
+    i: usize,
+) -> *const VPackResult {
+    results
+        .as_ref()
+        .and_then(|results| results.items.get(i))
+        .map_or(ptr::null(), |item| item as *const VPackResult)
+}
+
+/// Release a result set. Null is ignored.
+///
+/// # Safety
+/// `results` must come from this library and not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn vpack_results_free(results: *mut VPackResults) {
+    if !results.is_null() {
+        drop(Box::from_raw(results));
+    }
+}
+
+/// Release a string returned by this library. Null is ignored.
+///
+/// # Safety
+/// `text` must come from this library and not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn vpack_string_free(text: *mut c_char) {
+    if !text.is_null() {
+        drop(CString::from_raw(text));
+    }
+}
+
+/// `VPackError::code()` of the last failure on this thread, e.g.
+/// `"DIMENSION_MISMATCH"`, or null if nothing failed yet. Valid until the
+/// next failure on this thread.
+#[no_mangle]
+pub extern "C" fn vpack_last_error_code() -> *const c_char {
+    LAST_ERROR.with(|last| {
+        last.borrow()
+            .as_ref()
+            .map_or(ptr::null(), |err| err.code.as_ptr())
+    })
+}
+
+/// Message of the last failure on this thread, or null. Valid until the
+/// next failure on this thread.
+#[no_mangle]
+pub extern "C" fn vpack_last_error_message() -> *const c_char {
+    LAST_ERROR.with(|last| {
+        last.borrow()
+            .as_ref()
+            .map_or(ptr::null(), |err| err.message.as_ptr())
+    })
+}
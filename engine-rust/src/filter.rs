@@ -0,0 +1,414 @@
+// filter.rs — filter expressions compiled to typed predicates
+//
+// A `FilterExpr` is compiled once per query. Each leaf resolves its field path
+// up front: known `ChunkMetadata` fields become direct field reads, anything
+// else is looked up in `extra` and walked through nested JSON objects. Leaves
+// compare borrowed values against the filter's JSON operand, so matching a
+// chunk allocates nothing.
+//
+// Semantics match evaluating the filter against `serde_json::to_value` of the
+// metadata: unset optional fields read as JSON null, unknown paths read as
+// missing.
+//
+// Query planning: `eq` and `in` leaves on fields with a metadata index, and
+// `and`/`or` over such leaves, resolve to a candidate row set from postings
+// (see metadata_index.rs). The full filter is still checked on every
+// candidate, so unindexed parts of an `and` stay correct.
+
+use crate::chunk::ChunkMetadata;
+use crate::metadata_index::MetadataIndex;
+use crate::query::{FilterExpr, FilterOp, MetadataFilter};
+use serde_json::Value;
+
+pub(crate) enum CompiledFilter {
+    And(Vec<CompiledFilter>),
+    Or(Vec<CompiledFilter>),
+    Not(Box<CompiledFilter>),
+    Leaf(Predicate),
+}
+
+pub(crate) struct Predicate {
+    field: FieldPath,
+    test: Test,
+}
+
+/// A dotted filter field, resolved against `ChunkMetadata`.
+pub(crate) struct FieldPath {
+    /// The field as written, which is also its metadata index name.
+    name: String,
+    field: Field,
+    /// Keys below the field, for nested JSON in `extra`.
+    path: Vec<String>,
+}
+
+enum Field {
+    SourcePlugin,
+    SourceId,
+    SourceUrl,
+    CreatedAt,
+    UpdatedAt,
+    PackName,
+    ChunkerPlugin,
+    Extra(String),
+}
+
+enum Test {
+    /// `eq` without a value: the field must be missing.
+    Missing,
+    /// `neq` without a value: the field must be present, even if null.
+    Present,
+    Eq(Value),
+    Neq(Value),
+    In(Vec<Value>),
+    Nin(Vec<Value>),
+    Gte(f64),
+    Lte(f64),
+    Exists,
+    /// Operand of the wrong type, e.g. `in` without an array.
+    Never,
+}
+
+/// A field value borrowed from the metadata.
+#[derive(Clone, Copy)]
+enum FieldValue<'a> {
+    Str(&'a str),
+    Json(&'a Value),
+    Null,
+    Missing,
+}
+
+impl CompiledFilter {
+    pub(crate) fn new(expr: &FilterExpr) -> Self {
+        match expr {
+            FilterExpr::And { and } => CompiledFilter::And(and.iter().map(Self::new).collect()),
+            FilterExpr::Or { or } => CompiledFilter::Or(or.iter().map(Self::new).collect()),
+            FilterExpr::Not { not } => CompiledFilter::Not(Box::new(Self::new(not))),
+            FilterExpr::Leaf(filter) => CompiledFilter::Leaf(Predicate::new(filter)),
+        }
+    }
+
+    /// Rows the filter can match, sorted ascending, when metadata indexes
+    /// narrow it down. `None` means every row has to be checked.
+    pub(crate) fn candidates(&self, index: &MetadataIndex) -> Option<Vec<u32>> {
+        match self {
+            CompiledFilter::And(children) => children
+                .iter()
+                .filter_map(|child| child.candidates(index))
+                .reduce(|a, b| intersect(&a, &b)),
+            CompiledFilter::Or(children) => children
+                .iter()
+                .map(|child| child.candidates(index))
+                .reduce(|a, b| Some(union(&a?, &b?)))
+                .unwrap_or(Some(Vec::new())),
+            CompiledFilter::Not(_) => None,
+            CompiledFilter::Leaf(predicate) => predicate.candidates(index),
+        }
+    }
+
+    pub(crate) fn matches(&self, metadata: &ChunkMetadata) -> bool {
+        match self {
+            CompiledFilter::And(children) => children.iter().all(|child| child.matches(metadata)),
+            CompiledFilter::Or(children) => children.iter().any(|child| child.matches(metadata)),
+            CompiledFilter::Not(child) => !child.matches(metadata),
+            CompiledFilter::Leaf(predicate) => predicate.matches(metadata),
+        }
+    }
+}
+
+impl Predicate {
+    pub(crate) fn new(filter: &MetadataFilter) -> Self {
+        let number = |value: &Option<Value>| value.as_ref().and_then(Value::as_f64);
+        let test = match (&filter.op, &filter.value) {
+            (FilterOp::Eq, None) => Test::Missing,
+            (FilterOp::Eq, Some(value)) => Test::Eq(value.clone()),
+            (FilterOp::Neq, None) => Test::Present,
+            (FilterOp::Neq, Some(value)) => Test::Neq(value.clone()),
+            (FilterOp::In, Some(Value::Array(values))) => Test::In(values.clone()),
+            (FilterOp::Nin, Some(Value::Array(values))) => Test::Nin(values.clone()),
+            (FilterOp::In | FilterOp::Nin, _) => Test::Never,
+            (FilterOp::Gte, value) => number(value).map_or(Test::Never, Test::Gte),
+            (FilterOp::Lte, value) => number(value).map_or(Test::Never, Test::Lte),
+            (FilterOp::Exists, _) => Test::Exists,
+        };
+        Self {
+            field: FieldPath::new(&filter.field),
+            test,
+        }
+    }
+
+    pub(crate) fn matches(&self, metadata: &ChunkMetadata) -> bool {
+        let value = self.field.resolve(metadata);
+        match &self.test {
+            Test::Missing => matches!(value, FieldValue::Missing),
+            Test::Present => !matches!(value, FieldValue::Missing),
+            Test::Eq(expected) => value.equals(expected),
+            Test::Neq(expected) => !value.equals(expected),
+            Test::In(values) => value.is_present() && values.iter().any(|item| value.equals(item)),
+            Test::Nin(values) => {
+                value.is_present() && values.iter().all(|item| !value.equals(item))
+            }
+            Test::Gte(bound) => value.as_f64().is_some_and(|x| x >= *bound),
+            Test::Lte(bound) => value.as_f64().is_some_and(|x| x <= *bound),
+            Test::Exists => match value {
+                FieldValue::Str(_) => true,
+                FieldValue::Json(json) => !json.is_null(),
+                FieldValue::Null | FieldValue::Missing => false,
+            },
+            Test::Never => false,
+        }
+    }
+
+    /// Rows that can match, from the field's postings. `None` when the
+    /// field has no index or the test is not an equality.
+    fn candidates(&self, index: &MetadataIndex) -> Option<Vec<u32>> {
+        match &self.test {
+            Test::Eq(value) => index.rows(&self.field.name, std::slice::from_ref(value)),
+            Test::In(values) => index.rows(&self.field.name, values),
+            _ => None,
+        }
+    }
+}
+
+impl FieldPath {
+    pub(crate) fn new(name: &str) -> Self {
+        let mut keys = name.split('.');
+        let field = match keys.next().unwrap_or_default() {
+            "source_plugin" => Field::SourcePlugin,
+            "source_id" => Field::SourceId,
+            "source_url" => Field::SourceUrl,
+            "created_at" => Field::CreatedAt,
+            "updated_at" => Field::UpdatedAt,
+            "pack_name" => Field::PackName,
+            "chunker_plugin" => Field::ChunkerPlugin,
+            other => Field::Extra(other.to_string()),
+        };
+        Self {
+            name: name.to_string(),
+            field,
+            path: keys.map(str::to_string).collect(),
+        }
+    }
+
+    /// Posting key of this field's value in `metadata`: the value as compact
+    /// JSON, so keys compare exactly like filter operands. `None` if missing.
+    pub(crate) fn key(&self, metadata: &ChunkMetadata) -> Option<String> {
+        match self.resolve(metadata) {
+            FieldValue::Str(text) => Some(value_key(&Value::from(text))),
+            FieldValue::Json(json) => Some(value_key(json)),
+            FieldValue::Null => Some(value_key(&Value::Null)),
+            FieldValue::Missing => None,
+        }
+    }
+
+    fn resolve<'a>(&self, metadata: &'a ChunkMetadata) -> FieldValue<'a> {
+        let optional = |value: &'a Option<String>| match value {
+            Some(text) => FieldValue::Str(text),
+            None => FieldValue::Null,
+        };
+        let top = match &self.field {
+            Field::SourcePlugin => FieldValue::Str(&metadata.source_plugin),
+            Field::SourceId => FieldValue::Str(&metadata.source_id),
+            Field::SourceUrl => optional(&metadata.source_url),
+            Field::CreatedAt => optional(&metadata.created_at),
+            Field::UpdatedAt => optional(&metadata.updated_at),
+            Field::PackName => FieldValue::Str(&metadata.pack_name),
+            Field::ChunkerPlugin => FieldValue::Str(&metadata.chunker_plugin),
+            Field::Extra(key) => metadata
+                .extra
+                .get(key)
+                .map_or(FieldValue::Missing, FieldValue::Json),
+        };
+        if self.path.is_empty() {
+            return top;
+        }
+        let FieldValue::Json(mut current) = top else {
+            return FieldValue::Missing;
+        };
+        for key in &self.path {
+            match current.get(key.as_str()) {
+                Some(next) => current = next,
+                None => return FieldValue::Missing,
+            }
+        }
+        FieldValue::Json(current)
+    }
+}
+
+/// Intersection of two ascending row lists.
+fn intersect(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut out = Vec::with_capacity(a.len().min(b.len()));
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() && j < b.len() {
+        match a[i].cmp(&b[j]) {
+            std::cmp::Ordering::Less => i += 1,
+            std::cmp::Ordering::Greater => j += 1,
+            std::cmp::Ordering::Equal => {
+                out.push(a[i]);
+                i += 1;
+                j += 1;
+            }
+        }
+    }
+    out
+}
+
+/// Union of two ascending row lists.
+pub(crate) fn union(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut out = Vec::with_capacity(a.len() + b.len());
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() && j < b.len() {
+        match a[i].cmp(&b[j]) {
+            std::cmp::Ordering::Less => {
+                out.push(a[i]);
+                i += 1;
+            }
+            std::cmp::Ordering::Greater => {
+                out.push(b[j]);
+                j += 1;
+            }
+            std::cmp::Ordering::Equal => {
+                out.push(a[i]);
+                i += 1;
+                j += 1;
+            }
+        }
+    }
+    out.extend_from_slice(&a[i..]);
+    out.extend_from_slice(&b[j..]);
+    out
+}
+
+/// Posting key of a JSON value. serde_json maps are sorted, so equal values
+/// always produce equal keys.
+pub(crate) fn value_key(value: &Value) -> String {
+    value.to_string()
+}
+
+impl FieldValue<'_> {
+    fn is_present(self) -> bool {
+        !matches!(self, FieldValue::Missing)
+    }
+
+    fn equals(self, expected: &Value) -> bool {
+        match self {
+            FieldValue::Str(text) => expected.as_str() == Some(text),
+            FieldValue::Json(json) => json == expected,
+            FieldValue::Null => expected.is_null(),
+            FieldValue::Missing => false,
+        }
+    }
+
+    fn as_f64(self) -> Option<f64> {
+        match self {
+            FieldValue::Json(json) => json.as_f64(),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::collections::HashMap;
+
+    /// The pre-compilation evaluator: serialize the metadata, then walk it.
+    fn reference(metadata: &ChunkMetadata, filter: &MetadataFilter) -> bool {
+        let meta = serde_json::to_value(metadata).unwrap();
+        let mut value = Some(&meta);
+        for key in filter.field.split('.') {
+            value = value
+                .and_then(|v| v.as_object())
+                .and_then(|map| map.get(key));
+        }
+        let number = |v: Option<&Value>| v.and_then(Value::as_f64);
+        match filter.op {
+            FilterOp::Eq => match &filter.value {
+                Some(expected) => value == Some(expected),
+                None => value.is_none(),
+            },
+            FilterOp::Neq => match &filter.value {
+                Some(expected) => value != Some(expected),
+                None => value.is_some(),
+            },
+            FilterOp::In => match &filter.value {
+                Some(Value::Array(values)) => value.is_some_and(|v| values.contains(v)),
+                _ => false,
+            },
+            FilterOp::Nin => match &filter.value {
+                Some(Value::Array(values)) => value.is_some_and(|v| !values.contains(v)),
+                _ => false,
+            },
+            FilterOp::Gte => match (number(value), number(filter.value.as_ref())) {
+                (Some(a), Some(b)) => a >= b,
+                _ => false,
+            },
+            FilterOp::Lte => match (number(value), number(filter.value.as_ref())) {
+                (Some(a), Some(b)) => a <= b,
+                _ => false,
+            },
+            FilterOp::Exists => !matches!(value, None | Some(Value::Null)),
+        }
+    }
+
+    #[test]
+    fn compiled_predicates_match_json_evaluation() {
+        let mut extra = HashMap::new();
+        extra.insert("year".to_string(), json!(2025));
+        extra.insert("tags".to_string(), json!({ "team": "core", "none": null }));
+        extra.insert("empty".to_string(), Value::Null);
+        let metadata = ChunkMetadata {
+            source_plugin: "@vpack/source-fs".to_string(),
+            source_id: "doc".to_string(),
+            source_url: None,
+            created_at: Some("2026-01-01".to_string()),
+            updated_at: None,
+            pack_name: "test".to_string(),
+            chunker_plugin: "@vpack/chunker-fixed".to_string(),
+            extra,
+        };
+
+        let fields = [
+            "source_plugin",
+            "source_url",
+            "created_at",
+            "year",
+            "tags",
+            "tags.team",
+            "tags.none",
+            "tags.missing",
+            "empty",
+            "missing",
+            "source_id.nested",
+        ];
+        let operands = [
+            None,
+            Some(Value::Null),
+            Some(json!("@vpack/source-fs")),
+            Some(json!("2026-01-01")),
+            Some(json!("core")),
+            Some(json!(2025)),
+            Some(json!(2025.0)),
+            Some(json!(2030)),
+            Some(json!(["core", 2025, null])),
+            Some(json!(["@vpack/source-notion"])),
+            Some(json!({ "team": "core", "none": null })),
+        ];
+        let ops = ["eq", "neq", "in", "nin", "gte", "lte", "exists"];
+
+        for field in fields {
+            for op in ops {
+                for value in &operands {
+                    let filter: MetadataFilter =
+                        serde_json::from_value(json!({ "field": field, "op": op, "value": value }))
+                            .unwrap();
+                    assert_eq!(
+                        Predicate::new(&filter).matches(&metadata),
+                        reference(&metadata, &filter),
+                        "{field} {op} {value:?}"
+                    );
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,119 @@
+// hash.rs — content hashes, checksums and seeded randomness
+// (RFC-0001 §4.3, RFC-0002 §4.6)
+
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+#[cfg(feature = "embed")]
+use std::fs::File;
+#[cfg(feature = "embed")]
+use std::path::Path;
+
+pub fn sha256(bytes: &[u8]) -> [u8; 32] {
+    Sha256::digest(bytes).into()
+}
+
+/// SHA-256 of a file's contents, streamed so large model weights are never
+/// held in memory at once.
+#[cfg(feature = "embed")]
+pub(crate) fn sha256_file(path: &Path) -> std::io::Result<[u8; 32]> {
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut File::open(path)?, &mut hasher)?;
+    Ok(hasher.finalize().into())
+}
+
+/// Compare pinned and computed hashes; the `sha256:` prefix and hex case are
+/// optional in the manifest.
+pub(crate) fn same_hash(pinned: &str, computed: &str) -> bool {
+    let strip = |hash: &str| hash.trim_start_matches("sha256:").to_ascii_lowercase();
+    strip(pinned) == strip(computed)
+}
+
+/// SHA-256 of the normalised manifest. serde_json objects serialize with
+/// sorted keys, so key order in the source YAML/JSON does not matter.
+pub fn manifest_hash(manifest: &Value) -> [u8; 32] {
+    let normalised = serde_json::to_vec(manifest).expect("JSON values always serialize");
+    sha256(&normalised)
+}
+
+/// `u64_from_sha256(manifest_hash)` — the Det-HNSW / Det-IVF-PQ global seed.
+pub(crate) fn derive_seed(manifest_hash: &[u8; 32]) -> u64 {
+    u64::from_le_bytes(manifest_hash[..8].try_into().unwrap())
+}
+
+/// Hash a vector's bytes together with the global seed into a u64.
+pub(crate) fn vector_hash(vector: &[f32], seed: u64) -> u64 {
+    let mut hasher = Sha256::new();
+    for value in vector {
+        hasher.update(value.to_le_bytes());
+    }
+    hasher.update(seed.to_le_bytes());
+    let digest: [u8; 32] = hasher.finalize().into();
+    u64::from_le_bytes(digest[..8].try_into().unwrap())
+}
+
+/// SplitMix64 — small, seedable, and identical on every platform.
+pub(crate) struct SplitMix64(u64);
+
+impl SplitMix64 {
+    pub(crate) fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    pub(crate) fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+}
+
+/// Map 64 random bits to (0, 1] — never 0, so ln() is always finite.
+pub(crate) fn unit_interval(bits: u64) -> f64 {
+    ((bits >> 11) as f64 + 1.0) / (1u64 << 53) as f64
+}
+
+/// CRC-32 (IEEE 802.3). Section checksums in the .vpack section table.
+pub fn crc32(bytes: &[u8]) -> u32 {
+    const TABLE: [u32; 256] = crc32_table();
+    let mut crc = !0u32;
+    for &byte in bytes {
+        crc = TABLE[((crc ^ byte as u32) & 0xff) as usize] ^ (crc >> 8);
+    }
+    !crc
+}
+
+const fn crc32_table() -> [u32; 256] {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut c = i as u32;
+        let mut k = 0;
+        while k < 8 {
+            c = if c & 1 != 0 {
+                0xEDB8_8320 ^ (c >> 1)
+            } else {
+                c >> 1
+            };
+            k += 1;
+        }
+        table[i] = c;
+        i += 1;
+    }
+    table
+}
+
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn crc32_matches_reference_check_value() {
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+        assert_eq!(crc32(b""), 0);
+    }
+}
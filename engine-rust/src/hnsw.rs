@@ -0,0 +1,368 @@
+// hnsw.rs — Hierarchical Navigable Small World graph
+//
+// Malkov & Yashunin (2018). Nodes are positions in the index's chunk list;
+// the graph stores only links, never vectors. Callers supply distances through
+// closures so the same graph works over any vector storage.
+//
+// Candidate ordering is total: ties on distance break by node id. With a fixed
+// seed and a fixed insertion order, the graph is fully deterministic.
+//
+// Det-HNSW (RFC-0002) goes further: the caller inserts nodes in chunk-ID order
+// and derives each node's level from a hash of its vector, so the graph is a
+// pure function of the manifest and the chunk set, independent of input order.
+//
+// Insertion itself is sequential. The neighbors a new node links to are pruned
+// independently of one another, so with the `parallel` feature they are pruned
+// concurrently; each pruned list depends only on that neighbor's own links,
+// which keeps the graph identical to a single-threaded build.
+
+use crate::hash::{unit_interval, SplitMix64};
+use crate::options::HnswConfig;
+use crate::parallel;
+use serde::{Deserialize, Serialize};
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BinaryHeap, HashSet};
+
+/// Upper bound on node levels. Reached with probability ~m^-16.
+const MAX_LEVEL: usize = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Candidate {
+    pub dist: f32,
+    pub id: u32,
+}
+
+impl Eq for Candidate {}
+
+impl Ord for Candidate {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.dist
+            .total_cmp(&other.dist)
+            .then(self.id.cmp(&other.id))
+    }
+}
+
+impl PartialOrd for Candidate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct HnswGraph {
+    m: usize,
+    ef_construction: usize,
+    entry_point: Option<u32>,
+    /// links[node][layer] = neighbor node ids
+    links: Vec<Vec<Vec<u32>>>,
+}
+
+impl HnswGraph {
+    pub(crate) fn new(config: &HnswConfig) -> Self {
+        Self {
+            m: config.m,
+            ef_construction: config.ef_construction,
+            entry_point: None,
+            links: Vec::new(),
+        }
+    }
+
+    /// Build a graph over nodes `0..len`, inserted in order.
+    /// `distance(a, b)` must be symmetric; lower means closer.
+    pub(crate) fn build(
+        config: &HnswConfig,
+        len: usize,
+        distance: impl Fn(u32, u32) -> f32 + Sync,
+    ) -> Self {
+        let mut rng = SplitMix64::new(config.seed);
+        Self::build_with_levels(config, len, |_| rng.next_u64(), distance)
+    }
+
+    /// Build with caller-supplied level entropy: `level_bits(node)` returns 64
+    /// uniformly distributed bits, mapped onto the usual exponential level
+    /// distribution. Det-HNSW passes a hash of the node's vector here.
+    pub(crate) fn build_with_levels(
+        config: &HnswConfig,
+        len: usize,
+        mut level_bits: impl FnMut(u32) -> u64,
+        distance: impl Fn(u32, u32) -> f32 + Sync,
+    ) -> Self {
+        let mut graph = Self::new(config);
+        for node in 0..len as u32 {
+            graph.add(level_bits(node), &distance);
+        }
+        graph
+    }
+
+    /// Append the next node, with its level drawn from `level_bits` as in
+    /// `build_with_levels`. Returns the new node id.
+    pub(crate) fn add(
+        &mut self,
+        level_bits: u64,
+        distance: &(impl Fn(u32, u32) -> f32 + Sync),
+    ) -> u32 {
+        let node = self.links.len() as u32;
+        let ml = 1.0 / (self.m as f64).ln();
+        let unit = unit_interval(level_bits);
+        let level = ((-unit.ln() * ml) as usize).min(MAX_LEVEL);
+        self.insert(node, level, distance);
+        node
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.links.len()
+    }
+
+    /// Return up to `ef` nearest nodes for which `allowed` holds, ascending
+    /// by distance. Rejected nodes are still traversed, so a filter does not
+    /// cut the graph into unreachable pieces; the search ends once `ef`
+    /// allowed nodes are found and no closer candidate is left.
+    pub(crate) fn search(
+        &self,
+        distance: impl Fn(u32) -> f32,
+        allowed: impl Fn(u32) -> bool,
+        ef: usize,
+    ) -> Vec<Candidate> {
+        let Some(ep) = self.entry_point else {
+            return Vec::new();
+        };
+
+        let mut entry = vec![Candidate {
+            dist: distance(ep),
+            id: ep,
+        }];
+        for layer in (1..=self.level(ep)).rev() {
+            entry = self.search_layer(&distance, &entry, 1, layer);
+        }
+        self.search_layer_filtered(&distance, &allowed, &entry, ef.max(1), 0)
+    }
+
+    /// Insert `node` with the given top level. Nodes must be inserted as
+    /// consecutive ids starting from 0.
+    pub(crate) fn insert(
+        &mut self,
+        node: u32,
+        level: usize,
+        distance: &(impl Fn(u32, u32) -> f32 + Sync),
+    ) {
+        debug_assert_eq!(
+            node as usize,
+            self.links.len(),
+            "nodes must be inserted in order"
+        );
+        self.links.push(vec![Vec::new(); level + 1]);
+
+        let Some(ep) = self.entry_point else {
+            self.entry_point = Some(node);
+            return;
+        };
+
+        let top = self.level(ep);
+        let to_node = |other: u32| distance(node, other);
+        let mut entry = vec![Candidate {
+            dist: to_node(ep),
+            id: ep,
+        }];
+
+        for layer in (level + 1..=top).rev() {
+            entry = self.search_layer(&to_node, &entry, 1, layer);
+        }
+
+        for layer in (0..=level.min(top)).rev() {
+            let found = self.search_layer(&to_node, &entry, self.ef_construction, layer);
+            let neighbors = select_neighbors(&found, self.m, distance);
+            let max_links = self.max_links(layer);
+
+            for &neighbor in &neighbors {
+                self.links[neighbor as usize][layer].push(node);
+            }
+            let pruned = parallel::map(&neighbors, |&neighbor| {
+                (self.neighbors(neighbor, layer).len() > max_links)
+                    .then(|| self.pruned(neighbor, layer, max_links, distance))
+            });
+            for (&neighbor, links) in neighbors.iter().zip(pruned) {
+                if let Some(links) = links {
+                    self.links[neighbor as usize][layer] = links;
+                }
+            }
+
+            self.links[node as usize][layer] = neighbors;
+            entry = found;
+        }
+
+        if level > top {
+            self.entry_point = Some(node);
+        }
+    }
+
+    fn level(&self, node: u32) -> usize {
+        self.links[node as usize].len() - 1
+    }
+
+    fn max_links(&self, layer: usize) -> usize {
+        if layer == 0 {
+            self.m * 2
+        } else {
+            self.m
+        }
+    }
+
+    fn neighbors(&self, node: u32, layer: usize) -> &[u32] {
+        self.links[node as usize]
+            .get(layer)
+            .map(Vec::as_slice)
+            .unwrap_or(&[])
+    }
+
+    /// `node`'s links on `layer`, cut back to `max_links`.
+    fn pruned(
+        &self,
+        node: u32,
+        layer: usize,
+        max_links: usize,
+        distance: &impl Fn(u32, u32) -> f32,
+    ) -> Vec<u32> {
+        let mut candidates: Vec<Candidate> = self.links[node as usize][layer]
+            .iter()
+            .map(|&id| Candidate {
+                dist: distance(node, id),
+                id,
+            })
+            .collect();
+        candidates.sort_unstable();
+        select_neighbors(&candidates, max_links, distance)
+    }
+
+    fn search_layer(
+        &self,
+        distance: &impl Fn(u32) -> f32,
+        entry: &[Candidate],
+        ef: usize,
+        layer: usize,
+    ) -> Vec<Candidate> {
+        self.search_layer_filtered(distance, &|_| true, entry, ef, layer)
+    }
+
+    /// Beam search over one layer. Only allowed nodes enter the results, but
+    /// every node within the current bound is expanded.
+    fn search_layer_filtered(
+        &self,
+        distance: &impl Fn(u32) -> f32,
+        allowed: &impl Fn(u32) -> bool,
+        entry: &[Candidate],
+        ef: usize,
+        layer: usize,
+    ) -> Vec<Candidate> {
+        let mut visited: HashSet<u32> = entry.iter().map(|c| c.id).collect();
+        let mut candidates: BinaryHeap<Reverse<Candidate>> =
+            entry.iter().copied().map(Reverse).collect();
+        let mut results: BinaryHeap<Candidate> =
+            entry.iter().copied().filter(|c| allowed(c.id)).collect();
+
+        while let Some(Reverse(current)) = candidates.pop() {
+            if results.len() >= ef && results.peek().is_some_and(|furthest| current > *furthest) {
+                break;
+            }
+
+            for &neighbor in self.neighbors(current.id, layer) {
+                if !visited.insert(neighbor) {
+                    continue;
+                }
+                let candidate = Candidate {
+                    dist: distance(neighbor),
+                    id: neighbor,
+                };
+                if results.len() < ef
+                    || results.peek().is_some_and(|furthest| candidate < *furthest)
+                {
+                    candidates.push(Reverse(candidate));
+                    if allowed(neighbor) {
+                        results.push(candidate);
+                        if results.len() > ef {
+                            results.pop();
+                        }
+                    }
+                }
+            }
+        }
+
+        results.into_sorted_vec()
+    }
+}
+
+/// Neighbor selection heuristic (Algorithm 4): keep a candidate only if it is
+/// closer to the base node than to every neighbor already kept, then backfill
+/// with pruned candidates up to `m`. `candidates` must be sorted ascending.
+fn select_neighbors(
+    candidates: &[Candidate],
+    m: usize,
+    distance: &impl Fn(u32, u32) -> f32,
+) -> Vec<u32> {
+    let mut selected: Vec<Candidate> = Vec::with_capacity(m);
+    let mut pruned: Vec<Candidate> = Vec::new();
+
+    for &candidate in candidates {
+        if selected.len() >= m {
+            break;
+        }
+        if selected
+            .iter()
+            .all(|kept| distance(candidate.id, kept.id) > candidate.dist)
+        {
+            selected.push(candidate);
+        } else {
+            pruned.push(candidate);
+        }
+    }
+
+    for candidate in pruned {
+        if selected.len() >= m {
+            break;
+        }
+        selected.push(candidate);
+    }
+
+    selected.into_iter().map(|c| c.id).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line_distance(points: &[f32]) -> impl Fn(u32, u32) -> f32 + '_ {
+        move |a, b| (points[a as usize] - points[b as usize]).abs()
+    }
+
+    #[test]
+    fn search_finds_nearest_on_a_line() {
+        let points: Vec<f32> = (0..200).map(|i| i as f32).collect();
+        let graph = HnswGraph::build(&HnswConfig::default(), points.len(), line_distance(&points));
+        let results = graph.search(|id| (points[id as usize] - 42.3).abs(), |_| true, 16);
+        assert_eq!(results[0].id, 42);
+        assert_eq!(results[1].id, 43);
+    }
+
+    #[test]
+    fn filtered_search_returns_only_allowed_nodes() {
+        let points: Vec<f32> = (0..300).map(|i| i as f32).collect();
+        let graph = HnswGraph::build(&HnswConfig::default(), points.len(), line_distance(&points));
+        let results = graph.search(
+            |id| (points[id as usize] - 150.2).abs(),
+            |id| id % 7 == 0,
+            8,
+        );
+        let ids: Vec<u32> = results.iter().map(|c| c.id).collect();
+        assert_eq!(ids, [147, 154, 140, 161, 133, 168, 126, 175]);
+    }
+
+    #[test]
+    fn same_seed_builds_same_graph() {
+        let points: Vec<f32> = (0..100).map(|i| ((i * 37) % 101) as f32).collect();
+        let a = HnswGraph::build(&HnswConfig::default(), points.len(), line_distance(&points));
+        let b = HnswGraph::build(&HnswConfig::default(), points.len(), line_distance(&points));
+        assert_eq!(
+            bincode::serialize(&a).unwrap(),
+            bincode::serialize(&b).unwrap()
+        );
+    }
+}
@@ -1,102 +1,742 @@
+use crate::changelog::{self, Changelog};
 use crate::chunk::EmbeddedChunk;
+#[cfg(feature = "embed")]
+use crate::embeddings::embed_texts;
 use crate::error::VPackError;
-use crate::math::cosine_similarity;
-use crate::query::{matches_filter, QueryOptions, QueryResult};
+use crate::filter::CompiledFilter;
+use crate::hash::{derive_seed, manifest_hash, same_hash, vector_hash, SplitMix64};
+use crate::hnsw::HnswGraph;
+use crate::ivf_pq::IvfPqIndex;
+use crate::math;
+use crate::metadata_index::MetadataIndex;
+use crate::options::{BuildOptions, IndexType};
+use crate::parallel;
+use crate::quantize::Quantizer;
+use crate::query::{History, QueryOptions, QueryResult};
+use crate::scoring::Scoring;
+use crate::serialize::LoadOptions;
+use crate::store::{self, ChunkStore, Tombstones, VectorSlice, VectorStore};
 use serde_json::Value;
+use std::borrow::Cow;
+use std::path::Path;
+
+/// Filters estimated to match less than this fraction of the chunks skip the
+/// ANN index and score the matching chunks exactly. Overridable per query.
+pub const BRUTE_FORCE_SELECTIVITY: f32 = 0.05;
+
+/// Chunks sampled to estimate a filter's selectivity.
+const SELECTIVITY_SAMPLE: usize = 1024;
 
 /// The in-memory queryable index.
 /// Built from EmbeddedChunks by VPackIndex::build().
 pub struct VPackIndex {
-    pub(crate) chunks: Vec<EmbeddedChunk>,
+    pub(crate) chunks: ChunkStore,
+    /// Row i is the vector of chunks[i].
+    pub(crate) vectors: VectorStore,
     pub(crate) dimensions: usize,
     pub(crate) manifest: Value,
-    // TODO Phase 2: replace linear scan with HNSW graph from instant-distance
-    // pub(crate) hnsw: HnswMap<...>,
+    pub(crate) options: BuildOptions,
+    /// Present for Hnsw and DetHnsw. Node i is chunks[i].
+    pub(crate) hnsw: Option<HnswGraph>,
+    /// Present for IvfPq. Node i is chunks[i].
+    pub(crate) ivf_pq: Option<IvfPqIndex>,
+    /// Present when `options.quantization` is set. Row i is chunks[i].
+    pub(crate) quantized: Option<Quantizer>,
+    /// Postings for `options.metadata_index`. Empty when none are configured.
+    pub(crate) metadata_index: MetadataIndex,
+    /// Rows deleted since the last build or compaction.
+    pub(crate) tombstones: Tombstones,
+    /// Det-HNSW rows appended since the last build or compaction. The graph
+    /// is no longer the canonical one until compacted.
+    pub(crate) appended: bool,
+    /// Changelog chunks (RFC-0004). They have no row in `chunks`.
+    pub(crate) changelog: Changelog,
 }
 
 impl VPackIndex {
     /// Build an index from pre-embedded chunks.
     /// All chunk vectors must have length == dimensions declared by the embedder plugin.
+    /// Index options come from the manifest's `index:` block (defaults to HNSW).
     pub fn build(
         chunks: Vec<EmbeddedChunk>,
         manifest: Value,
+    ) -> Result<Self, VPackError> {
+        let options = BuildOptions::from_manifest(&manifest)?;
+        Self::build_with_options(chunks, manifest, options)
+    }
+
+    /// Build with explicit options. These take precedence over the manifest.
+    /// Changelog chunks go to the CHANGELOG section, not the index.
+    pub fn build_with_options(
+        chunks: Vec<EmbeddedChunk>,
+        manifest: Value,
+        options: BuildOptions,
+    ) -> Result<Self, VPackError> {
+        Self::build_encoded(chunks, manifest, options, None)
+    }
+
+    /// `build_with_options`, encoding IVF-PQ vectors with `codebook`'s
+    /// centroids and codebooks when it has the pack's dimensions.
+    fn build_encoded(
+        chunks: Vec<EmbeddedChunk>,
+        manifest: Value,
+        options: BuildOptions,
+        codebook: Option<&IvfPqIndex>,
+    ) -> Result<Self, VPackError> {
+        options.validate()?;
+        let (mut chunks, changelog) = changelog::partition(chunks);
+        let dimensions = validate_chunks(&chunks, &manifest)?;
+        changelog.validate(dimensions)?;
+        // Index the vectors as the store will hold them.
+        parallel::for_each_mut(&mut chunks, |chunk| {
+            store::prepare(&mut chunk.vector, &options)
+        });
+
+        let quantizer = {
+            let vectors: Vec<&[f32]> = chunks.iter().map(|c| c.vector.as_slice()).collect();
+            Quantizer::train(options.quantization.kind, dimensions, &vectors)
+        };
+        if let Some(quantizer) = quantizer.as_ref().filter(|_| options.codes_only()) {
+            // The pack will hold codes only. Index and answer from the
+            // reconstructions, as a loaded pack will.
+            parallel::for_each_mut(&mut chunks, |chunk| {
+                chunk.vector = quantizer.reconstructed(&chunk.vector)
+            });
+        }
+
+        let metric = options.metric;
+        let unit = options.unit_vectors();
+        let distance = |chunks: &[EmbeddedChunk], a: u32, b: u32| {
+            let (a, b) = (&chunks[a as usize].vector, &chunks[b as usize].vector);
+            if unit {
+                metric.distance_prepared(a, b)
+            } else {
+                metric.distance(a, b)
+            }
+        };
+        let mut hnsw = None;
+        let mut ivf_pq = None;
+        match options.index_type {
+            IndexType::Flat => {}
+            IndexType::Hnsw => {
+                hnsw = Some(HnswGraph::build(&options.hnsw, chunks.len(), |a, b| {
+                    distance(&chunks, a, b)
+                }));
+            }
+            IndexType::DetHnsw => {
+                // RFC-0002 §4.2: fixed insertion order. Node ids are then ranks
+                // in chunk-ID order, so (distance, node id) is an
+                // input-order-independent tie-break for neighbor selection.
+                chunks.sort_by(|a, b| a.chunk.id.as_bytes().cmp(b.chunk.id.as_bytes()));
+                let seed = derive_seed(&manifest_hash(&manifest));
+                let level_bits =
+                    parallel::map(&chunks, |chunk| vector_hash(&chunk.vector, seed));
+                hnsw = Some(HnswGraph::build_with_levels(
+                    &options.hnsw,
+                    chunks.len(),
+                    |node| level_bits[node as usize],
+                    |a, b| distance(&chunks, a, b),
+                ));
+            }
+            IndexType::IvfPq => {
+                if !dimensions.is_multiple_of(options.ivf_pq.m) {
+                    return Err(VPackError::ManifestInvalid(format!(
+                        "index.ivf_pq.m ({}) must divide the embedding dimensions ({dimensions})",
+                        options.ivf_pq.m
+                    )));
+                }
+                // RFC-0003 §4.1: train in chunk-ID order with the manifest seed.
+                chunks.sort_by(|a, b| a.chunk.id.as_bytes().cmp(b.chunk.id.as_bytes()));
+                let index = match codebook.filter(|c| c.dimensions() == dimensions) {
+                    Some(codebook) => {
+                        let mut index = codebook.cleared();
+                        for chunk in &chunks {
+                            index.add(metric, &chunk.vector);
+                        }
+                        index
+                    }
+                    None => {
+                        let seed = derive_seed(&manifest_hash(&manifest));
+                        let vectors: Vec<&[f32]> =
+                            chunks.iter().map(|c| c.vector.as_slice()).collect();
+                        IvfPqIndex::build(&options.ivf_pq, metric, dimensions, &vectors, seed)
+                    }
+                };
+                if options.ivf_pq.rerank == 0 {
+                    // The pack will hold codes only. Answer from the same
+                    // reconstructions now, so a loaded pack queries identically.
+                    for (chunk, vector) in chunks.iter_mut().zip(index.reconstruct()) {
+                        chunk.vector = vector;
+                    }
+                }
+                ivf_pq = Some(index);
+            }
+        }
+
+        // Encoded in final row order, after det-hnsw has sorted the chunks.
+        let quantized = quantizer.map(|mut quantizer| {
+            for chunk in &chunks {
+                quantizer.add(&chunk.vector);
+            }
+            quantizer
+        });
+
+        let (chunks, vectors) = store::split(chunks, dimensions, options.stored_precision());
+        let metadata_index = MetadataIndex::build(&options.metadata_index, &chunks);
+        Ok(Self {
+            chunks,
+            vectors,
+            dimensions,
+            manifest,
+            options,
+            hnsw,
+            ivf_pq,
+            quantized,
+            metadata_index,
+            tombstones: Tombstones::default(),
+            appended: false,
+            changelog,
+        })
+    }
+
+    /// Build like `build`, but encode IVF-PQ vectors with `previous`'s
+    /// centroids and codebooks instead of retraining them (RFC-0007 §9.2),
+    /// so the new pack can be published as a patch against `previous`.
+    /// Retrains as `build` does when the metric, dimensions or IVF-PQ
+    /// parameters changed, or when `previous` is not IVF-PQ.
+    pub fn build_with_codebook(
+        chunks: Vec<EmbeddedChunk>,
+        manifest: Value,
+        previous: &VPackIndex,
+    ) -> Result<Self, VPackError> {
+        let options = BuildOptions::from_manifest(&manifest)?;
+        let codebook = previous.ivf_pq.as_ref().filter(|_| {
+            options.index_type == IndexType::IvfPq
+                && options.metric == previous.options.metric
+                && options.ivf_pq == previous.options.ivf_pq
+        });
+        Self::build_encoded(chunks, manifest, options, codebook)
+    }
+
+    /// Open a v0x03 pack through a read-only memory map. Vectors are read in
+    /// place and chunk text is decoded only for returned results, so startup
+    /// cost does not grow with the size of the VECTORS and text data.
+    ///
+    /// The file must not be modified while the index is open. Packs that
+    /// cannot be mapped in place (v0x02, codes only) load as usual.
+    pub fn open_mmap(path: impl AsRef<Path>) -> Result<Self, VPackError> {
+        crate::serialize::open_mmap(path.as_ref())
+    }
+
+    /// `open_mmap`, then check the pack against `options`.
+    pub fn open_mmap_with_options(
+        path: impl AsRef<Path>,
+        options: &LoadOptions,
+    ) -> Result<Self, VPackError> {
+        let index = Self::open_mmap(path)?;
+        index.check_model(options.model.as_deref(), options.model_hash.as_deref())?;
+        Ok(index)
+    }
+
+    /// Reassemble a deserialized index without rebuilding its graph.
+    /// Metadata indexes missing from the pack are rebuilt from the chunks.
+    pub(crate) fn from_parts(
+        chunks: ChunkStore,
+        vectors: VectorStore,
+        manifest: Value,
+        options: BuildOptions,
+        hnsw: Option<HnswGraph>,
+        ivf_pq: Option<IvfPqIndex>,
+        metadata_index: Option<MetadataIndex>,
     ) -> Result<Self, VPackError> {
         if chunks.is_empty() {
             return Err(VPackError::EmptyIndex);
         }
-
         let dimensions = get_embedder_dimensions(&manifest)?;
+        if vectors.dimensions() != dimensions {
+            return Err(VPackError::DimensionMismatch {
+                expected: dimensions,
+                got: vectors.dimensions(),
+            });
+        }
+        if vectors.len() != chunks.len() {
+            return Err(VPackError::InvalidFormat(
+                "vector count does not match chunk count".to_string(),
+            ));
+        }
+        if vectors.precision() != options.stored_precision() {
+            return Err(VPackError::InvalidFormat(
+                "vector precision does not match the index options".to_string(),
+            ));
+        }
 
-        for chunk in &chunks {
-            if chunk.vector.len() != dimensions {
-                return Err(VPackError::DimensionMismatch {
-                    expected: dimensions,
-                    got: chunk.vector.len(),
-                });
+        let expects_graph = options.index_type.uses_graph();
+        match &hnsw {
+            Some(graph) if !expects_graph || graph.len() != chunks.len() => {
+                return Err(VPackError::InvalidFormat(
+                    "HNSW graph does not match chunk list".to_string(),
+                ));
+            }
+            None if expects_graph => {
+                return Err(VPackError::InvalidFormat("missing HNSW graph".to_string()));
             }
+            _ => {}
         }
 
+        let expects_ivf_pq = options.index_type == IndexType::IvfPq;
+        match &ivf_pq {
+            Some(ivf) if !expects_ivf_pq || !ivf.is_valid(chunks.len(), dimensions) => {
+                return Err(VPackError::InvalidFormat(
+                    "IVF-PQ index does not match chunk list".to_string(),
+                ));
+            }
+            None if expects_ivf_pq => {
+                return Err(VPackError::InvalidFormat("missing IVF-PQ index".to_string()));
+            }
+            _ => {}
+        }
+
+        let metadata_index = match metadata_index {
+            Some(index) => {
+                index.validate(&options.metadata_index, chunks.len())?;
+                index
+            }
+            None => MetadataIndex::build(&options.metadata_index, &chunks),
+        };
+
         Ok(Self {
             chunks,
+            vectors,
             dimensions,
             manifest,
+            options,
+            hnsw,
+            ivf_pq,
+            quantized: None,
+            metadata_index,
+            tombstones: Tombstones::default(),
+            appended: false,
+            changelog: Changelog::default(),
         })
     }
 
+    /// Attach the quantized codes of an index assembled by `from_parts`.
+    /// Required when its options enable quantization.
+    pub(crate) fn with_quantized(
+        mut self,
+        quantized: Option<Quantizer>,
+    ) -> Result<Self, VPackError> {
+        let expected = self.options.quantization.kind;
+        match quantized {
+            Some(q) if q.kind() != expected || !q.is_valid(self.chunks.len(), self.dimensions) => {
+                Err(VPackError::InvalidFormat(
+                    "quantized codes do not match chunk list".to_string(),
+                ))
+            }
+            None if self.options.quantization.is_enabled() => {
+                Err(VPackError::InvalidFormat("missing quantized codes".to_string()))
+            }
+            quantized => {
+                self.quantized = quantized.map(Quantizer::restored);
+                Ok(self)
+            }
+        }
+    }
+
+    /// Attach changelog chunks to an index assembled by `from_parts`.
+    pub(crate) fn with_changelog(mut self, changelog: Changelog) -> Result<Self, VPackError> {
+        changelog.validate(self.dimensions)?;
+        self.changelog = changelog;
+        Ok(self)
+    }
+
+    /// Restore the deleted rows and det-hnsw appends of an index assembled
+    /// by `from_parts`, as they were when it was serialized.
+    pub(crate) fn with_mutations(
+        mut self,
+        deleted: &[u32],
+        appended: bool,
+    ) -> Result<Self, VPackError> {
+        for &row in deleted {
+            if row as usize >= self.chunks.len() || !self.tombstones.insert(row as usize) {
+                return Err(VPackError::InvalidFormat(
+                    "deleted rows do not match chunk list".to_string(),
+                ));
+            }
+        }
+        if self.tombstones.len() == self.chunks.len() {
+            return Err(VPackError::EmptyIndex);
+        }
+        self.appended = appended && self.options.index_type == IndexType::DetHnsw;
+        Ok(self)
+    }
+
     /// Query the index.
     /// query_vector must have length == self.dimensions.
+    /// Scores follow the pack's metric, higher is better (see `DistanceMetric`).
     pub fn query(
         &self,
         query_vector: &[f32],
         options: QueryOptions,
     ) -> Result<Vec<QueryResult>, VPackError> {
+        self.check_model(options.model.as_deref(), options.model_hash.as_deref())?;
+        let scoring = Scoring::new(&options)?;
         if query_vector.len() != self.dimensions {
             return Err(VPackError::DimensionMismatch {
                 expected: self.dimensions,
                 got: query_vector.len(),
             });
         }
+        let query_vector = &*self.prepare_query(query_vector);
 
-        // Linear scan — O(n). Replace with HNSW traversal in Phase 2.
-        let mut scored: Vec<(f32, usize)> = self
-            .chunks
-            .iter()
-            .enumerate()
-            .filter(|(_, chunk)| {
-                options
-                    .filter
-                    .as_ref()
-                    .map(|filter| matches_filter(&chunk.chunk, filter))
-                    .unwrap_or(true)
-            })
-            .map(|(i, chunk)| (cosine_similarity(query_vector, &chunk.vector), i))
-            .collect();
+        let filter = options.filter.as_ref().map(CompiledFilter::new);
+        let mut scored: Vec<(f32, Hit)> = Vec::new();
+        if options.history != History::Only {
+            let content = match &filter {
+                Some(filter) => self.search_filtered(filter, query_vector, &options),
+                None => self.search(query_vector, &options, None),
+            };
+            scored.extend(content.into_iter().map(|(score, row)| (score, Hit::Chunk(row))));
+        }
+        if options.history != History::Exclude && !self.changelog.is_empty() {
+            let history = self
+                .changelog
+                .search(query_vector, self.options.metric, filter.as_ref());
+            scored.extend(history.into_iter().map(|(score, i)| (score, Hit::Changelog(i))));
+            // Stable: on equal scores content chunks come first.
+            scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+        }
 
-        // Sort descending by score
-        scored.sort_unstable_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(std::cmp::Ordering::Equal));
+        // (score, similarity, hit)
+        let mut ranked: Vec<(f32, f32, Hit)> = match &scoring {
+            Some(scoring) => scored
+                .into_iter()
+                .map(|(similarity, hit)| {
+                    let score = match hit {
+                        Hit::Chunk(row) => {
+                            scoring.adjust(similarity, self.chunks.metadata(row), false)
+                        }
+                        Hit::Changelog(i) => {
+                            let metadata = &self.changelog.get(i).chunk.metadata;
+                            scoring.adjust(similarity, metadata, true)
+                        }
+                    };
+                    (score, similarity, hit)
+                })
+                .collect(),
+            None => scored.into_iter().map(|(s, hit)| (s, s, hit)).collect(),
+        };
+        if scoring.is_some() {
+            ranked.sort_by(|a, b| b.0.total_cmp(&a.0));
+        }
 
-        scored
+        ranked
             .iter()
-            .filter(|(score, _)| options.min_score.map_or(true, |min| *score >= min))
+            .filter(|(score, _, _)| options.min_score.is_none_or(|min| *score >= min))
             .take(options.top_k)
             .enumerate()
-            .map(|(rank, (score, idx))| {
+            .map(|(rank, (score, similarity, hit))| {
+                let (chunk, vector) = match *hit {
+                    Hit::Chunk(row) => (self.chunks.to_chunk(row)?, self.vectors.get(row)),
+                    Hit::Changelog(i) => {
+                        let entry = self.changelog.get(i);
+                        (entry.chunk.clone(), VectorSlice::F32(&entry.vector))
+                    }
+                };
                 Ok(QueryResult {
-                    chunk: self.chunks[*idx].chunk.clone(),
+                    chunk,
                     score: *score,
+                    similarity: *similarity,
                     rank,
-                    vector: if options.include_vectors {
-                        Some(self.chunks[*idx].vector.clone())
-                    } else {
-                        None
-                    },
+                    vector: options.include_vectors.then(|| vector.to_vec()),
                 })
             })
             .collect()
     }
 
+    /// Query with plain text (RFC-0001 §9.1 `QueryInput::Text`). The text is
+    /// embedded with the embedder plugin pinned in the manifest, so the query
+    /// vector always comes from the model the pack was built with.
+    #[cfg(feature = "embed")]
+    pub fn query_text(
+        &self,
+        text: &str,
+        options: QueryOptions,
+    ) -> Result<Vec<QueryResult>, VPackError> {
+        self.check_model(options.model.as_deref(), options.model_hash.as_deref())?;
+        let config = embedder_config(&self.manifest).ok_or_else(|| {
+            VPackError::UnknownModel("manifest has no embedder plugin".to_string())
+        })?;
+        let vector = embed_texts(config.clone(), vec![text.to_string()])?
+            .pop()
+            .ok_or_else(|| VPackError::UnknownModel("embedder returned no vector".to_string()))?;
+        self.query(&vector, options)
+    }
+
+    /// A caller that names a model, or its weights hash, must name the one
+    /// the pack was built with.
+    pub(crate) fn check_model(
+        &self,
+        model: Option<&str>,
+        model_hash: Option<&str>,
+    ) -> Result<(), VPackError> {
+        let expected = self.model().unwrap_or_default();
+        if let Some(got) = model {
+            if got != expected {
+                return Err(VPackError::ModelMismatch {
+                    expected: expected.to_string(),
+                    got: got.to_string(),
+                });
+            }
+        }
+        if let (Some(got), Some(pinned)) = (model_hash, self.model_hash()) {
+            if !same_hash(pinned, got) {
+                return Err(VPackError::ModelHashMismatch {
+                    model: expected.to_string(),
+                    expected: pinned.to_string(),
+                    got: got.to_string(),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Search with the pack's index. A filter is applied while searching.
+    fn search(
+        &self,
+        query_vector: &[f32],
+        options: &QueryOptions,
+        filter: Option<&CompiledFilter>,
+    ) -> Vec<(f32, usize)> {
+        match (&self.hnsw, &self.ivf_pq) {
+            (Some(graph), _) => self.search_graph(graph, query_vector, options, filter),
+            (_, Some(ivf)) => self.search_ivf_pq(ivf, query_vector, options, filter),
+            _ => self.scan(query_vector, filter, None, options.top_k),
+        }
+    }
+
+    /// Filtered search. Packs without an ANN index, and filters estimated
+    /// to match less than `brute_force_selectivity` of the chunks, are
+    /// answered exactly over the matching chunks. Otherwise the ANN index is
+    /// searched with the filter applied during traversal; if that finds
+    /// fewer than `top_k` hits, the exact scan answers instead.
+    fn search_filtered(
+        &self,
+        filter: &CompiledFilter,
+        query_vector: &[f32],
+        options: &QueryOptions,
+    ) -> Vec<(f32, usize)> {
+        let candidates = filter.candidates(&self.metadata_index);
+        if self.hnsw.is_some() || self.ivf_pq.is_some() {
+            let threshold = options
+                .brute_force_selectivity
+                .unwrap_or(BRUTE_FORCE_SELECTIVITY);
+            let selectivity = match &candidates {
+                Some(rows) => rows.len() as f32 / self.chunks.len() as f32,
+                None => self.estimate_selectivity(filter),
+            };
+            if selectivity >= threshold {
+                let scored = self.search(query_vector, options, Some(filter));
+                if scored.len() >= options.top_k {
+                    return scored;
+                }
+            }
+        }
+        self.scan(query_vector, Some(filter), candidates, options.top_k)
+    }
+
+    /// Fraction of chunks matching `filter`: exact for small packs, else from
+    /// a fixed pseudo-random sample, so the plan is the same on every run.
+    fn estimate_selectivity(&self, filter: &CompiledFilter) -> f32 {
+        let len = self.chunks.len();
+        let matches = |i: usize| filter.matches(self.chunks.metadata(i));
+        let matched = if len <= SELECTIVITY_SAMPLE {
+            (0..len).filter(|&i| matches(i)).count()
+        } else {
+            let mut rng = SplitMix64::new(len as u64);
+            (0..SELECTIVITY_SAMPLE)
+                .filter(|_| matches((rng.next_u64() % len as u64) as usize))
+                .count()
+        };
+        matched as f32 / len.min(SELECTIVITY_SAMPLE) as f32
+    }
+
+    /// Approximate search: walk the graph, then score candidates exactly.
+    /// Int8 packs walk and score on the codes, then rescore. Sign bits are
+    /// too coarse to steer the walk, so binary packs walk the f32 vectors.
+    fn search_graph(
+        &self,
+        graph: &HnswGraph,
+        query_vector: &[f32],
+        options: &QueryOptions,
+        filter: Option<&CompiledFilter>,
+    ) -> Vec<(f32, usize)> {
+        let metric = self.options.metric;
+        let unit = self.options.unit_vectors();
+        let ef = options
+            .ef_search
+            .unwrap_or(self.options.hnsw.ef_search)
+            .max(options.top_k);
+        let quantized = match &self.quantized {
+            Some(Quantizer::Int8(quantizer)) => Some(quantizer.query(metric, query_vector)),
+            _ => None,
+        };
+
+        let candidates = graph.search(
+            |id| match &quantized {
+                Some(quantized) => quantized.distance(id as usize),
+                None => self.vectors.get(id as usize).distance(metric, unit, query_vector),
+            },
+            |id| self.allows(filter, id as usize),
+            ef,
+        );
+        let mut scored: Vec<(f32, usize)> = candidates
+            .into_iter()
+            .map(|candidate| {
+                let idx = candidate.id as usize;
+                match &quantized {
+                    Some(quantized) => (quantized.score(idx), idx),
+                    None => (self.vectors.get(idx).score(metric, unit, query_vector), idx),
+                }
+            })
+            .collect();
+
+        if quantized.is_some() {
+            return self.rescore(query_vector, scored, options.top_k, true);
+        }
+        sort_by_score(&mut scored);
+        scored
+    }
+
+    /// Approximate search: ADC over the probed lists, then score the best
+    /// `max(rerank, top_k)` candidates exactly against the stored vectors.
+    /// With a filter, more lists are probed until that many candidates match.
+    fn search_ivf_pq(
+        &self,
+        ivf: &IvfPqIndex,
+        query_vector: &[f32],
+        options: &QueryOptions,
+        filter: Option<&CompiledFilter>,
+    ) -> Vec<(f32, usize)> {
+        let metric = self.options.metric;
+        let unit = self.options.unit_vectors();
+        let nprobe = options.nprobe.unwrap_or(self.options.ivf_pq.nprobe);
+        let keep = self.options.ivf_pq.rerank.max(options.top_k);
+        let min_hits = if filter.is_some() || !self.tombstones.is_empty() {
+            keep
+        } else {
+            0
+        };
+        let mut candidates = ivf.search(
+            query_vector,
+            metric,
+            nprobe,
+            |idx| self.allows(filter, idx),
+            min_hits,
+        );
+        sort_by_score(&mut candidates);
+        candidates.truncate(keep);
+
+        let mut scored: Vec<(f32, usize)> = candidates
+            .into_iter()
+            .map(|(_, idx)| (self.vectors.get(idx).score(metric, unit, query_vector), idx))
+            .collect();
+        sort_by_score(&mut scored);
+        scored
+    }
+
+    /// Exact scan over `candidates`, or every chunk, keeping those that
+    /// pass the filter. Quantized packs scan the codes (Hamming distance
+    /// for binary), then rescore.
+    fn scan(
+        &self,
+        query_vector: &[f32],
+        filter: Option<&CompiledFilter>,
+        candidates: Option<Vec<u32>>,
+        top_k: usize,
+    ) -> Vec<(f32, usize)> {
+        let metric = self.options.metric;
+        let unit = self.options.unit_vectors();
+        let quantized = self
+            .quantized
+            .as_ref()
+            .map(|quantizer| quantizer.query(metric, query_vector));
+        let score = |i: usize| {
+            self.allows(filter, i).then(|| match &quantized {
+                Some(quantized) => (quantized.score(i), i),
+                None => (self.vectors.get(i).score(metric, unit, query_vector), i),
+            })
+        };
+        let mut scored: Vec<(f32, usize)> = match candidates {
+            Some(rows) => parallel::filter_map(&rows, |&row| score(row as usize)),
+            None => parallel::filter_map_range(self.chunks.len(), score),
+        };
+
+        if let Some(quantized) = &quantized {
+            return self.rescore(query_vector, scored, top_k, quantized.scores_metric());
+        }
+        sort_by_score(&mut scored);
+        scored
+    }
+
+    /// Rank first-pass scores over the codes, then score the best
+    /// `max(rescore, top_k)` exactly against the stored f32 vectors.
+    /// Codes-only packs whose first pass is already in the metric's units
+    /// (`scores_metric`) keep it: they have no f32 vectors but the codes'.
+    fn rescore(
+        &self,
+        query_vector: &[f32],
+        mut scored: Vec<(f32, usize)>,
+        top_k: usize,
+        scores_metric: bool,
+    ) -> Vec<(f32, usize)> {
+        sort_by_score(&mut scored);
+        let rescore = self.options.quantization.rescore;
+        if rescore == 0 && scores_metric {
+            return scored;
+        }
+        let metric = self.options.metric;
+        let unit = self.options.unit_vectors();
+        scored.truncate(rescore.max(top_k));
+        for (score, idx) in &mut scored {
+            *score = self.vectors.get(*idx).score(metric, unit, query_vector);
+        }
+        sort_by_score(&mut scored);
+        scored
+    }
+
+    /// The query as stored vectors are scored against: unit length if they
+    /// are.
+    fn prepare_query<'a>(&self, query_vector: &'a [f32]) -> Cow<'a, [f32]> {
+        if !self.options.unit_vectors() {
+            return Cow::Borrowed(query_vector);
+        }
+        let mut query = query_vector.to_vec();
+        math::normalize(&mut query, 0.0);
+        Cow::Owned(query)
+    }
+
+    /// Row `i` is live and passes the filter.
+    fn allows(&self, filter: Option<&CompiledFilter>, i: usize) -> bool {
+        !self.tombstones.contains(i)
+            && filter.is_none_or(|filter| filter.matches(self.chunks.metadata(i)))
+    }
+
+    /// Live chunks: deleted chunks no longer count. Changelog chunks are
+    /// counted by `changelog()`.
     pub fn chunk_count(&self) -> usize {
-        self.chunks.len()
+        self.chunks.len() - self.tombstones.len()
+    }
+
+    /// Changelog chunks that have not expired, oldest first.
+    pub fn changelog(&self) -> Vec<&EmbeddedChunk> {
+        self.changelog.live().collect()
+    }
+
+    /// Hide changelog chunks whose `ttl_days` ran out before `now_ms` (Unix
+    /// milliseconds). Loading a pack does this with the system clock.
+    pub fn expire_changelog(&mut self, now_ms: i64) {
+        self.changelog.expire(now_ms);
     }
 
     pub fn dimensions(&self) -> usize {
@@ -106,35 +746,81 @@ impl VPackIndex {
     pub fn manifest(&self) -> &Value {
         &self.manifest
     }
+
+    /// Embedding model id pinned by the manifest's embedder plugin.
+    pub fn model(&self) -> Option<&str> {
+        embedder_config(&self.manifest)?.get("model")?.as_str()
+    }
+
+    /// Weights hash pinned by the manifest's embedder plugin (`model_hash`).
+    pub fn model_hash(&self) -> Option<&str> {
+        embedder_config(&self.manifest)?.get("model_hash")?.as_str()
+    }
+
+    pub fn options(&self) -> &BuildOptions {
+        &self.options
+    }
+
+    /// True when vectors are read in place from a memory-mapped file.
+    pub fn is_mapped(&self) -> bool {
+        self.vectors.is_mapped()
+    }
+}
+
+/// A query hit: a content row or a changelog entry.
+#[derive(Clone, Copy)]
+enum Hit {
+    Chunk(usize),
+    Changelog(usize),
+}
+
+/// Sort descending by score; equal scores keep chunk order.
+fn sort_by_score(scored: &mut [(f32, usize)]) {
+    scored.sort_unstable_by(|a, b| b.0.total_cmp(&a.0).then(a.1.cmp(&b.1)));
+}
+
+/// Check chunk count and vector lengths against the manifest's embedder.
+fn validate_chunks(chunks: &[EmbeddedChunk], manifest: &Value) -> Result<usize, VPackError> {
+    if chunks.is_empty() {
+        return Err(VPackError::EmptyIndex);
+    }
+
+    let dimensions = get_embedder_dimensions(manifest)?;
+
+    if let Some(i) = parallel::position(chunks, |chunk| chunk.vector.len() != dimensions) {
+        return Err(VPackError::DimensionMismatch {
+            expected: dimensions,
+            got: chunks[i].vector.len(),
+        });
+    }
+
+    Ok(dimensions)
+}
+
+/// The first `kind: embedder` entry of the manifest's plugin list.
+pub(crate) fn embedder_config(manifest: &Value) -> Option<&Value> {
+    manifest
+        .get("plugins")?
+        .as_array()?
+        .iter()
+        .find(|plugin| plugin.get("kind").and_then(|v| v.as_str()) == Some("embedder"))
 }
 
 fn get_embedder_dimensions(manifest: &Value) -> Result<usize, VPackError> {
-    let plugins = manifest
-        .get("plugins")
-        .and_then(|value| value.as_array())
-        .ok_or_else(|| VPackError::UnknownModel("Embedder plugin config must include dimensions".to_string()))?;
-
-    for plugin in plugins {
-        let kind = plugin.get("kind").and_then(|v| v.as_str());
-        if kind == Some("embedder") {
-            let dims = plugin.get("dimensions");
-            if let Some(dims) = dims.and_then(|v| v.as_u64()) {
-                return Ok(dims as usize);
-            }
-            if let Some(dims) = dims.and_then(|v| v.as_f64()) {
-                if dims.fract() == 0.0 {
-                    return Ok(dims as usize);
-                }
-            }
-            return Err(VPackError::UnknownModel(
-                "Embedder plugin config must include dimensions".to_string(),
-            ));
+    let missing =
+        || VPackError::UnknownModel("Embedder plugin config must include dimensions".to_string());
+    let dims = embedder_config(manifest)
+        .ok_or_else(missing)?
+        .get("dimensions");
+    if let Some(dims) = dims.and_then(|v| v.as_u64()) {
+        return Ok(dims as usize);
+    }
+    if let Some(dims) = dims.and_then(|v| v.as_f64()) {
+        if dims.fract() == 0.0 {
+            return Ok(dims as usize);
         }
     }
-
-    Err(VPackError::UnknownModel(
-        "Embedder plugin config must include dimensions".to_string(),
-    ))
+    Err(missing())
 }
 
 #[cfg(test)]
@@ -194,6 +880,48 @@ mod tests {
         assert!((results[0].score - 1.0).abs() < 1e-6);
     }
 
+    #[test]
+    fn flat_and_hnsw_agree_on_small_index() {
+        let chunks: Vec<EmbeddedChunk> = (0..50)
+            .map(|i| {
+                let angle = i as f32 * 0.1;
+                make_chunk(&format!("c{i}"), vec![angle.cos(), angle.sin(), 0.5])
+            })
+            .collect();
+
+        let flat = VPackIndex::build_with_options(
+            chunks.clone(),
+            make_manifest(3),
+            BuildOptions {
+                index_type: IndexType::Flat,
+                ..BuildOptions::default()
+            },
+        )
+        .unwrap();
+        let hnsw = VPackIndex::build(chunks, make_manifest(3)).unwrap();
+        assert!(flat.hnsw.is_none());
+        assert!(hnsw.hnsw.is_some());
+
+        let query = [0.3f32.cos(), 0.3f32.sin(), 0.5];
+        let ids = |index: &VPackIndex| -> Vec<String> {
+            index
+                .query(&query, QueryOptions::default())
+                .unwrap()
+                .into_iter()
+                .map(|r| r.chunk.id)
+                .collect()
+        };
+        assert_eq!(ids(&flat), ids(&hnsw));
+    }
+
+    #[test]
+    fn build_rejects_invalid_index_block() {
+        let mut manifest = make_manifest(3);
+        manifest["index"] = json!({ "type": "hnsw", "hnsw": { "m": 1 } });
+        let result = VPackIndex::build(vec![make_chunk("a", vec![1.0, 0.0, 0.0])], manifest);
+        assert!(matches!(result, Err(VPackError::ManifestInvalid(_))));
+    }
+
     #[test]
     fn build_requires_embedder_dimensions() {
         let chunks = vec![make_chunk("a", vec![1.0, 0.0])];
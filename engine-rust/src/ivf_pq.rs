@@ -0,0 +1,573 @@
+// ivf_pq.rs — Deterministic IVF-PQ (RFC-0003)
+//
+// Vectors (normalised first under the cosine metric) are assigned to the
+// nearest of `nlist` coarse centroids. The residual (vector - centroid) is product-quantized: split into
+// `m` subvectors, each replaced by the id of its nearest codeword. The index
+// stores `m` bytes per node instead of `4 * dimensions`.
+//
+// Queries use asymmetric distance computation (ADC): the query stays exact and
+// q · (centroid + residual) is summed from a per-query lookup table holding
+// q_sub · codeword for every (subspace, code) pair. Under the euclidean metric
+// the table instead holds |(q - centroid)_sub - codeword|² and is rebuilt for
+// each probed list.
+//
+// Determinism: callers pass vectors in chunk-ID order, k-means++ seeding draws
+// from SplitMix64 seeded by the manifest hash, every argmin breaks ties by the
+// lowest index, and all sums run in a fixed order. With the `parallel` feature,
+// nearest-centroid searches, per-subspace training and encoding run across
+// threads; each subspace's seed is drawn up front, so the codebooks match a
+// single-threaded build.
+
+use crate::hash::{sha256, unit_interval, SplitMix64};
+use crate::math::DistanceMetric;
+use crate::options::IvfPqConfig;
+use crate::parallel;
+use serde::{Deserialize, Serialize};
+
+/// k-means trains on at most this many points per centroid, evenly strided
+/// through the input. Keeps training time bounded on large packs.
+const MAX_POINTS_PER_CENTROID: usize = 256;
+
+/// One inverted list as (node, codes) pairs, ascending by node.
+pub(crate) type ClusterList = Vec<(u32, Vec<u8>)>;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct IvfPqIndex {
+    dimensions: usize,
+    m: usize,
+    /// Codewords per subspace: min(2^nbits, training points).
+    ksub: usize,
+    /// nlist x dimensions, row-major.
+    centroids: Vec<f32>,
+    /// m x ksub x (dimensions / m), row-major.
+    codebooks: Vec<f32>,
+    /// lists[c] = nodes assigned to centroid c, ascending.
+    lists: Vec<Vec<u32>>,
+    /// codes[node * m + j] = codeword id of the node's j-th residual subvector.
+    codes: Vec<u8>,
+}
+
+impl IvfPqIndex {
+    /// Train codebooks on `vectors` and encode them. Node i is vectors[i].
+    /// `config.m` must divide `dimensions`.
+    pub(crate) fn build(
+        config: &IvfPqConfig,
+        metric: DistanceMetric,
+        dimensions: usize,
+        vectors: &[&[f32]],
+        seed: u64,
+    ) -> Self {
+        let n = vectors.len();
+        let data: Vec<f32> = match metric {
+            DistanceMetric::Cosine => vectors.iter().flat_map(|v| normalized(v)).collect(),
+            _ => vectors.iter().flat_map(|v| v.iter().copied()).collect(),
+        };
+        let mut rng = SplitMix64::new(seed);
+
+        let training = gather(&data, dimensions, &training_sample(n, config.nlist));
+        let centroids = kmeans(
+            &training,
+            dimensions,
+            config.nlist,
+            config.kmeans_iters,
+            rng.next_u64(),
+        );
+        let nlist = centroids.len() / dimensions;
+
+        let assignments: Vec<usize> = parallel::map_range(n, |i| {
+            nearest(row(&data, dimensions, i), &centroids, dimensions)
+        });
+        let residuals: Vec<f32> = (0..n)
+            .flat_map(|i| {
+                let centroid = row(&centroids, dimensions, assignments[i]);
+                row(&data, dimensions, i)
+                    .iter()
+                    .zip(centroid)
+                    .map(|(x, c)| x - c)
+            })
+            .collect();
+
+        let dsub = dimensions / config.m;
+        let pq_sample = training_sample(n, 1 << config.nbits);
+        let ksub = (1usize << config.nbits).min(pq_sample.len());
+        let seeds: Vec<u64> = (0..config.m).map(|_| rng.next_u64()).collect();
+        let codebooks = parallel::map_range(config.m, |j| {
+            let subvectors: Vec<f32> = pq_sample
+                .iter()
+                .flat_map(|&i| &row(&residuals, dimensions, i)[j * dsub..(j + 1) * dsub])
+                .copied()
+                .collect();
+            kmeans(&subvectors, dsub, ksub, config.kmeans_iters, seeds[j])
+        })
+        .concat();
+
+        let mut index = Self {
+            dimensions,
+            m: config.m,
+            ksub,
+            centroids,
+            codebooks,
+            lists: vec![Vec::new(); nlist],
+            codes: Vec::new(),
+        };
+        index.codes = parallel::map_range(n, |node| {
+            let residual = row(&residuals, dimensions, node);
+            (0..config.m)
+                .map(|j| {
+                    let sub = &residual[j * dsub..(j + 1) * dsub];
+                    nearest(sub, index.codebook(j), dsub) as u8
+                })
+                .collect::<Vec<u8>>()
+        })
+        .concat();
+        for (node, &list) in assignments.iter().enumerate() {
+            index.lists[list].push(node as u32);
+        }
+        index
+    }
+
+    /// Encode `vector` with the trained centroids and codebooks as the next
+    /// node. Returns its reconstruction.
+    pub(crate) fn add(&mut self, metric: DistanceMetric, vector: &[f32]) -> Vec<f32> {
+        let vector = match metric {
+            DistanceMetric::Cosine => normalized(vector),
+            _ => vector.to_vec(),
+        };
+        let node = self.len();
+        let dsub = self.dimensions / self.m;
+        let list = nearest(&vector, &self.centroids, self.dimensions);
+        let centroid = row(&self.centroids, self.dimensions, list);
+        let residual: Vec<f32> = vector.iter().zip(centroid).map(|(x, c)| x - c).collect();
+        for j in 0..self.m {
+            let code = nearest(&residual[j * dsub..(j + 1) * dsub], self.codebook(j), dsub);
+            self.codes.push(code as u8);
+        }
+        self.lists[list].push(node as u32);
+        self.decode(list, node)
+    }
+
+    /// Keep the nodes in `rows`, renumbered so node i is old node `rows[i]`.
+    /// Centroids and codebooks are kept as trained.
+    pub(crate) fn select(&self, rows: &[usize]) -> Self {
+        let mut renumbered = vec![u32::MAX; self.len()];
+        let mut codes = Vec::with_capacity(rows.len() * self.m);
+        for (node, &row) in rows.iter().enumerate() {
+            renumbered[row] = node as u32;
+            codes.extend_from_slice(self.node_codes(row));
+        }
+        let lists = self
+            .lists
+            .iter()
+            .map(|list| {
+                let mut list: Vec<u32> = list
+                    .iter()
+                    .map(|&node| renumbered[node as usize])
+                    .filter(|&node| node != u32::MAX)
+                    .collect();
+                list.sort_unstable();
+                list
+            })
+            .collect();
+        Self {
+            lists,
+            codes,
+            ..self.cleared()
+        }
+    }
+
+    /// The trained centroids and codebooks with no nodes.
+    pub(crate) fn cleared(&self) -> Self {
+        Self {
+            dimensions: self.dimensions,
+            m: self.m,
+            ksub: self.ksub,
+            centroids: self.centroids.clone(),
+            codebooks: self.codebooks.clone(),
+            lists: vec![Vec::new(); self.lists.len()],
+            codes: Vec::new(),
+        }
+    }
+
+    /// SHA-256 over the shape, centroids and codebooks. Equal hashes mean
+    /// vectors encode identically.
+    pub(crate) fn codebook_hash(&self) -> [u8; 32] {
+        let mut bytes = Vec::new();
+        for size in [self.dimensions, self.m, self.ksub, self.lists.len()] {
+            bytes.extend_from_slice(&(size as u64).to_le_bytes());
+        }
+        for value in self.centroids.iter().chain(&self.codebooks) {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        sha256(&bytes)
+    }
+
+    pub(crate) fn nlist(&self) -> usize {
+        self.lists.len()
+    }
+
+    /// The list each node is in.
+    pub(crate) fn assignments(&self) -> Vec<u32> {
+        let mut assignments = vec![0; self.len()];
+        for (list, nodes) in self.lists.iter().enumerate() {
+            for &node in nodes {
+                assignments[node as usize] = list as u32;
+            }
+        }
+        assignments
+    }
+
+    /// The nodes of `list` with their codes, ascending.
+    pub(crate) fn cluster(&self, list: usize) -> Vec<(u32, &[u8])> {
+        self.lists[list]
+            .iter()
+            .map(|&node| (node, self.node_codes(node as usize)))
+            .collect()
+    }
+
+    /// A `len`-node index with the same codebooks. Lists named in `replaced`
+    /// are taken from it as (node, codes); every other list keeps its nodes,
+    /// renumbered through `renumbered` (old node → new node). `None` unless
+    /// every new node ends up in exactly one list with in-range codes, and
+    /// every replacement list is ascending.
+    pub(crate) fn patched(
+        &self,
+        len: usize,
+        renumbered: &[Option<u32>],
+        replaced: &[(u32, ClusterList)],
+    ) -> Option<Self> {
+        let mut index = self.cleared();
+        index.codes = vec![0; len * self.m];
+        let mut placed = vec![false; len];
+        let mut place = |node: u32, codes: &[u8], index: &mut Self| {
+            let node = node as usize;
+            if node >= len || placed[node] || codes.len() != self.m {
+                return None;
+            }
+            if codes.iter().any(|&code| code as usize >= self.ksub) {
+                return None;
+            }
+            placed[node] = true;
+            index.codes[node * self.m..(node + 1) * self.m].copy_from_slice(codes);
+            Some(())
+        };
+
+        for (list, entries) in replaced {
+            let list = *list as usize;
+            if list >= self.nlist()
+                || !index.lists[list].is_empty()
+                || !entries.windows(2).all(|pair| pair[0].0 < pair[1].0)
+            {
+                return None;
+            }
+            for (node, codes) in entries {
+                place(*node, codes, &mut index)?;
+                index.lists[list].push(*node);
+            }
+        }
+        let replaced: Vec<bool> = (0..self.nlist())
+            .map(|list| replaced.iter().any(|(id, _)| *id as usize == list))
+            .collect();
+        for (list, nodes) in self.lists.iter().enumerate() {
+            if replaced[list] {
+                continue;
+            }
+            for &node in nodes {
+                let new = (*renumbered.get(node as usize)?)?;
+                place(new, self.node_codes(node as usize), &mut index)?;
+                index.lists[list].push(new);
+            }
+            index.lists[list].sort_unstable();
+        }
+        placed.iter().all(|&placed| placed).then_some(index)
+    }
+
+    pub(crate) fn dimensions(&self) -> usize {
+        self.dimensions
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.codes.len() / self.m
+    }
+
+    /// Shape checks for an index read from disk: sizes agree, and every list
+    /// entry and code is in range.
+    pub(crate) fn is_valid(&self, len: usize, dimensions: usize) -> bool {
+        let dsub = dimensions / self.m.max(1);
+        let listed: usize = self.lists.iter().map(Vec::len).sum();
+        self.m > 0
+            && self.dimensions == dimensions
+            && dimensions.is_multiple_of(self.m)
+            && self.codes.len() == len * self.m
+            && self.centroids.len() == self.lists.len() * dimensions
+            && self.codebooks.len() == self.m * self.ksub * dsub
+            && self.codes.iter().all(|&code| (code as usize) < self.ksub)
+            && listed == len
+            && self
+                .lists
+                .iter()
+                .flatten()
+                .all(|&node| (node as usize) < len)
+    }
+
+    /// Approximate scores for every node in the `nprobe` clusters nearest the
+    /// query, higher is better. Inner product for cosine (of unit vectors) and
+    /// dot; negated squared distance for euclidean. Only the order is
+    /// meaningful across metrics. Unsorted.
+    ///
+    /// Only nodes for which `allowed` holds are scored. Past `nprobe`, further
+    /// clusters are probed in order until `min_hits` nodes are scored, so a
+    /// restrictive filter still yields enough candidates.
+    pub(crate) fn search(
+        &self,
+        query: &[f32],
+        metric: DistanceMetric,
+        nprobe: usize,
+        allowed: impl Fn(usize) -> bool,
+        min_hits: usize,
+    ) -> Vec<(f32, usize)> {
+        let query = match metric {
+            DistanceMetric::Cosine => normalized(query),
+            _ => query.to_vec(),
+        };
+
+        let mut probes: Vec<(f32, usize)> = self
+            .centroids
+            .chunks_exact(self.dimensions)
+            .enumerate()
+            .map(|(list, centroid)| (squared_l2(&query, centroid), list))
+            .collect();
+        probes.sort_unstable_by(|a, b| a.0.total_cmp(&b.0).then(a.1.cmp(&b.1)));
+
+        let mut scored = Vec::new();
+        // Inner products do not depend on the list, so one table serves all.
+        let shared = (metric != DistanceMetric::Euclidean).then(|| self.lookup(&query, dot));
+        for (probed, &(_, list)) in probes.iter().enumerate() {
+            if probed >= nprobe && scored.len() >= min_hits {
+                break;
+            }
+            let per_list;
+            let centroid = row(&self.centroids, self.dimensions, list);
+            let (base, lookup) = match &shared {
+                Some(lookup) => (dot(&query, centroid), lookup),
+                None => {
+                    let offset: Vec<f32> = query.iter().zip(centroid).map(|(q, c)| q - c).collect();
+                    per_list = self.lookup(&offset, |a, b| -squared_l2(a, b));
+                    (0.0, &per_list)
+                }
+            };
+            for &node in &self.lists[list] {
+                if !allowed(node as usize) {
+                    continue;
+                }
+                let residual: f32 = self
+                    .node_codes(node as usize)
+                    .iter()
+                    .enumerate()
+                    .map(|(j, &code)| lookup[j * self.ksub + code as usize])
+                    .sum();
+                scored.push((base + residual, node as usize));
+            }
+        }
+        scored
+    }
+
+    /// lookup[j * ksub + c] = f(query_j, codeword(j, c))
+    fn lookup(&self, query: &[f32], f: impl Fn(&[f32], &[f32]) -> f32) -> Vec<f32> {
+        let dsub = self.dimensions / self.m;
+        (0..self.m)
+            .flat_map(|j| {
+                let sub = &query[j * dsub..(j + 1) * dsub];
+                self.codebook(j)
+                    .chunks_exact(dsub)
+                    .map(|codeword| f(sub, codeword))
+                    .collect::<Vec<_>>()
+            })
+            .collect()
+    }
+
+    /// Decode every node back to centroid + quantized residual.
+    pub(crate) fn reconstruct(&self) -> Vec<Vec<f32>> {
+        let mut vectors = vec![Vec::new(); self.len()];
+        for (list, nodes) in self.lists.iter().enumerate() {
+            for &node in nodes {
+                vectors[node as usize] = self.decode(list, node as usize);
+            }
+        }
+        vectors
+    }
+
+    /// Centroid of `list` plus the quantized residual of `node`.
+    fn decode(&self, list: usize, node: usize) -> Vec<f32> {
+        let dsub = self.dimensions / self.m;
+        let mut vector = row(&self.centroids, self.dimensions, list).to_vec();
+        for (j, &code) in self.node_codes(node).iter().enumerate() {
+            let codeword = row(self.codebook(j), dsub, code as usize);
+            for (x, c) in vector[j * dsub..(j + 1) * dsub].iter_mut().zip(codeword) {
+                *x += c;
+            }
+        }
+        vector
+    }
+
+    fn codebook(&self, subspace: usize) -> &[f32] {
+        let size = self.ksub * (self.dimensions / self.m);
+        &self.codebooks[subspace * size..(subspace + 1) * size]
+    }
+
+    fn node_codes(&self, node: usize) -> &[u8] {
+        &self.codes[node * self.m..(node + 1) * self.m]
+    }
+}
+
+/// Deterministic k-means (RFC-0003 §4.1): k-means++ seeding driven by `seed`,
+/// then Lloyd iterations until assignments stop changing. `points` is
+/// row-major with `dim` columns. Returns min(k, points) centroids, row-major.
+/// A cluster that empties keeps its previous centroid.
+fn kmeans(points: &[f32], dim: usize, k: usize, iterations: usize, seed: u64) -> Vec<f32> {
+    let n = points.len() / dim;
+    let k = k.min(n);
+    let mut rng = SplitMix64::new(seed);
+
+    let first = (rng.next_u64() % n as u64) as usize;
+    let mut centroids = row(points, dim, first).to_vec();
+    let mut closest: Vec<f32> = parallel::map_range(n, |i| {
+        squared_l2(row(points, dim, i), row(points, dim, first))
+    });
+    while centroids.len() < k * dim {
+        let total: f64 = closest.iter().map(|&d| d as f64).sum();
+        let pick = if total > 0.0 {
+            let target = unit_interval(rng.next_u64()) * total;
+            let mut acc = 0.0;
+            closest
+                .iter()
+                .position(|&d| {
+                    acc += d as f64;
+                    acc >= target
+                })
+                .unwrap_or(n - 1)
+        } else {
+            // Every point coincides with a centroid already; duplicates are fine.
+            (rng.next_u64() % n as u64) as usize
+        };
+        let chosen = row(points, dim, pick);
+        closest = parallel::map_range(n, |i| {
+            closest[i].min(squared_l2(row(points, dim, i), chosen))
+        });
+        centroids.extend_from_slice(chosen);
+    }
+
+    let mut assignments = vec![usize::MAX; n];
+    for _ in 0..iterations {
+        let updated = parallel::map_range(n, |i| nearest(row(points, dim, i), &centroids, dim));
+        if updated == assignments {
+            break;
+        }
+        assignments = updated;
+
+        let mut sums = vec![0f64; k * dim];
+        let mut counts = vec![0usize; k];
+        for (i, &cluster) in assignments.iter().enumerate() {
+            counts[cluster] += 1;
+            for (sum, &x) in sums[cluster * dim..(cluster + 1) * dim]
+                .iter_mut()
+                .zip(row(points, dim, i))
+            {
+                *sum += x as f64;
+            }
+        }
+        for (cluster, &count) in counts.iter().enumerate() {
+            if count == 0 {
+                continue;
+            }
+            for d in 0..dim {
+                centroids[cluster * dim + d] = (sums[cluster * dim + d] / count as f64) as f32;
+            }
+        }
+    }
+    centroids
+}
+
+/// Index of the nearest centroid; ties go to the lowest index.
+fn nearest(point: &[f32], centroids: &[f32], dim: usize) -> usize {
+    let mut best = (f32::INFINITY, 0);
+    for (i, centroid) in centroids.chunks_exact(dim).enumerate() {
+        let dist = squared_l2(point, centroid);
+        if dist < best.0 {
+            best = (dist, i);
+        }
+    }
+    best.1
+}
+
+/// Evenly strided subset of `0..n` with at most MAX_POINTS_PER_CENTROID * k points.
+fn training_sample(n: usize, k: usize) -> Vec<usize> {
+    let cap = k.saturating_mul(MAX_POINTS_PER_CENTROID);
+    if n <= cap {
+        return (0..n).collect();
+    }
+    (0..cap).map(|i| i * n / cap).collect()
+}
+
+fn gather(data: &[f32], dim: usize, rows: &[usize]) -> Vec<f32> {
+    rows.iter()
+        .flat_map(|&i| row(data, dim, i))
+        .copied()
+        .collect()
+}
+
+#[inline]
+fn row(data: &[f32], dim: usize, i: usize) -> &[f32] {
+    &data[i * dim..(i + 1) * dim]
+}
+
+/// Cosine: train and encode unit vectors, so inner product = similarity.
+fn normalized(vector: &[f32]) -> Vec<f32> {
+    let norm = dot(vector, vector).sqrt();
+    if norm == 0.0 {
+        return vector.to_vec();
+    }
+    vector.iter().map(|x| x / norm).collect()
+}
+
+#[inline]
+fn dot(a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b).map(|(x, y)| x * y).sum()
+}
+
+#[inline]
+fn squared_l2(a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b).map(|(x, y)| (x - y) * (x - y)).sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn kmeans_separates_distinct_clusters() {
+        let points = [0.0, 0.1, 0.2, 10.0, 10.1, 10.2];
+        let mut centroids = kmeans(&points, 1, 2, 10, 7);
+        centroids.sort_by(f32::total_cmp);
+        assert!((centroids[0] - 0.1).abs() < 1e-6);
+        assert!((centroids[1] - 10.1).abs() < 1e-6);
+    }
+
+    #[test]
+    fn reconstruction_is_exact_when_every_point_is_a_codeword() {
+        let vectors: Vec<Vec<f32>> = (0..8)
+            .map(|i| normalized(&[i as f32, 1.0, (i % 3) as f32, 2.0]))
+            .collect();
+        let refs: Vec<&[f32]> = vectors.iter().map(Vec::as_slice).collect();
+        let config = IvfPqConfig {
+            nlist: 2,
+            m: 2,
+            ..IvfPqConfig::default()
+        };
+        let index = IvfPqIndex::build(&config, DistanceMetric::Cosine, 4, &refs, 1);
+
+        assert!(index.is_valid(8, 4));
+        for (original, decoded) in vectors.iter().zip(index.reconstruct()) {
+            assert!(squared_l2(original, &decoded) < 1e-10);
+        }
+    }
+}
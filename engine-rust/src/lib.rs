@@ -5,21 +5,56 @@
 // Callers never know which implementation is running.
 //
 // Modules:
+//   changelog — changelog chunks and the CHANGELOG section (RFC-0004)
 //   chunk     — Chunk and EmbeddedChunk types
+//   diff      — ChunkDiff between two packs (RFC-0004 §5)
+//   embeddings — fastembed text embedding (feature = "embed", on by default)
 //   index     — VPackIndex: HNSW build + query
-//   serialize — .vpack binary format (columnar layout per RFC-0001 §3.1)
-//   query     — scoring, filtering, result ranking
+//   mutate    — VPackIndex insert, upsert, delete and compact
+//   hnsw      — HNSW graph construction and search (incl. Det-HNSW, RFC-0002)
+//   ivf_pq    — Deterministic IVF-PQ training, encoding and ADC search (RFC-0003)
+//   hash      — SHA-256 manifest hash, CRC-32, derived seeds, seeded PRNG
+//   options   — BuildOptions: index type, HNSW and IVF-PQ parameters, precision
+//   serialize — .vpack binary format (sectioned layout per RFC-0001 §3.1)
+//   patch     — .vpack.patch produce and apply (RFC-0007)
+//   store     — owned or memory-mapped chunk and f32/f16/bf16 vector storage
+//   quantize  — int8 and binary quantization of stored vectors
+//   query     — query options, filter expressions, results
+//   scoring   — temporal weight and recency decay applied to query scores
+//   filter    — filters compiled to allocation-free predicates, query planning
+//   metadata_index — inverted indexes over chosen metadata fields
 //   error     — VPackError enum (all error codes from RFC-0001 §9.4)
-//   math      — vector distance functions (cosine, euclidean, dot)
+//   math      — vector distance functions (cosine, euclidean, dot), f32 and half
+//   simd      — AVX-512, AVX2 and SSE distance kernels, picked at runtime
+//   parallel  — order-preserving loops, on rayon with feature = "parallel"
 //   napi      — napi-rs Node.js bindings (feature = "napi")
+//   wasm      — wasm-bindgen browser and edge bindings (feature = "wasm")
+//   ffi       — C ABI for Python, Go and Ruby adapters (feature = "ffi")
+//   python    — PyO3 `vpack` extension module (feature = "python")
 
+pub mod changelog;
 pub mod chunk;
+pub mod diff;
+#[cfg(feature = "embed")]
 pub mod embeddings;
 pub mod error;
+mod filter;
+mod hash;
+mod hnsw;
 pub mod index;
+mod ivf_pq;
 pub mod math;
+mod metadata_index;
+mod mutate;
+pub mod options;
+mod parallel;
+pub mod patch;
 pub mod query;
+mod quantize;
+mod scoring;
 pub mod serialize;
+mod simd;
+mod store;
 
 #[cfg(feature = "napi")]
 pub mod napi_bindings;
@@ -27,9 +62,32 @@ pub mod napi_bindings;
 #[cfg(feature = "wasm")]
 pub mod wasm_bindings;
 
+#[cfg(feature = "ffi")]
+pub mod ffi;
+
+#[cfg(feature = "python")]
+pub mod python_bindings;
+
 // Re-export the public API
+pub use changelog::{is_changelog, CHANGELOG_CHUNK_TYPE};
 pub use chunk::{Chunk, ChunkMetadata, EmbeddedChunk};
+pub use diff::{diff, ChunkDiff, ModifiedChunk, RemovedChunk};
 pub use error::VPackError;
+pub use hash::manifest_hash;
 pub use index::VPackIndex;
-pub use query::{QueryOptions, QueryResult};
-pub use serialize::{deserialize, serialize};
+pub use math::DistanceMetric;
+pub use options::{
+    BuildOptions, HnswConfig, IndexType, IvfPqConfig, QuantizationConfig, QuantizationType,
+    VectorPrecision,
+};
+pub use patch::{
+    apply_patch, create_patch, read_changelog_entry, read_patch_header, PatchHeader,
+    PatchSectionId,
+};
+pub use query::{
+    FilterExpr, FilterOp, History, MetadataFilter, QueryOptions, QueryResult, RecencyDecay,
+};
+pub use serialize::{
+    deserialize, deserialize_with_options, read_header, read_manifest, read_section, read_vectors,
+    serialize, LoadOptions, PackHeader, SectionEntry, SectionId,
+};
@@ -1,21 +1,129 @@
-/// Cosine similarity between two f32 vectors.
+use crate::simd;
+use half::slice::HalfFloatSliceExt;
+use half::{bf16, f16};
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+
+/// Metric used to compare a query with stored vectors. Chosen at build time
+/// and stored in the pack with the rest of the index options.
 ///
-/// The compiler auto-vectorizes this loop to SIMD (AVX2/NEON) in release builds.
-/// You get near-assembly performance from idiomatic Rust — no unsafe required.
-#[inline]
-pub fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
-    debug_assert_eq!(a.len(), b.len(), "vectors must have equal dimensions");
+/// Scores are always "higher is better", so `min_score` reads as a lower bound:
+/// - `Cosine`: cosine similarity, -1..=1. Stored f32 vectors are scaled to
+///   unit length at build, so scoring them is a dot product.
+/// - `Dot`: raw dot product, unbounded. Equals cosine for unit vectors.
+/// - `Euclidean`: `1 / (1 + distance)`, in (0, 1]. 1 means identical;
+///   `min_score: s` keeps results within distance `1 / s - 1`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum DistanceMetric {
+    #[default]
+    Cosine,
+    Euclidean,
+    Dot,
+}
+
+impl DistanceMetric {
+    /// Similarity score of `a` and `b`; higher means closer.
+    #[inline]
+    pub fn score(self, a: &[f32], b: &[f32]) -> f32 {
+        match self {
+            DistanceMetric::Cosine => cosine_similarity(a, b),
+            DistanceMetric::Euclidean => 1.0 / (1.0 + euclidean_distance(a, b)),
+            DistanceMetric::Dot => dot_product(a, b),
+        }
+    }
+
+    /// Distance for graph construction and traversal; lower means closer.
+    /// Orders pairs exactly opposite to `score`.
+    #[inline]
+    pub fn distance(self, a: &[f32], b: &[f32]) -> f32 {
+        match self {
+            DistanceMetric::Cosine => 1.0 - cosine_similarity(a, b),
+            DistanceMetric::Euclidean => euclidean_distance(a, b),
+            DistanceMetric::Dot => -dot_product(a, b),
+        }
+    }
+
+    /// `score` of vectors that are unit length when the metric is cosine,
+    /// as stored vectors and prepared queries are. Cosine is then just the
+    /// dot product.
+    #[inline]
+    pub(crate) fn score_prepared(self, a: &[f32], b: &[f32]) -> f32 {
+        match self {
+            DistanceMetric::Cosine | DistanceMetric::Dot => dot_product(a, b),
+            DistanceMetric::Euclidean => 1.0 / (1.0 + euclidean_distance(a, b)),
+        }
+    }
+
+    /// `distance` of vectors prepared as for `score_prepared`.
+    #[inline]
+    pub(crate) fn distance_prepared(self, a: &[f32], b: &[f32]) -> f32 {
+        match self {
+            DistanceMetric::Cosine => 1.0 - dot_product(a, b),
+            DistanceMetric::Euclidean => euclidean_distance(a, b),
+            DistanceMetric::Dot => -dot_product(a, b),
+        }
+    }
+
+    /// `score` of an f32 query `a` against a half-precision row `b`.
+    #[inline]
+    pub(crate) fn score_half<T: HalfFloat>(self, a: &[f32], b: &[T]) -> f32 {
+        widened(b, |b| self.score(a, b))
+    }
 
-    let mut dot = 0.0f32;
-    let mut norm_a = 0.0f32;
-    let mut norm_b = 0.0f32;
+    /// `distance` of an f32 query `a` to a half-precision row `b`.
+    #[inline]
+    pub(crate) fn distance_half<T: HalfFloat>(self, a: &[f32], b: &[T]) -> f32 {
+        widened(b, |b| self.distance(a, b))
+    }
+}
+
+/// Half-precision storage types. Widening uses F16C on x86 CPUs that have it.
+pub(crate) trait HalfFloat: Copy {
+    fn widen(src: &[Self], dst: &mut [f32]);
+}
 
-    for (x, y) in a.iter().zip(b.iter()) {
-        dot += x * y;
-        norm_a += x * x;
-        norm_b += y * y;
+impl HalfFloat for f16 {
+    #[inline]
+    fn widen(src: &[Self], dst: &mut [f32]) {
+        src.convert_to_f32_slice(dst);
     }
+}
+
+impl HalfFloat for bf16 {
+    #[inline]
+    fn widen(src: &[Self], dst: &mut [f32]) {
+        src.convert_to_f32_slice(dst);
+    }
+}
+
+thread_local! {
+    /// Reused for every half row scored on this thread.
+    static WIDENED: RefCell<Vec<f32>> = const { RefCell::new(Vec::new()) };
+}
 
+/// Run `f` on `row` widened to f32, so a half row scores exactly like its
+/// widened copy.
+#[inline]
+fn widened<T: HalfFloat>(row: &[T], f: impl FnOnce(&[f32]) -> f32) -> f32 {
+    WIDENED.with_borrow_mut(|buffer| {
+        if buffer.len() < row.len() {
+            buffer.resize(row.len(), 0.0);
+        }
+        let buffer = &mut buffer[..row.len()];
+        T::widen(row, buffer);
+        f(buffer)
+    })
+}
+
+/// Cosine similarity between two f32 vectors.
+///
+/// Runs on the widest SIMD kernels the CPU has (see `simd.rs`). Stored
+/// vectors skip this: they are unit length, so `dot_product` suffices.
+#[inline]
+pub fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    debug_assert_eq!(a.len(), b.len(), "vectors must have equal dimensions");
+    let [dot, norm_a, norm_b] = (simd::kernels().cosine_sums)(a, b);
     let denom = norm_a.sqrt() * norm_b.sqrt();
     if denom == 0.0 { 0.0 } else { dot / denom }
 }
@@ -23,17 +131,27 @@ pub fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
 #[inline]
 pub fn euclidean_distance(a: &[f32], b: &[f32]) -> f32 {
     debug_assert_eq!(a.len(), b.len());
-    a.iter()
-        .zip(b.iter())
-        .map(|(x, y)| (x - y).powi(2))
-        .sum::<f32>()
-        .sqrt()
+    (simd::kernels().squared_distance)(a, b).sqrt()
 }
 
 #[inline]
 pub fn dot_product(a: &[f32], b: &[f32]) -> f32 {
     debug_assert_eq!(a.len(), b.len());
-    a.iter().zip(b.iter()).map(|(x, y)| x * y).sum()
+    (simd::kernels().dot)(a, b)
+}
+
+/// Scale `vector` to unit length. Vectors whose squared norm is already
+/// within `tolerance` of 1, and zero vectors, are left as they are, so
+/// normalizing twice changes nothing.
+pub(crate) fn normalize(vector: &mut [f32], tolerance: f32) {
+    let squared = dot_product(vector, vector);
+    if squared == 0.0 || (squared - 1.0).abs() <= tolerance {
+        return;
+    }
+    let norm = squared.sqrt();
+    for x in vector {
+        *x /= norm;
+    }
 }
 
 #[cfg(test)]
@@ -60,4 +178,60 @@ mod tests {
         let b = vec![-1.0, 0.0];
         assert_relative_eq!(cosine_similarity(&a, &b), -1.0, epsilon = 1e-6);
     }
+
+    #[test]
+    fn euclidean_score_is_one_for_identical_vectors() {
+        let v = vec![3.0, 4.0];
+        assert_relative_eq!(DistanceMetric::Euclidean.score(&v, &v), 1.0, epsilon = 1e-6);
+        // distance 5 -> 1 / 6
+        let origin = vec![0.0, 0.0];
+        assert_relative_eq!(DistanceMetric::Euclidean.score(&v, &origin), 1.0 / 6.0, epsilon = 1e-6);
+    }
+
+    #[test]
+    fn half_rows_score_like_their_widened_copies() {
+        let q: Vec<f32> = (0..150).map(|i| (i as f32 * 0.37).sin()).collect();
+        let row: Vec<f32> = (0..150).map(|i| (i as f32 * 0.11).cos()).collect();
+        let halves: Vec<f16> = row.iter().map(|&x| f16::from_f32(x)).collect();
+        let brains: Vec<bf16> = row.iter().map(|&x| bf16::from_f32(x)).collect();
+        let widened: Vec<f32> = halves.iter().map(|x| x.to_f32()).collect();
+        let brain_widened: Vec<f32> = brains.iter().map(|x| x.to_f32()).collect();
+        for metric in [DistanceMetric::Cosine, DistanceMetric::Euclidean, DistanceMetric::Dot] {
+            assert_eq!(metric.score_half(&q, &halves), metric.score(&q, &widened));
+            assert_eq!(metric.distance_half(&q, &halves), metric.distance(&q, &widened));
+            assert_eq!(metric.score_half(&q, &brains), metric.score(&q, &brain_widened));
+            let exact = metric.score(&q, &row);
+            assert_relative_eq!(metric.score(&q, &widened), exact, epsilon = 1e-2);
+        }
+    }
+
+    #[test]
+    fn prepared_cosine_is_the_dot_product_of_unit_vectors() {
+        let (mut a, mut b) = (vec![3.0, 4.0, 0.0], vec![1.0, 2.0, 2.0]);
+        let expected = cosine_similarity(&a, &b);
+        normalize(&mut a, 1e-4);
+        normalize(&mut b, 1e-4);
+        assert_relative_eq!(a.as_slice(), [0.6, 0.8, 0.0].as_slice(), epsilon = 1e-6);
+        let score = DistanceMetric::Cosine.score_prepared(&a, &b);
+        assert_relative_eq!(score, expected, epsilon = 1e-6);
+
+        // Idempotent, and zero vectors stay zero.
+        let once = a.clone();
+        normalize(&mut a, 1e-4);
+        assert_eq!(a, once);
+        let mut zero = vec![0.0; 3];
+        normalize(&mut zero, 1e-4);
+        assert_eq!(zero, vec![0.0; 3]);
+    }
+
+    #[test]
+    fn metric_distance_orders_opposite_to_score() {
+        let q = vec![1.0, 0.5];
+        let near = vec![2.0, 1.0];
+        let far = vec![-1.0, 3.0];
+        for metric in [DistanceMetric::Cosine, DistanceMetric::Euclidean, DistanceMetric::Dot] {
+            assert!(metric.score(&q, &near) > metric.score(&q, &far));
+            assert!(metric.distance(&q, &near) < metric.distance(&q, &far));
+        }
+    }
 }
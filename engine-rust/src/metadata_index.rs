@@ -0,0 +1,197 @@
+// metadata_index.rs — inverted indexes over chunk metadata
+//
+// Fields listed in the manifest's `index: { metadata_index: [...] }` get a
+// postings map at build time: each distinct value (as compact JSON) maps to
+// the ascending rows of the chunks holding it. Chunks where the field is
+// missing are not posted. Field names are dotted filter paths, so
+// `tags.team` indexes a nested `extra` value.
+//
+// The map is stored in the METADATA_INDEX section. Packs without that section
+// rebuild it from CHUNKS on load.
+
+use crate::chunk::ChunkMetadata;
+use crate::error::VPackError;
+use crate::filter::{union, value_key, FieldPath};
+use crate::store::ChunkStore;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub(crate) struct MetadataIndex {
+    /// field → value key → ascending rows.
+    fields: BTreeMap<String, BTreeMap<String, Vec<u32>>>,
+}
+
+impl MetadataIndex {
+    pub(crate) fn build(fields: &[String], chunks: &ChunkStore) -> Self {
+        let fields = fields
+            .iter()
+            .map(|name| {
+                let path = FieldPath::new(name);
+                let mut postings: BTreeMap<String, Vec<u32>> = BTreeMap::new();
+                for (row, chunk) in chunks.iter().enumerate() {
+                    if let Some(key) = path.key(&chunk.metadata) {
+                        postings.entry(key).or_default().push(row as u32);
+                    }
+                }
+                (name.clone(), postings)
+            })
+            .collect();
+        Self { fields }
+    }
+
+    /// Post a row appended after every indexed row.
+    pub(crate) fn add(&mut self, row: u32, metadata: &ChunkMetadata) {
+        for (name, postings) in &mut self.fields {
+            if let Some(key) = FieldPath::new(name).key(metadata) {
+                postings.entry(key).or_default().push(row);
+            }
+        }
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.fields.is_empty()
+    }
+
+    /// Rows whose `field` equals any of `values`, ascending. `None` when the
+    /// field is not indexed.
+    pub(crate) fn rows(&self, field: &str, values: &[Value]) -> Option<Vec<u32>> {
+        let postings = self.fields.get(field)?;
+        Some(
+            values
+                .iter()
+                .filter_map(|value| postings.get(&value_key(value)))
+                .fold(Vec::new(), |rows, list| union(&rows, list)),
+        )
+    }
+
+    /// Indexed fields must be the configured ones, with rows in range.
+    pub(crate) fn validate(&self, fields: &[String], chunk_count: usize) -> Result<(), VPackError> {
+        let same_fields = self.fields.len() == fields.len()
+            && fields.iter().all(|field| self.fields.contains_key(field));
+        let in_range = self
+            .fields
+            .values()
+            .flat_map(BTreeMap::values)
+            .all(|rows| rows.iter().all(|&row| (row as usize) < chunk_count));
+        if same_fields && in_range {
+            Ok(())
+        } else {
+            Err(VPackError::InvalidFormat(
+                "metadata index does not match chunk list".to_string(),
+            ))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk::Chunk;
+    use crate::filter::CompiledFilter;
+    use crate::query::FilterExpr;
+    use serde_json::json;
+    use std::collections::HashMap;
+
+    fn make_store() -> ChunkStore {
+        ChunkStore::from_chunks((0..12).map(|i| {
+            let mut extra = HashMap::new();
+            extra.insert("year".to_string(), json!(2020 + i % 4));
+            if i % 3 == 0 {
+                extra.insert("tags".to_string(), json!({ "team": "core" }));
+            }
+            Chunk {
+                id: format!("c{i}"),
+                text: String::new(),
+                metadata: ChunkMetadata {
+                    source_plugin: "@vpack/source-fs".to_string(),
+                    source_id: format!("doc-{}", i / 2),
+                    source_url: None,
+                    created_at: None,
+                    updated_at: None,
+                    pack_name: "test".to_string(),
+                    chunker_plugin: "@vpack/chunker-fixed".to_string(),
+                    extra,
+                },
+            }
+        }))
+    }
+
+    #[test]
+    fn planner_narrows_indexed_equalities_and_keeps_scans_otherwise() {
+        let chunks = make_store();
+        let fields = ["source_id", "year", "tags.team"].map(str::to_string);
+        let index = MetadataIndex::build(&fields, &chunks);
+        index.validate(&fields, chunks.len()).unwrap();
+
+        let plan = |filter: Value| {
+            let expr: FilterExpr = serde_json::from_value(filter).unwrap();
+            let filter = CompiledFilter::new(&expr);
+            let candidates = filter.candidates(&index);
+            // Candidates must never drop a matching row.
+            let matching: Vec<u32> = chunks
+                .iter()
+                .enumerate()
+                .filter(|(_, chunk)| filter.matches(&chunk.metadata))
+                .map(|(row, _)| row as u32)
+                .collect();
+            if let Some(rows) = &candidates {
+                assert!(matching.iter().all(|row| rows.contains(row)));
+            }
+            candidates
+        };
+
+        let in_docs =
+            json!({ "field": "source_id", "op": "in", "value": ["doc-1", "doc-4", "doc-9"] });
+        assert_eq!(plan(in_docs.clone()), Some(vec![2, 3, 8, 9]));
+        assert_eq!(
+            plan(json!({ "field": "tags.team", "op": "eq", "value": "core" })),
+            Some(vec![0, 3, 6, 9])
+        );
+        assert_eq!(
+            plan(
+                json!({ "and": [in_docs.clone(), { "field": "year", "op": "eq", "value": 2021 }] })
+            ),
+            Some(vec![9])
+        );
+        // Unindexed parts of an `and` are checked on the candidates instead.
+        assert_eq!(
+            plan(
+                json!({ "and": [in_docs.clone(), { "field": "pack_name", "op": "eq", "value": "test" }] })
+            ),
+            Some(vec![2, 3, 8, 9])
+        );
+        assert_eq!(
+            plan(
+                json!({ "or": [in_docs.clone(), { "field": "year", "op": "eq", "value": 2020 }] })
+            ),
+            Some(vec![0, 2, 3, 4, 8, 9])
+        );
+        assert_eq!(
+            plan(json!({ "or": [in_docs, { "field": "pack_name", "op": "eq", "value": "test" }] })),
+            None
+        );
+        assert_eq!(
+            plan(json!({ "field": "year", "op": "gte", "value": 2022 })),
+            None
+        );
+        assert_eq!(
+            plan(json!({ "not": { "field": "year", "op": "eq", "value": 2020 } })),
+            None
+        );
+        assert_eq!(
+            plan(json!({ "field": "year", "op": "eq", "value": 2020.0 })),
+            Some(vec![])
+        );
+    }
+
+    #[test]
+    fn validate_rejects_mismatched_fields_and_rows() {
+        let chunks = make_store();
+        let fields = ["source_id".to_string()];
+        let index = MetadataIndex::build(&fields, &chunks);
+        assert!(index.validate(&["year".to_string()], chunks.len()).is_err());
+        assert!(index.validate(&fields, 4).is_err());
+    }
+}
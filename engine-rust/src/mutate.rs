@@ -0,0 +1,249 @@
+// mutate.rs — in-place insert, upsert and delete on VPackIndex
+//
+// New chunks are appended as rows: linked into the HNSW graph, or encoded with
+// the trained IVF-PQ centroids and codebooks, quantized with the build's
+// calibration, and posted to the metadata indexes. Deleted rows are tombstoned and skipped by every query path.
+//
+// `serialize` writes tombstones and appended rows as they are. Only an explicit
+// `compact()` drops tombstoned rows and rebuilds. HNSW and flat indexes keep
+// their row order and are rebuilt from the remaining chunks, so the result
+// equals a fresh build over them in that order. Det-HNSW is compacted after
+// inserts too, which restores the canonical chunk-ID order and graph. IVF-PQ
+// keeps its trained codebooks rather than retraining, and its rows are put
+// back in chunk-ID order.
+//
+// Changelog chunks are added to and removed from the changelog directly.
+
+use crate::changelog::is_changelog;
+use crate::chunk::EmbeddedChunk;
+use crate::error::VPackError;
+use crate::hash::{derive_seed, manifest_hash, vector_hash, SplitMix64};
+use crate::index::VPackIndex;
+use crate::options::IndexType;
+use crate::store;
+use std::collections::{HashMap, HashSet};
+
+impl VPackIndex {
+    /// Append chunks. Vectors must have the index's dimensions, and ids must
+    /// not be in the index yet. On error the index is unchanged.
+    pub fn insert(&mut self, chunks: Vec<EmbeddedChunk>) -> Result<(), VPackError> {
+        self.check_new(&chunks, false)?;
+        self.add(chunks);
+        Ok(())
+    }
+
+    /// Insert chunks, replacing any chunk that has the same id.
+    /// On error the index is unchanged.
+    pub fn upsert(&mut self, chunks: Vec<EmbeddedChunk>) -> Result<(), VPackError> {
+        self.check_new(&chunks, true)?;
+        let replaced: Vec<usize> = {
+            let rows = self.live_rows();
+            chunks
+                .iter()
+                .filter_map(|chunk| rows.get(chunk.chunk.id.as_str()).copied())
+                .collect()
+        };
+        for row in replaced {
+            self.tombstones.insert(row);
+        }
+        let ids: HashSet<&str> = chunks.iter().map(|c| c.chunk.id.as_str()).collect();
+        self.changelog
+            .remove(|entry| ids.contains(entry.chunk.id.as_str()));
+        self.add(chunks);
+        Ok(())
+    }
+
+    /// Delete chunks by id. Returns how many were deleted; unknown ids are
+    /// ignored.
+    pub fn delete(&mut self, ids: &[&str]) -> usize {
+        let ids: HashSet<&str> = ids.iter().copied().collect();
+        let rows: Vec<usize> = self
+            .chunks
+            .iter()
+            .enumerate()
+            .filter(|(_, chunk)| ids.contains(chunk.id.as_str()))
+            .map(|(row, _)| row)
+            .collect();
+        let history = self
+            .changelog
+            .remove(|entry| ids.contains(entry.chunk.id.as_str()));
+        rows.into_iter()
+            .filter(|&row| self.tombstones.insert(row))
+            .count()
+            + history
+    }
+
+    /// Delete every chunk from `source_id`. Returns how many were deleted.
+    pub fn delete_source(&mut self, source_id: &str) -> usize {
+        let rows: Vec<usize> = self
+            .chunks
+            .iter()
+            .enumerate()
+            .filter(|(_, chunk)| chunk.metadata.source_id == source_id)
+            .map(|(row, _)| row)
+            .collect();
+        let history = self
+            .changelog
+            .remove(|entry| entry.chunk.metadata.source_id == source_id);
+        rows.into_iter()
+            .filter(|&row| self.tombstones.insert(row))
+            .count()
+            + history
+    }
+
+    /// Chunks deleted but not yet compacted away.
+    pub fn deleted_count(&self) -> usize {
+        self.tombstones.len()
+    }
+
+    /// Drop deleted chunks and rebuild the index structures over the rest.
+    /// Fails with `EmptyIndex` if every chunk was deleted.
+    pub fn compact(&mut self) -> Result<(), VPackError> {
+        if self.needs_compaction() {
+            *self = self.compacted()?;
+        }
+        Ok(())
+    }
+
+    /// True when chunks were deleted, or inserted into a det-hnsw index,
+    /// since the last build or compaction.
+    pub fn needs_compaction(&self) -> bool {
+        !self.tombstones.is_empty() || self.appended
+    }
+
+    /// A new index holding only the live chunks, in row order. Det-HNSW and
+    /// IVF-PQ put them back in chunk-ID order, as a build would.
+    fn compacted(&self) -> Result<Self, VPackError> {
+        let mut rows: Vec<usize> = (0..self.chunks.len())
+            .filter(|&row| !self.tombstones.contains(row))
+            .collect();
+        if self.ivf_pq.is_some() {
+            rows.sort_by_key(|&row| self.chunks.id(row).as_bytes());
+        }
+        let live = rows
+            .iter()
+            .map(|&row| {
+                Ok(EmbeddedChunk {
+                    chunk: self.chunks.to_chunk(row)?,
+                    vector: self.vectors.get(row).to_vec(),
+                })
+            })
+            .collect::<Result<Vec<_>, VPackError>>()?;
+        if live.is_empty() {
+            return Err(VPackError::EmptyIndex);
+        }
+
+        let compacted = match &self.ivf_pq {
+            Some(ivf) => {
+                let (chunks, vectors) =
+                    store::split(live, self.dimensions, self.options.stored_precision());
+                Self::from_parts(
+                    chunks,
+                    vectors,
+                    self.manifest.clone(),
+                    self.options.clone(),
+                    None,
+                    Some(ivf.select(&rows)),
+                    None,
+                )
+            }
+            None => Self::build_with_options(live, self.manifest.clone(), self.options.clone()),
+        };
+        compacted?.with_changelog(self.changelog.clone())
+    }
+
+    /// Dimension and id checks for chunks about to be added. With `replace`,
+    /// ids already in the index are allowed.
+    fn check_new(&self, chunks: &[EmbeddedChunk], replace: bool) -> Result<(), VPackError> {
+        let live = if replace {
+            HashMap::new()
+        } else {
+            self.live_rows()
+        };
+        let mut seen = HashSet::new();
+        for chunk in chunks {
+            if chunk.vector.len() != self.dimensions {
+                return Err(VPackError::DimensionMismatch {
+                    expected: self.dimensions,
+                    got: chunk.vector.len(),
+                });
+            }
+            let id = chunk.chunk.id.as_str();
+            let exists = live.contains_key(id) || (!replace && self.changelog.contains(id));
+            if !seen.insert(id) || exists {
+                return Err(VPackError::DuplicateChunkId(id.to_string()));
+            }
+        }
+        Ok(())
+    }
+
+    /// Row of every live chunk, by id.
+    pub(crate) fn live_rows(&self) -> HashMap<&str, usize> {
+        self.chunks
+            .iter()
+            .enumerate()
+            .filter(|(row, _)| !self.tombstones.contains(*row))
+            .map(|(row, chunk)| (chunk.id.as_str(), row))
+            .collect()
+    }
+
+    /// Seed for new HNSW levels: the manifest-derived seed for det-hnsw,
+    /// otherwise the configured one.
+    fn level_seed(&self) -> u64 {
+        match self.options.index_type {
+            IndexType::DetHnsw => derive_seed(&manifest_hash(&self.manifest)),
+            _ => self.options.hnsw.seed,
+        }
+    }
+
+    /// Append content chunks and file changelog chunks.
+    fn add(&mut self, chunks: Vec<EmbeddedChunk>) {
+        let level_seed = self.level_seed();
+        for chunk in chunks {
+            if is_changelog(&chunk.chunk.metadata) {
+                self.changelog.insert(chunk);
+            } else {
+                self.append(chunk, level_seed);
+            }
+        }
+    }
+
+    fn append(&mut self, mut embedded: EmbeddedChunk, level_seed: u64) {
+        let row = self.chunks.len();
+        let metric = self.options.metric;
+        let unit = self.options.unit_vectors();
+        store::prepare(&mut embedded.vector, &self.options);
+        if let Some(ivf) = &mut self.ivf_pq {
+            let decoded = ivf.add(metric, &embedded.vector);
+            if self.options.ivf_pq.rerank == 0 {
+                // Codes only: answer from the reconstruction, like a build.
+                embedded.vector = decoded;
+            }
+        }
+        if let Some(quantizer) = &mut self.quantized {
+            let decoded = quantizer.add(&embedded.vector);
+            if self.options.quantization.rescore == 0 {
+                embedded.vector = decoded;
+            }
+        }
+        self.vectors.push(&embedded.vector);
+        if let Some(graph) = &mut self.hnsw {
+            let level_bits = match self.options.index_type {
+                IndexType::DetHnsw => {
+                    self.appended = true;
+                    vector_hash(&embedded.vector, level_seed)
+                }
+                _ => SplitMix64::new(level_seed ^ row as u64).next_u64(),
+            };
+            let vectors = &self.vectors;
+            graph.add(level_bits, &|a, b| {
+                vectors
+                    .get(a as usize)
+                    .distance_to(metric, unit, vectors.get(b as usize))
+            });
+        }
+        self.metadata_index
+            .add(row as u32, &embedded.chunk.metadata);
+        self.chunks.push(embedded.chunk);
+    }
+}
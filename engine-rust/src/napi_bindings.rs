@@ -3,11 +3,13 @@ use napi::Error;
 use napi_derive::napi;
 
 use crate::chunk::EmbeddedChunk;
-use crate::embeddings::embed_texts;
+use crate::diff::diff;
+use crate::embeddings::{embed_texts, model_hash};
 use crate::error::VPackError;
 use crate::index::VPackIndex;
+use crate::options::BuildOptions;
 use crate::query::QueryOptions;
-use crate::serialize::{deserialize, serialize};
+use crate::serialize::{deserialize_with_options, serialize, LoadOptions};
 
 fn napi_error_from_vpack(err: VPackError) -> Error {
     Error::from_reason(format!("{}|{}", err.code(), err))
@@ -25,10 +27,22 @@ pub struct NativeIndex {
 }
 
 #[napi]
-pub fn build_index(chunks_json: String, manifest_json: String) -> NapiResult<NativeIndex> {
+pub fn build_index(
+    chunks_json: String,
+    manifest_json: String,
+    options_json: Option<String>,
+) -> NapiResult<NativeIndex> {
     let chunks: Vec<EmbeddedChunk> = serde_json::from_str(&chunks_json).map_err(napi_error_from_json)?;
     let manifest: serde_json::Value = serde_json::from_str(&manifest_json).map_err(napi_error_from_json)?;
-    let index = VPackIndex::build(chunks, manifest).map_err(napi_error_from_vpack)?;
+    let index = match options_json {
+        Some(json) => {
+            let value: serde_json::Value = serde_json::from_str(&json).map_err(napi_error_from_json)?;
+            let options = BuildOptions::from_value(value).map_err(napi_error_from_vpack)?;
+            VPackIndex::build_with_options(chunks, manifest, options)
+        }
+        None => VPackIndex::build(chunks, manifest),
+    }
+    .map_err(napi_error_from_vpack)?;
     Ok(NativeIndex { inner: index })
 }
 
@@ -38,9 +52,24 @@ pub fn serialize_index(index: &NativeIndex) -> NapiResult<Buffer> {
     Ok(Buffer::from(bytes))
 }
 
+fn load_options(options_json: Option<String>) -> NapiResult<LoadOptions> {
+    match options_json {
+        Some(json) => serde_json::from_str(&json).map_err(napi_error_from_json),
+        None => Ok(LoadOptions::default()),
+    }
+}
+
+#[napi]
+pub fn deserialize_index(bytes: Buffer, options_json: Option<String>) -> NapiResult<NativeIndex> {
+    let options = load_options(options_json)?;
+    let index = deserialize_with_options(bytes.as_ref(), &options).map_err(napi_error_from_vpack)?;
+    Ok(NativeIndex { inner: index })
+}
+
 #[napi]
-pub fn deserialize_index(bytes: Buffer) -> NapiResult<NativeIndex> {
-    let index = deserialize(bytes.as_ref()).map_err(napi_error_from_vpack)?;
+pub fn open_index_mmap(path: String, options_json: Option<String>) -> NapiResult<NativeIndex> {
+    let options = load_options(options_json)?;
+    let index = VPackIndex::open_mmap_with_options(path, &options).map_err(napi_error_from_vpack)?;
     Ok(NativeIndex { inner: index })
 }
 
@@ -52,6 +81,12 @@ pub fn embed_texts_json(config_json: String, texts_json: String) -> NapiResult<S
     serde_json::to_string(&vectors).map_err(napi_error_from_json)
 }
 
+#[napi]
+pub fn model_hash_json(config_json: String) -> NapiResult<String> {
+    let config: serde_json::Value = serde_json::from_str(&config_json).map_err(napi_error_from_json)?;
+    model_hash(config).map_err(napi_error_from_vpack)
+}
+
 #[napi]
 pub fn query_index(
     index: &NativeIndex,
@@ -70,6 +105,30 @@ pub fn query_index(
     serde_json::to_string(&results).map_err(napi_error_from_json)
 }
 
+#[napi]
+pub fn query_index_text(
+    index: &NativeIndex,
+    text: String,
+    options_json: Option<String>,
+) -> NapiResult<String> {
+    let options = match options_json {
+        Some(json) => serde_json::from_str::<QueryOptions>(&json).map_err(napi_error_from_json)?,
+        None => QueryOptions::default(),
+    };
+    let results = index
+        .inner
+        .query_text(&text, options)
+        .map_err(napi_error_from_vpack)?;
+    serde_json::to_string(&results).map_err(napi_error_from_json)
+}
+
+/// ChunkDiff from `old` to `new` as JSON, for output plugins (RFC-0004 §5.2).
+#[napi]
+pub fn diff_indexes(old: &NativeIndex, new: &NativeIndex) -> NapiResult<String> {
+    let diff = diff(&old.inner, &new.inner).map_err(napi_error_from_vpack)?;
+    serde_json::to_string(&diff).map_err(napi_error_from_json)
+}
+
 #[napi]
 pub fn manifest_json(index: &NativeIndex) -> NapiResult<String> {
     serde_json::to_string(index.inner.manifest()).map_err(napi_error_from_json)
@@ -0,0 +1,251 @@
+// options.rs — build-time index configuration
+//
+// Mirrors `BuildOptions` / `HnswConfig` / `IvfPqConfig` / `QuantizationConfig` /
+// `VectorPrecision` from @vpack/core. Options come either
+// from the manifest's optional top-level `index:` block or are passed
+// explicitly to `VPackIndex::build_with_options`. The resolved options are
+// stored in the pack so a deserialized index queries exactly like the original.
+
+use crate::error::VPackError;
+use crate::math::DistanceMetric;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum IndexType {
+    /// Exact linear scan. No INDEX structure is built.
+    Flat,
+    /// Approximate nearest-neighbor search over an HNSW graph.
+    #[default]
+    Hnsw,
+    /// Deterministic HNSW (RFC-0002): byte-identical INDEX for identical
+    /// manifest + chunks. The level seed is derived from the manifest hash.
+    DetHnsw,
+    /// Deterministic IVF-PQ (RFC-0003): coarse clusters plus product-quantized
+    /// residuals. Trained with k-means seeded from the manifest hash.
+    IvfPq,
+}
+
+impl IndexType {
+    pub fn uses_graph(self) -> bool {
+        matches!(self, IndexType::Hnsw | IndexType::DetHnsw)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HnswConfig {
+    /// Links per node on upper layers. Layer 0 keeps up to 2 * m.
+    pub m: usize,
+    /// Candidate list width while inserting nodes.
+    pub ef_construction: usize,
+    /// Default candidate list width at query time. Overridable per query.
+    pub ef_search: usize,
+    /// Seed for level assignment. Same seed + same chunk order = same graph.
+    /// Ignored by det-hnsw, which derives its seed from the manifest hash.
+    pub seed: u64,
+}
+
+impl Default for HnswConfig {
+    fn default() -> Self {
+        Self {
+            m: 16,
+            ef_construction: 200,
+            ef_search: 64,
+            seed: 0x5650_414b,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct IvfPqConfig {
+    /// Coarse clusters. Clamped to the chunk count for small packs.
+    pub nlist: usize,
+    /// Default clusters probed per query. Overridable per query.
+    pub nprobe: usize,
+    /// PQ subspaces (bytes per code). Must divide the embedding dimensions.
+    pub m: usize,
+    /// Bits per PQ code, 1..=8: 2^nbits centroids per subspace.
+    #[serde(alias = "bits")]
+    pub nbits: u8,
+    /// Lloyd iterations after k-means++ seeding.
+    pub kmeans_iters: usize,
+    /// Approximate candidates rescored exactly. When 0, the pack keeps only
+    /// PQ codes and chunk vectors become their PQ reconstructions; otherwise
+    /// raw vectors are stored alongside the codes.
+    pub rerank: usize,
+}
+
+impl Default for IvfPqConfig {
+    fn default() -> Self {
+        Self {
+            nlist: 256,
+            nprobe: 16,
+            m: 16,
+            nbits: 8,
+            kmeans_iters: 20,
+            rerank: 0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum QuantizationType {
+    /// Vectors are stored and scored as f32.
+    #[default]
+    None,
+    /// One byte per component, calibrated to each dimension's min/max.
+    Int8,
+    /// One sign bit per component. Ranks by Hamming distance, then rescores
+    /// the best `max(rescore, top_k)` candidates.
+    Binary,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct QuantizationConfig {
+    #[serde(rename = "type")]
+    pub kind: QuantizationType,
+    /// Quantized candidates rescored against f32 vectors. When 0, the pack
+    /// keeps only the codes and chunk vectors become their reconstructions;
+    /// otherwise f32 vectors are stored alongside the codes. Binary packs
+    /// should oversample: a few times the expected `top_k`.
+    pub rescore: usize,
+}
+
+impl QuantizationConfig {
+    pub fn is_enabled(&self) -> bool {
+        self.kind != QuantizationType::None
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum VectorPrecision {
+    /// Four bytes per component.
+    #[default]
+    F32,
+    /// IEEE 754 half precision: 11-bit significand, magnitudes up to 65504.
+    F16,
+    /// bfloat16: f32's exponent range with an 8-bit significand.
+    Bf16,
+}
+
+impl VectorPrecision {
+    /// Bytes per stored component.
+    pub fn size(self) -> usize {
+        match self {
+            VectorPrecision::F32 => 4,
+            VectorPrecision::F16 | VectorPrecision::Bf16 => 2,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct BuildOptions {
+    #[serde(rename = "type", alias = "index")]
+    pub index_type: IndexType,
+    /// Scoring metric for every index type. Defaults to cosine.
+    pub metric: DistanceMetric,
+    pub hnsw: HnswConfig,
+    pub ivf_pq: IvfPqConfig,
+    /// Metadata fields (dotted filter paths) to build inverted indexes for.
+    /// Filters with `eq`/`in` on these fields score only the matching rows.
+    pub metadata_index: Vec<String>,
+    /// Quantization of the stored vectors. Not available with IVF-PQ, which
+    /// quantizes through its own codebooks.
+    pub quantization: QuantizationConfig,
+    /// Storage type of the vectors, in VECTORS and in memory. Half precision
+    /// halves their size; queries and returned vectors stay f32.
+    pub precision: VectorPrecision,
+}
+
+impl BuildOptions {
+    /// Read options from the manifest's `index:` block. Absent block = defaults.
+    pub fn from_manifest(manifest: &Value) -> Result<Self, VPackError> {
+        match manifest.get("index") {
+            None | Some(Value::Null) => Ok(Self::default()),
+            Some(block) => Self::from_value(block.clone()),
+        }
+    }
+
+    pub fn from_value(value: Value) -> Result<Self, VPackError> {
+        let options: Self = serde_json::from_value(value)
+            .map_err(|err| VPackError::ManifestInvalid(format!("index: {err}")))?;
+        options.validate()?;
+        Ok(options)
+    }
+
+    /// The pack stores codes instead of f32 vectors: IVF-PQ without rerank,
+    /// or quantization without rescore.
+    pub(crate) fn codes_only(&self) -> bool {
+        match self.index_type {
+            IndexType::IvfPq => self.ivf_pq.rerank == 0,
+            _ => self.quantization.is_enabled() && self.quantization.rescore == 0,
+        }
+    }
+
+    /// Precision of the stored vectors. Codes-only packs have none stored,
+    /// and keep their reconstructions as f32.
+    pub(crate) fn stored_precision(&self) -> VectorPrecision {
+        if self.codes_only() {
+            VectorPrecision::F32
+        } else {
+            self.precision
+        }
+    }
+
+    /// Stored vectors are unit length, so cosine against them is a dot
+    /// product. Only for f32 vectors: rounding to half precision moves a
+    /// norm by more than near neighbours differ, which full cosine divides
+    /// back out. Nor for codes-only packs, whose reconstructions must
+    /// retrain to the same codes.
+    pub(crate) fn unit_vectors(&self) -> bool {
+        self.metric == DistanceMetric::Cosine
+            && !self.codes_only()
+            && self.precision == VectorPrecision::F32
+    }
+
+    pub(crate) fn validate(&self) -> Result<(), VPackError> {
+        if self.metadata_index.iter().any(|field| field.is_empty()) {
+            return Err(VPackError::ManifestInvalid(
+                "index.metadata_index fields must not be empty".to_string(),
+            ));
+        }
+        if self.index_type.uses_graph() {
+            if self.hnsw.m < 2 {
+                return Err(VPackError::ManifestInvalid(
+                    "index.hnsw.m must be at least 2".to_string(),
+                ));
+            }
+            if self.hnsw.ef_construction == 0 || self.hnsw.ef_search == 0 {
+                return Err(VPackError::ManifestInvalid(
+                    "index.hnsw.ef_construction and ef_search must be positive".to_string(),
+                ));
+            }
+        }
+        if self.index_type == IndexType::IvfPq && self.quantization.is_enabled() {
+            return Err(VPackError::ManifestInvalid(
+                "index.quantization cannot be combined with ivf-pq".to_string(),
+            ));
+        }
+        if self.index_type == IndexType::IvfPq {
+            let ivf = &self.ivf_pq;
+            if ivf.nlist == 0 || ivf.nprobe == 0 || ivf.m == 0 {
+                return Err(VPackError::ManifestInvalid(
+                    "index.ivf_pq.nlist, nprobe and m must be positive".to_string(),
+                ));
+            }
+            if !(1..=8).contains(&ivf.nbits) {
+                return Err(VPackError::ManifestInvalid(
+                    "index.ivf_pq.nbits must be between 1 and 8".to_string(),
+                ));
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,108 @@
+// parallel.rs — Data-parallel loops behind the `parallel` feature
+//
+// Each helper is an order-preserving map over independent items. With the
+// `parallel` feature it runs on rayon's global thread pool; without, it is the
+// plain sequential iterator. Items never see each other's results, and every
+// result lands in its input position, so both builds compute the same values
+// in the same order: threads change who computes a value, never the value.
+// Anything that folds across items (sums, sorts, graph insertion order) stays
+// outside these helpers, on the calling thread.
+
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+
+/// Filtering loops hand out items in blocks this long, so a thread's
+/// matches are collected together rather than one item at a time.
+#[cfg(feature = "parallel")]
+const BLOCK: usize = 1024;
+
+/// `items.iter().map(f).collect()`.
+pub(crate) fn map<T, U, F>(items: &[T], f: F) -> Vec<U>
+where
+    T: Sync,
+    U: Send,
+    F: Fn(&T) -> U + Sync + Send,
+{
+    #[cfg(feature = "parallel")]
+    return items.par_iter().map(f).collect();
+    #[cfg(not(feature = "parallel"))]
+    return items.iter().map(f).collect();
+}
+
+/// `(0..len).map(f).collect()`.
+pub(crate) fn map_range<U, F>(len: usize, f: F) -> Vec<U>
+where
+    U: Send,
+    F: Fn(usize) -> U + Sync + Send,
+{
+    #[cfg(feature = "parallel")]
+    return (0..len).into_par_iter().map(f).collect();
+    #[cfg(not(feature = "parallel"))]
+    return (0..len).map(f).collect();
+}
+
+/// `items.iter().filter_map(f).collect()`.
+pub(crate) fn filter_map<T, U, F>(items: &[T], f: F) -> Vec<U>
+where
+    T: Sync,
+    U: Send,
+    F: Fn(&T) -> Option<U> + Sync + Send,
+{
+    #[cfg(feature = "parallel")]
+    return items
+        .par_chunks(BLOCK)
+        .map(|block| block.iter().filter_map(&f).collect::<Vec<U>>())
+        .collect::<Vec<_>>()
+        .into_iter()
+        .flatten()
+        .collect();
+    #[cfg(not(feature = "parallel"))]
+    return items.iter().filter_map(f).collect();
+}
+
+/// `(0..len).filter_map(f).collect()`.
+pub(crate) fn filter_map_range<U, F>(len: usize, f: F) -> Vec<U>
+where
+    U: Send,
+    F: Fn(usize) -> Option<U> + Sync + Send,
+{
+    #[cfg(feature = "parallel")]
+    return (0..len.div_ceil(BLOCK))
+        .into_par_iter()
+        .map(|block| {
+            (block * BLOCK..len.min((block + 1) * BLOCK))
+                .filter_map(&f)
+                .collect::<Vec<U>>()
+        })
+        .collect::<Vec<_>>()
+        .into_iter()
+        .flatten()
+        .collect();
+    #[cfg(not(feature = "parallel"))]
+    return (0..len).filter_map(f).collect();
+}
+
+/// `items.iter_mut().for_each(f)`.
+pub(crate) fn for_each_mut<T, F>(items: &mut [T], f: F)
+where
+    T: Send,
+    F: Fn(&mut T) + Sync + Send,
+{
+    #[cfg(feature = "parallel")]
+    items.par_iter_mut().for_each(f);
+    #[cfg(not(feature = "parallel"))]
+    items.iter_mut().for_each(f);
+}
+
+/// `items.iter().position(predicate)`: the first match, even when a later
+/// item is checked first.
+pub(crate) fn position<T, F>(items: &[T], predicate: F) -> Option<usize>
+where
+    T: Sync,
+    F: Fn(&T) -> bool + Sync + Send,
+{
+    #[cfg(feature = "parallel")]
+    return items.par_iter().position_first(predicate);
+    #[cfg(not(feature = "parallel"))]
+    return items.iter().position(predicate);
+}
@@ -0,0 +1,676 @@
+// patch.rs — .vpack.patch files (RFC-0007)
+//
+// A patch carries the chunks that changed between two artifacts of one pack
+// (CHUNK DIFF), replacement IVF-PQ inverted lists for the clusters those
+// chunks touch (INDEX PATCH), and the changelog chunk the result added to its
+// CHANGELOG section, if any (CHANGELOG ENTRY). It is anchored by the SHA-256
+// of the complete base and result .vpack files.
+//
+// Applying a patch stages the result beside the live index and serializes
+// it; the staged pack replaces the live index only if those bytes hash to
+// `result_artifact_hash`. Flat packs keep the base's row order, with modified
+// chunks in place and added chunks appended. det-hnsw and IVF-PQ packs are
+// staged in chunk-ID order: IVF-PQ lists are spliced, and the det-hnsw graph
+// is rebuilt, which is canonical. Plain hnsw graphs depend on insertion order
+// and there is no INDEX PATCH for them, so hnsw packs cannot be patched.
+//
+// INDEX PATCH node ids are rows of the result artifact. Lists not in the
+// patch are renumbered by the consumer from its own base rows.
+//
+// Layout: a 140-byte header (magic, spec_version, flags, section_count, both
+// artifact hashes, both versions as null-padded UTF-8), the section table
+// (id, offset, length, CRC-32, as in .vpack), then bincode sections back to
+// back.
+
+use crate::chunk::{Chunk, EmbeddedChunk};
+use crate::diff::{diff_rows, RowDiff};
+use crate::error::VPackError;
+use crate::hash::{crc32, sha256, to_hex};
+use crate::index::VPackIndex;
+use crate::ivf_pq::{ClusterList, IvfPqIndex};
+use crate::options::IndexType;
+use crate::serialize::{decode_metadata, encode_metadata, serialize, PackMetadata, SectionEntry};
+use crate::store;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+const MAGIC: &[u8; 8] = b"VPATCH\0\0";
+const FORMAT_VERSION: u8 = 0x01;
+const HEADER_LEN: usize = 140;
+/// Null-padded UTF-8 version fields in the header.
+const VERSION_LEN: usize = 32;
+/// section_id: u8, offset: u64, length: u64, checksum: u32
+const SECTION_ENTRY_LEN: usize = 21;
+
+/// Header flag: an INDEX PATCH section is present.
+pub const FLAG_HAS_INDEX_PATCH: u16 = 0x0001;
+/// Header flag: a CHANGELOG ENTRY section is present.
+pub const FLAG_HAS_CHANGELOG_ENTRY: u16 = 0x0002;
+/// Header flag: the index options or IVF-PQ codebook changed. The CHUNK DIFF
+/// is informational only; consumers must download the full pack.
+pub const FLAG_CODEBOOK_CHANGED: u16 = 0x0004;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum PatchSectionId {
+    /// Added, modified and removed chunks.
+    ChunkDiff = 0x01,
+    /// Replacement inverted lists for the affected IVF-PQ clusters.
+    IndexPatch = 0x02,
+    /// A pre-embedded changelog chunk (RFC-0004).
+    ChangelogEntry = 0x03,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PatchHeader {
+    pub spec_version: u8,
+    pub flags: u16,
+    /// SHA-256 of the complete base .vpack file.
+    pub base_artifact_hash: [u8; 32],
+    /// SHA-256 of the complete result .vpack file.
+    pub result_artifact_hash: [u8; 32],
+    pub base_version: String,
+    pub result_version: String,
+    pub sections: Vec<SectionEntry>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct PatchChunk {
+    id: String,
+    text: String,
+    metadata: PackMetadata,
+    vector: Vec<f32>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ModifiedChunk {
+    old_id: String,
+    chunk: PatchChunk,
+    before_text: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct RemovedChunk {
+    id: String,
+    text: String,
+    metadata: PackMetadata,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ChunkDiffSection {
+    added: Vec<PatchChunk>,
+    modified: Vec<ModifiedChunk>,
+    removed: Vec<RemovedChunk>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ClusterEntry {
+    node_id: u64,
+    pq_code: Vec<u8>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct IndexPatchSection {
+    /// affected_cluster_ids[i] is replaced by affected_cluster_lists[i].
+    affected_cluster_ids: Vec<u32>,
+    affected_cluster_lists: Vec<Vec<ClusterEntry>>,
+    total_clusters: u32,
+    codebook_hash: [u8; 32],
+}
+
+#[derive(Serialize, Deserialize)]
+struct ChangelogEntrySection {
+    chunk: PatchChunk,
+}
+
+/// Compute the patch that turns `base` into `result`. A changelog chunk in
+/// `result` but not in `base` is carried as the CHANGELOG ENTRY; a patch can
+/// carry one, and cannot remove any.
+///
+/// When the index options or IVF-PQ codebooks differ, the patch is flagged
+/// `FLAG_CODEBOOK_CHANGED` and carries the chunk diff only. Otherwise it is
+/// staged on `base` before being returned, and fails with `PatchFailed`
+/// unless that reproduces `result` byte for byte, or when the pack is an
+/// `hnsw` one. Build `result` with `VPackIndex::build_with_codebook` (or by
+/// mutating a copy of `base` and compacting it) to keep IVF-PQ packs
+/// patchable, and keep flat packs in the base's row order.
+pub fn create_patch(base: &VPackIndex, result: &VPackIndex) -> Result<Vec<u8>, VPackError> {
+    if result.needs_compaction() {
+        return Err(VPackError::PatchFailed(
+            "the result has deleted or inserted chunks pending; compact it first".to_string(),
+        ));
+    }
+    let base_bytes = serialize(base)?;
+    let result_bytes = serialize(result)?;
+
+    let rows = diff_rows(base, result)?;
+    let codebook_changed = base.options != result.options
+        || match (&base.ivf_pq, &result.ivf_pq) {
+            (Some(old), Some(new)) => old.codebook_hash() != new.codebook_hash(),
+            _ => false,
+        };
+
+    let mut flags = 0;
+    let mut sections = vec![(
+        PatchSectionId::ChunkDiff,
+        bincode::serialize(&chunk_diff(base, result, &rows)?)?,
+    )];
+    if codebook_changed {
+        flags |= FLAG_CODEBOOK_CHANGED;
+    } else {
+        check_patchable(base)?;
+        if let (Some(old), Some(new)) = (&base.ivf_pq, &result.ivf_pq) {
+            flags |= FLAG_HAS_INDEX_PATCH;
+            sections.push((
+                PatchSectionId::IndexPatch,
+                bincode::serialize(&index_patch(old, new, &rows))?,
+            ));
+        }
+        let mut appended = result
+            .changelog
+            .entries()
+            .iter()
+            .filter(|entry| !base.changelog.entries().contains(entry));
+        if let Some(chunk) = appended.next() {
+            if appended.next().is_some() {
+                return Err(VPackError::PatchFailed(
+                    "a patch carries one changelog chunk; publish the full pack".to_string(),
+                ));
+            }
+            flags |= FLAG_HAS_CHANGELOG_ENTRY;
+            let entry = ChangelogEntrySection {
+                chunk: PatchChunk::from_embedded(chunk)?,
+            };
+            sections.push((PatchSectionId::ChangelogEntry, bincode::serialize(&entry)?));
+        }
+    }
+
+    let header = PatchHeader {
+        spec_version: FORMAT_VERSION,
+        flags,
+        base_artifact_hash: sha256(&base_bytes),
+        result_artifact_hash: sha256(&result_bytes),
+        base_version: manifest_version(base.manifest()).to_string(),
+        result_version: manifest_version(result.manifest()).to_string(),
+        sections: Vec::new(),
+    };
+    let patch = write_patch(header, &sections)?;
+    if !codebook_changed {
+        let header = read_patch_header(&patch)?;
+        stage_and_check(base, &header, &patch).map_err(|err| {
+            VPackError::PatchFailed(format!(
+                "the result cannot be reproduced from the base and a patch ({err}); \
+                publish the full pack"
+            ))
+        })?;
+    }
+    Ok(patch)
+}
+
+/// Apply `patch` to `index` and return the result artifact's bytes, for the
+/// caller to write out.
+///
+/// Atomic: `index` is replaced only once the staged result hashes to the
+/// patch's `result_artifact_hash`, and is unchanged on any error. Fails with
+/// `PatchBaseMismatch` when `index` is not the patch's base, and with
+/// `PatchFailed` when the patch needs a full download instead.
+pub fn apply_patch(index: &mut VPackIndex, patch: &[u8]) -> Result<Vec<u8>, VPackError> {
+    let header = read_patch_header(patch)?;
+    if header.flags & FLAG_CODEBOOK_CHANGED != 0 {
+        return Err(VPackError::PatchFailed(format!(
+            "the index was retrained for version '{}'; download the full pack",
+            header.result_version
+        )));
+    }
+    check_patchable(index)?;
+    let local_hash = sha256(&serialize(index)?);
+    if local_hash != header.base_artifact_hash {
+        return Err(VPackError::PatchBaseMismatch {
+            expected: format!("sha256:{}", to_hex(&header.base_artifact_hash)),
+            got: format!("sha256:{}", to_hex(&local_hash)),
+        });
+    }
+
+    let (staged, result_bytes) = stage_and_check(index, &header, patch)?;
+    *index = staged;
+    Ok(result_bytes)
+}
+
+/// Plain HNSW graphs depend on insertion order, and RFC-0007 has no INDEX
+/// PATCH that carries their links.
+fn check_patchable(index: &VPackIndex) -> Result<(), VPackError> {
+    if index.options.index_type == IndexType::Hnsw {
+        return Err(VPackError::PatchFailed(
+            "hnsw packs cannot be patched; publish the full pack, or build with \
+            det-hnsw or ivf-pq"
+                .to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Stage `patch` on `base` and serialize the result, which must hash to the
+/// patch's `result_artifact_hash`.
+fn stage_and_check(
+    base: &VPackIndex,
+    header: &PatchHeader,
+    patch: &[u8],
+) -> Result<(VPackIndex, Vec<u8>), VPackError> {
+    let diff: ChunkDiffSection = bincode::deserialize(
+        section(patch, header, PatchSectionId::ChunkDiff)?
+            .ok_or_else(|| missing(PatchSectionId::ChunkDiff))?,
+    )?;
+    let staged = stage(base, header, patch, diff)?;
+    let result_bytes = serialize(&staged)?;
+    if sha256(&result_bytes) != header.result_artifact_hash {
+        return Err(VPackError::PatchFailed(
+            "the patched pack does not match result_artifact_hash".to_string(),
+        ));
+    }
+    Ok((staged, result_bytes))
+}
+
+/// The changelog chunk carried by `patch`, if any.
+pub fn read_changelog_entry(patch: &[u8]) -> Result<Option<EmbeddedChunk>, VPackError> {
+    let header = read_patch_header(patch)?;
+    match section(patch, &header, PatchSectionId::ChangelogEntry)? {
+        Some(data) => {
+            let entry: ChangelogEntrySection = bincode::deserialize(data)?;
+            entry.chunk.into_embedded().map(Some)
+        }
+        None => Ok(None),
+    }
+}
+
+/// Parse the patch header and section table. Does not read any section.
+pub fn read_patch_header(bytes: &[u8]) -> Result<PatchHeader, VPackError> {
+    if bytes.len() < HEADER_LEN {
+        return Err(VPackError::InvalidFormat("patch too short".to_string()));
+    }
+    if !bytes.starts_with(MAGIC) {
+        return Err(VPackError::InvalidFormat(
+            "bad .vpack.patch magic bytes".to_string(),
+        ));
+    }
+    let spec_version = bytes[8];
+    if spec_version != FORMAT_VERSION {
+        return Err(VPackError::InvalidFormat(format!(
+            "unsupported .vpack.patch format version 0x{spec_version:02x}",
+        )));
+    }
+
+    let section_count = bytes[11] as usize;
+    let table_end = HEADER_LEN + section_count * SECTION_ENTRY_LEN;
+    if bytes.len() < table_end {
+        return Err(VPackError::InvalidFormat(
+            "truncated patch section table".to_string(),
+        ));
+    }
+    let sections = bytes[HEADER_LEN..table_end]
+        .chunks_exact(SECTION_ENTRY_LEN)
+        .map(|entry| SectionEntry {
+            id: entry[0],
+            offset: u64::from_le_bytes(entry[1..9].try_into().unwrap()),
+            length: u64::from_le_bytes(entry[9..17].try_into().unwrap()),
+            checksum: u32::from_le_bytes(entry[17..21].try_into().unwrap()),
+        })
+        .collect();
+
+    Ok(PatchHeader {
+        spec_version,
+        flags: u16::from_le_bytes([bytes[9], bytes[10]]),
+        base_artifact_hash: bytes[12..44].try_into().unwrap(),
+        result_artifact_hash: bytes[44..76].try_into().unwrap(),
+        base_version: read_version(&bytes[76..108])?,
+        result_version: read_version(&bytes[108..140])?,
+        sections,
+    })
+}
+
+fn chunk_diff(
+    base: &VPackIndex,
+    result: &VPackIndex,
+    rows: &RowDiff,
+) -> Result<ChunkDiffSection, VPackError> {
+    // Added chunks in result row order, which is where flat packs append them.
+    let mut added = rows.added.clone();
+    added.sort_unstable();
+    Ok(ChunkDiffSection {
+        added: added
+            .iter()
+            .map(|&row| PatchChunk::from_row(result, row))
+            .collect::<Result<_, VPackError>>()?,
+        modified: rows
+            .modified
+            .iter()
+            .map(|&(old, new)| {
+                Ok(ModifiedChunk {
+                    old_id: base.chunks.id(old).to_string(),
+                    chunk: PatchChunk::from_row(result, new)?,
+                    before_text: base.chunks.text(old)?.to_string(),
+                })
+            })
+            .collect::<Result<_, VPackError>>()?,
+        removed: rows
+            .removed
+            .iter()
+            .map(|&row| {
+                let chunk = base.chunks.to_chunk(row)?;
+                Ok(RemovedChunk {
+                    id: chunk.id,
+                    text: chunk.text,
+                    metadata: encode_metadata(&chunk.metadata)?,
+                })
+            })
+            .collect::<Result<_, VPackError>>()?,
+    })
+}
+
+/// The result lists of every cluster holding a changed chunk, before or after.
+fn index_patch(base: &IvfPqIndex, result: &IvfPqIndex, rows: &RowDiff) -> IndexPatchSection {
+    let base_lists = base.assignments();
+    let result_lists = result.assignments();
+    let old_rows = rows
+        .modified
+        .iter()
+        .map(|&(old, _)| old)
+        .chain(rows.removed.iter().copied());
+    let new_rows = rows
+        .modified
+        .iter()
+        .map(|&(_, new)| new)
+        .chain(rows.added.iter().copied());
+    let affected: BTreeSet<u32> = old_rows
+        .map(|row| base_lists[row])
+        .chain(new_rows.map(|row| result_lists[row]))
+        .collect();
+    IndexPatchSection {
+        affected_cluster_lists: affected
+            .iter()
+            .map(|&list| {
+                result
+                    .cluster(list as usize)
+                    .into_iter()
+                    .map(|(node, codes)| ClusterEntry {
+                        node_id: node as u64,
+                        pq_code: codes.to_vec(),
+                    })
+                    .collect()
+            })
+            .collect(),
+        affected_cluster_ids: affected.into_iter().collect(),
+        total_clusters: result.nlist() as u32,
+        codebook_hash: result.codebook_hash(),
+    }
+}
+
+/// Build the result index from `base` and the patch, without touching `base`.
+fn stage(
+    base: &VPackIndex,
+    header: &PatchHeader,
+    patch: &[u8],
+    diff: ChunkDiffSection,
+) -> Result<VPackIndex, VPackError> {
+    let removed: HashSet<&str> = diff.removed.iter().map(|chunk| chunk.id.as_str()).collect();
+    let mut modified: HashMap<String, PatchChunk> = diff
+        .modified
+        .into_iter()
+        .map(|chunk| (chunk.old_id, chunk.chunk))
+        .collect();
+
+    // (base row, or None for a chunk from the patch; chunk). A modified chunk
+    // takes its base row, and added chunks follow.
+    let mut staged = Vec::with_capacity(base.chunks.len() + diff.added.len());
+    for row in 0..base.chunks.len() {
+        let id = base.chunks.id(row);
+        if base.tombstones.contains(row) || removed.contains(id) {
+            continue;
+        }
+        match modified.remove(id) {
+            Some(chunk) => staged.push((None, patched_chunk(base, chunk)?)),
+            None => {
+                let chunk = EmbeddedChunk {
+                    chunk: base.chunks.to_chunk(row)?,
+                    vector: base.vectors.get(row).to_vec(),
+                };
+                staged.push((Some(row), chunk));
+            }
+        }
+    }
+    if let Some(id) = modified.keys().next() {
+        return Err(VPackError::PatchFailed(format!(
+            "modified chunk '{id}' is not in the base"
+        )));
+    }
+    for chunk in diff.added {
+        staged.push((None, patched_chunk(base, chunk)?));
+    }
+    // Flat packs keep the base's row order; the others are staged in
+    // chunk-ID order.
+    if base.options.index_type != IndexType::Flat {
+        staged.sort_by(|(_, a), (_, b)| a.chunk.id.as_bytes().cmp(b.chunk.id.as_bytes()));
+    }
+    let mut ids = HashSet::with_capacity(staged.len());
+    if let Some((_, chunk)) = staged
+        .iter()
+        .find(|(_, chunk)| !ids.insert(&chunk.chunk.id))
+    {
+        return Err(VPackError::PatchFailed(format!(
+            "chunk '{}' is both in the base and added by the patch",
+            chunk.chunk.id
+        )));
+    }
+
+    let mut changelog = base.changelog.clone();
+    if let Some(data) = section(patch, header, PatchSectionId::ChangelogEntry)? {
+        let entry: ChangelogEntrySection = bincode::deserialize(data)?;
+        changelog.insert(entry.chunk.into_embedded()?);
+    }
+
+    let manifest = with_version(base.manifest(), &header.result_version);
+    let options = base.options().clone();
+    let Some(base_ivf) = &base.ivf_pq else {
+        let chunks = staged.into_iter().map(|(_, chunk)| chunk).collect();
+        return VPackIndex::build_with_options(chunks, manifest, options)?
+            .with_changelog(changelog);
+    };
+
+    let index_patch: IndexPatchSection = bincode::deserialize(
+        section(patch, header, PatchSectionId::IndexPatch)?
+            .ok_or_else(|| missing(PatchSectionId::IndexPatch))?,
+    )?;
+    if index_patch.codebook_hash != base_ivf.codebook_hash() {
+        return Err(VPackError::PatchFailed(
+            "INDEX PATCH codebook_hash does not match the local codebook".to_string(),
+        ));
+    }
+    if index_patch.total_clusters as usize != base_ivf.nlist()
+        || index_patch.affected_cluster_ids.len() != index_patch.affected_cluster_lists.len()
+    {
+        return Err(VPackError::PatchFailed(
+            "INDEX PATCH does not match the local cluster count".to_string(),
+        ));
+    }
+
+    let mut renumbered = vec![None; base.chunks.len()];
+    for (node, (row, _)) in staged.iter().enumerate() {
+        if let Some(row) = row {
+            renumbered[*row] = Some(node as u32);
+        }
+    }
+    let replaced: Vec<(u32, ClusterList)> = index_patch
+        .affected_cluster_ids
+        .into_iter()
+        .zip(index_patch.affected_cluster_lists)
+        .map(|(list, entries)| {
+            let entries = entries
+                .into_iter()
+                // Out-of-range ids are rejected by `patched`.
+                .map(|entry| {
+                    (
+                        u32::try_from(entry.node_id).unwrap_or(u32::MAX),
+                        entry.pq_code,
+                    )
+                })
+                .collect();
+            (list, entries)
+        })
+        .collect();
+    let ivf = base_ivf
+        .patched(staged.len(), &renumbered, &replaced)
+        .ok_or_else(|| {
+            VPackError::PatchFailed("INDEX PATCH does not match the chunk diff".to_string())
+        })?;
+
+    let mut chunks: Vec<EmbeddedChunk> = staged.into_iter().map(|(_, chunk)| chunk).collect();
+    if options.ivf_pq.rerank == 0 {
+        for (chunk, vector) in chunks.iter_mut().zip(ivf.reconstruct()) {
+            chunk.vector = vector;
+        }
+    }
+    let (chunks, vectors) = store::split(chunks, base.dimensions(), options.stored_precision());
+    VPackIndex::from_parts(chunks, vectors, manifest, options, None, Some(ivf), None)?
+        .with_changelog(changelog)
+}
+
+/// A chunk carried by the patch, checked against the pack's dimensions.
+fn patched_chunk(base: &VPackIndex, chunk: PatchChunk) -> Result<EmbeddedChunk, VPackError> {
+    let chunk = chunk.into_embedded()?;
+    if chunk.vector.len() != base.dimensions() {
+        return Err(VPackError::PatchFailed(format!(
+            "chunk '{}' has a {}d vector, the pack is {}d",
+            chunk.chunk.id,
+            chunk.vector.len(),
+            base.dimensions()
+        )));
+    }
+    Ok(chunk)
+}
+
+impl PatchChunk {
+    fn from_row(index: &VPackIndex, row: usize) -> Result<Self, VPackError> {
+        let chunk = index.chunks.to_chunk(row)?;
+        Ok(Self {
+            id: chunk.id,
+            text: chunk.text,
+            metadata: encode_metadata(&chunk.metadata)?,
+            vector: index.vectors.get(row).to_vec(),
+        })
+    }
+
+    fn from_embedded(embedded: &EmbeddedChunk) -> Result<Self, VPackError> {
+        Ok(Self {
+            id: embedded.chunk.id.clone(),
+            text: embedded.chunk.text.clone(),
+            metadata: encode_metadata(&embedded.chunk.metadata)?,
+            vector: embedded.vector.clone(),
+        })
+    }
+
+    fn into_embedded(self) -> Result<EmbeddedChunk, VPackError> {
+        Ok(EmbeddedChunk {
+            chunk: Chunk {
+                id: self.id,
+                text: self.text,
+                metadata: decode_metadata(self.metadata)?,
+            },
+            vector: self.vector,
+        })
+    }
+}
+
+fn manifest_version(manifest: &Value) -> &str {
+    manifest["version"].as_str().unwrap_or_default()
+}
+
+/// `manifest` with `version` set. Unchanged when `version` is empty.
+fn with_version(manifest: &Value, version: &str) -> Value {
+    let mut manifest = manifest.clone();
+    if let (Some(fields), false) = (manifest.as_object_mut(), version.is_empty()) {
+        fields.insert("version".to_string(), Value::from(version));
+    }
+    manifest
+}
+
+fn write_patch(
+    mut header: PatchHeader,
+    sections: &[(PatchSectionId, Vec<u8>)],
+) -> Result<Vec<u8>, VPackError> {
+    let mut offset = HEADER_LEN + sections.len() * SECTION_ENTRY_LEN;
+    for (id, data) in sections {
+        header.sections.push(SectionEntry {
+            id: *id as u8,
+            offset: offset as u64,
+            length: data.len() as u64,
+            checksum: crc32(data),
+        });
+        offset += data.len();
+    }
+
+    let mut buf = Vec::with_capacity(offset);
+    buf.extend_from_slice(MAGIC);
+    buf.push(header.spec_version);
+    buf.extend_from_slice(&header.flags.to_le_bytes());
+    buf.push(sections.len() as u8);
+    buf.extend_from_slice(&header.base_artifact_hash);
+    buf.extend_from_slice(&header.result_artifact_hash);
+    for version in [&header.base_version, &header.result_version] {
+        if version.len() > VERSION_LEN {
+            return Err(VPackError::ManifestInvalid(format!(
+                "version '{version}' is longer than {VERSION_LEN} bytes"
+            )));
+        }
+        buf.extend_from_slice(version.as_bytes());
+        buf.resize(buf.len() + VERSION_LEN - version.len(), 0);
+    }
+    for entry in &header.sections {
+        buf.push(entry.id);
+        buf.extend_from_slice(&entry.offset.to_le_bytes());
+        buf.extend_from_slice(&entry.length.to_le_bytes());
+        buf.extend_from_slice(&entry.checksum.to_le_bytes());
+    }
+    for (_, data) in sections {
+        buf.extend_from_slice(data);
+    }
+    Ok(buf)
+}
+
+fn read_version(field: &[u8]) -> Result<String, VPackError> {
+    let len = field
+        .iter()
+        .position(|&byte| byte == 0)
+        .unwrap_or(field.len());
+    String::from_utf8(field[..len].to_vec())
+        .map_err(|err| VPackError::InvalidFormat(format!("patch version: {err}")))
+}
+
+fn section<'a>(
+    bytes: &'a [u8],
+    header: &PatchHeader,
+    id: PatchSectionId,
+) -> Result<Option<&'a [u8]>, VPackError> {
+    let Some(entry) = header.sections.iter().find(|entry| entry.id == id as u8) else {
+        return Ok(None);
+    };
+    let start = usize::try_from(entry.offset).ok();
+    let end = start.and_then(|start| start.checked_add(usize::try_from(entry.length).ok()?));
+    let data = match (start, end) {
+        (Some(start), Some(end)) if end <= bytes.len() => &bytes[start..end],
+        _ => {
+            return Err(VPackError::InvalidFormat(format!(
+                "{id:?} section is out of bounds"
+            )))
+        }
+    };
+    if crc32(data) != entry.checksum {
+        return Err(VPackError::InvalidFormat(format!(
+            "{id:?} section checksum mismatch"
+        )));
+    }
+    Ok(Some(data))
+}
+
+fn missing(id: PatchSectionId) -> VPackError {
+    VPackError::InvalidFormat(format!("missing {id:?} patch section"))
+}
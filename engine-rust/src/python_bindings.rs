@@ -0,0 +1,323 @@
+// python_bindings.rs — PyO3 bindings: the `vpack` Python extension module
+//
+// Built with maturin (see pyproject.toml). Query vectors are NumPy float32
+// arrays, read in place when contiguous. Chunks, manifests, options and
+// results cross the boundary as plain Python dicts and lists, converted with
+// the `json` module. Each VPackError code is raised as its own exception
+// class, all subclasses of `vpack.VPackError` with the code in `.code`.
+
+use numpy::{PyArray2, PyArrayMethods, PyReadonlyArray1, PyReadonlyArray2};
+use pyo3::create_exception;
+use pyo3::exceptions::{PyException, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::{PyBytes, PyDict};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::chunk::EmbeddedChunk;
+use crate::embeddings;
+use crate::error::VPackError as EngineError;
+use crate::index::VPackIndex as EngineIndex;
+use crate::options::BuildOptions;
+use crate::query::{QueryOptions, QueryResult};
+use crate::serialize::{self, LoadOptions};
+
+create_exception!(
+    vpack,
+    VPackError,
+    PyException,
+    "Base class of vpack errors."
+);
+create_exception!(vpack, DimensionMismatchError, VPackError);
+create_exception!(vpack, ModelMismatchError, VPackError);
+create_exception!(vpack, EmptyIndexError, VPackError);
+create_exception!(vpack, ModelHashMismatchError, VPackError);
+create_exception!(vpack, SerializeError, VPackError);
+create_exception!(vpack, UnknownModelError, VPackError);
+create_exception!(vpack, DeserializeError, VPackError);
+create_exception!(vpack, ManifestInvalidError, VPackError);
+create_exception!(vpack, DuplicateChunkIdError, VPackError);
+create_exception!(vpack, PatchBaseMismatchError, VPackError);
+create_exception!(vpack, PatchFailedError, VPackError);
+create_exception!(vpack, InvalidQueryError, VPackError);
+
+fn py_error_from_vpack(err: EngineError) -> PyErr {
+    let code = err.code();
+    let message = err.to_string();
+    let py_err = match err {
+        EngineError::DimensionMismatch { .. } => DimensionMismatchError::new_err(message),
+        EngineError::ModelMismatch { .. } => ModelMismatchError::new_err(message),
+        EngineError::EmptyIndex => EmptyIndexError::new_err(message),
+        EngineError::ModelHashMismatch { .. } => ModelHashMismatchError::new_err(message),
+        EngineError::Serialize(_) => SerializeError::new_err(message),
+        EngineError::UnknownModel(_) => UnknownModelError::new_err(message),
+        EngineError::InvalidFormat(_) => DeserializeError::new_err(message),
+        EngineError::ManifestInvalid(_) => ManifestInvalidError::new_err(message),
+        EngineError::DuplicateChunkId(_) => DuplicateChunkIdError::new_err(message),
+        EngineError::PatchBaseMismatch { .. } => PatchBaseMismatchError::new_err(message),
+        EngineError::PatchFailed(_) => PatchFailedError::new_err(message),
+        EngineError::InvalidQuery(_) => InvalidQueryError::new_err(message),
+    };
+    with_code(py_err, code)
+}
+
+/// Set `.code` to the VPackError code, as in `VPackError.code` in TypeScript.
+fn with_code(err: PyErr, code: &str) -> PyErr {
+    Python::attach(|py| {
+        // Setting an attribute on a fresh exception instance cannot fail.
+        let _ = err.value(py).setattr("code", code);
+    });
+    err
+}
+
+/// A Python object (dicts, lists, scalars) as a Rust value, via `json.dumps`.
+fn from_py<T: DeserializeOwned>(value: &Bound<'_, PyAny>) -> PyResult<T> {
+    let json = value.py().import("json")?.call_method1("dumps", (value,))?;
+    serde_json::from_str(&json.extract::<String>()?).map_err(invalid_value)
+}
+
+fn invalid_value(err: serde_json::Error) -> PyErr {
+    with_code(
+        DeserializeError::new_err(format!("invalid value: {err}")),
+        "DESERIALIZE_FAILED",
+    )
+}
+
+/// A Rust value as plain Python objects, via `json.loads`.
+fn to_py<'py, T: Serialize>(py: Python<'py>, value: &T) -> PyResult<Bound<'py, PyAny>> {
+    let json = serde_json::to_string(value)
+        .map_err(|err| PyValueError::new_err(format!("not JSON-serializable: {err}")))?;
+    py.import("json")?.call_method1("loads", (json,))
+}
+
+/// Query input: text, a float32 NumPy array, or any sequence of floats.
+#[derive(FromPyObject)]
+enum QueryInput<'py> {
+    Text(String),
+    Array(PyReadonlyArray1<'py, f32>),
+    Sequence(Vec<f32>),
+}
+
+/// `QueryOptions` from Python keyword arguments. Names are snake_case, as in
+/// the Rust struct: `top_k`, `min_score`, `filter`, `temporal_weight`, ...
+fn query_options(options: Option<&Bound<'_, PyDict>>) -> PyResult<QueryOptions> {
+    let Some(options) = options else {
+        return Ok(QueryOptions::default());
+    };
+    let camel = PyDict::new(options.py());
+    for (key, value) in options {
+        camel.set_item(snake_to_camel(&key.extract::<String>()?), value)?;
+    }
+    from_py(camel.as_any())
+}
+
+fn snake_to_camel(name: &str) -> String {
+    let mut parts = name.split('_');
+    let mut camel = parts.next().unwrap_or_default().to_string();
+    for part in parts {
+        let mut chars = part.chars();
+        if let Some(first) = chars.next() {
+            camel.extend(first.to_uppercase());
+            camel.push_str(chars.as_str());
+        }
+    }
+    camel
+}
+
+#[pyclass(name = "VPackIndex", module = "vpack", frozen)]
+pub struct PyIndex {
+    inner: EngineIndex,
+}
+
+#[pymethods]
+impl PyIndex {
+    /// Build an index. `chunks` are dicts with `id`, `text`, `metadata` and
+    /// `vector`; alternatively pass the vectors as a 2-D float32 array, one
+    /// row per chunk.
+    #[staticmethod]
+    #[pyo3(signature = (chunks, manifest, vectors=None, options=None))]
+    fn build(
+        py: Python<'_>,
+        chunks: &Bound<'_, PyAny>,
+        manifest: &Bound<'_, PyAny>,
+        vectors: Option<PyReadonlyArray2<'_, f32>>,
+        options: Option<&Bound<'_, PyAny>>,
+    ) -> PyResult<Self> {
+        let mut values: Vec<serde_json::Value> = from_py(chunks)?;
+        if let Some(vectors) = vectors {
+            let vectors = vectors.as_array();
+            if vectors.nrows() != values.len() {
+                return Err(PyValueError::new_err(format!(
+                    "{} chunks but {} vectors",
+                    values.len(),
+                    vectors.nrows()
+                )));
+            }
+            for (value, row) in values.iter_mut().zip(vectors.rows()) {
+                if let Some(chunk) = value.as_object_mut() {
+                    chunk.insert("vector".to_string(), row.to_vec().into());
+                }
+            }
+        }
+        let chunks: Vec<EmbeddedChunk> =
+            serde_json::from_value(values.into()).map_err(invalid_value)?;
+        let manifest: serde_json::Value = from_py(manifest)?;
+        let index = match options {
+            Some(options) => {
+                let options =
+                    BuildOptions::from_value(from_py(options)?).map_err(py_error_from_vpack)?;
+                py.detach(|| EngineIndex::build_with_options(chunks, manifest, options))
+            }
+            None => py.detach(|| EngineIndex::build(chunks, manifest)),
+        }
+        .map_err(py_error_from_vpack)?;
+        Ok(Self { inner: index })
+    }
+
+    /// Load an index from `.vpack` bytes. `model` and `model_hash` are
+    /// checked as in `load`.
+    #[staticmethod]
+    #[pyo3(signature = (data, model=None, model_hash=None))]
+    fn deserialize(
+        py: Python<'_>,
+        data: &[u8],
+        model: Option<String>,
+        model_hash: Option<String>,
+    ) -> PyResult<Self> {
+        let options = LoadOptions { model, model_hash };
+        let index = py
+            .detach(|| serialize::deserialize_with_options(data, &options))
+            .map_err(py_error_from_vpack)?;
+        Ok(Self { inner: index })
+    }
+
+    /// The `.vpack` bytes of this index.
+    fn serialize<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyBytes>> {
+        let bytes = py
+            .detach(|| serialize::serialize(&self.inner))
+            .map_err(py_error_from_vpack)?;
+        Ok(PyBytes::new(py, &bytes))
+    }
+
+    /// Query with text or a vector. Keyword arguments are QueryOptions
+    /// fields in snake_case, e.g. `top_k=10, min_score=0.5`. Returns a list
+    /// of result dicts, best first.
+    #[pyo3(signature = (input, **options))]
+    fn query<'py>(
+        &self,
+        py: Python<'py>,
+        input: QueryInput<'py>,
+        options: Option<&Bound<'py, PyDict>>,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let options = query_options(options)?;
+        let results: Result<Vec<QueryResult>, EngineError> = match &input {
+            QueryInput::Text(text) => py.detach(|| self.inner.query_text(text, options)),
+            QueryInput::Array(array) => match array.as_slice() {
+                // Contiguous arrays are read in place.
+                Ok(vector) => py.detach(|| self.inner.query(vector, options)),
+                Err(_) => {
+                    let vector = array.to_vec()?;
+                    py.detach(|| self.inner.query(&vector, options))
+                }
+            },
+            QueryInput::Sequence(vector) => py.detach(|| self.inner.query(vector, options)),
+        };
+        to_py(py, &results.map_err(py_error_from_vpack)?)
+    }
+
+    fn manifest<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        to_py(py, self.inner.manifest())
+    }
+
+    fn chunk_count(&self) -> usize {
+        self.inner.chunk_count()
+    }
+
+    fn dimensions(&self) -> usize {
+        self.inner.dimensions()
+    }
+
+    fn __len__(&self) -> usize {
+        self.inner.chunk_count()
+    }
+}
+
+/// Open a `.vpack` file through a memory map. The file must not change while
+/// the index is open. Passing `model` or `model_hash` rejects a pack that
+/// pins a different embedding model.
+#[pyfunction]
+#[pyo3(signature = (path, model=None, model_hash=None))]
+fn load(
+    py: Python<'_>,
+    path: std::path::PathBuf,
+    model: Option<String>,
+    model_hash: Option<String>,
+) -> PyResult<PyIndex> {
+    let options = LoadOptions { model, model_hash };
+    let index = py
+        .detach(|| EngineIndex::open_mmap_with_options(path, &options))
+        .map_err(py_error_from_vpack)?;
+    Ok(PyIndex { inner: index })
+}
+
+/// Embed `texts` with the embedder plugin `config`, as a float32 array with
+/// one row per text.
+#[pyfunction]
+fn embed<'py>(
+    py: Python<'py>,
+    texts: Vec<String>,
+    config: &Bound<'py, PyAny>,
+) -> PyResult<Bound<'py, PyArray2<f32>>> {
+    let config: serde_json::Value = from_py(config)?;
+    let vectors = py
+        .detach(|| embeddings::embed_texts(config, texts))
+        .map_err(py_error_from_vpack)?;
+    PyArray2::from_vec2(py, &vectors).map_err(|err| PyValueError::new_err(err.to_string()))
+}
+
+/// `sha256:<hex>` of the local weights for `config`, the value to pin as
+/// `model_hash` in the manifest.
+#[pyfunction]
+fn model_hash(py: Python<'_>, config: &Bound<'_, PyAny>) -> PyResult<String> {
+    let config: serde_json::Value = from_py(config)?;
+    py.detach(|| embeddings::model_hash(config))
+        .map_err(py_error_from_vpack)
+}
+
+#[pymodule]
+fn vpack(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    let py = m.py();
+    m.add_class::<PyIndex>()?;
+    m.add_function(wrap_pyfunction!(load, m)?)?;
+    m.add_function(wrap_pyfunction!(embed, m)?)?;
+    m.add_function(wrap_pyfunction!(model_hash, m)?)?;
+    m.add("VPackError", py.get_type::<VPackError>())?;
+    m.add(
+        "DimensionMismatchError",
+        py.get_type::<DimensionMismatchError>(),
+    )?;
+    m.add("ModelMismatchError", py.get_type::<ModelMismatchError>())?;
+    m.add("EmptyIndexError", py.get_type::<EmptyIndexError>())?;
+    m.add(
+        "ModelHashMismatchError",
+        py.get_type::<ModelHashMismatchError>(),
+    )?;
+    m.add("SerializeError", py.get_type::<SerializeError>())?;
+    m.add("UnknownModelError", py.get_type::<UnknownModelError>())?;
+    m.add("DeserializeError", py.get_type::<DeserializeError>())?;
+    m.add(
+        "ManifestInvalidError",
+        py.get_type::<ManifestInvalidError>(),
+    )?;
+    m.add(
+        "DuplicateChunkIdError",
+        py.get_type::<DuplicateChunkIdError>(),
+    )?;
+    m.add(
+        "PatchBaseMismatchError",
+        py.get_type::<PatchBaseMismatchError>(),
+    )?;
+    m.add("PatchFailedError", py.get_type::<PatchFailedError>())?;
+    m.add("InvalidQueryError", py.get_type::<InvalidQueryError>())?;
+    Ok(())
+}
@@ -0,0 +1,536 @@
+// quantize.rs — int8 and binary quantization of stored vectors
+//
+// Int8: each dimension is calibrated to the [min, max] range of the built vectors
+// and every component is stored as one byte: 0 maps to min, 255 to max. The
+// pack then needs `dimensions` bytes per chunk instead of `4 * dimensions`.
+//
+// Queries stay f32. q · x is summed straight from the codes: with
+// x_j = min_j + c_j * step_j, q · x = q · min + Σ (q_j * step_j) * c_j, so
+// each query computes one weight per dimension and one offset, and a row
+// costs a single multiply-add per byte. Cosine and euclidean divide by or
+// add the squared norm of the row's reconstruction, which is derived from
+// the codes when the quantizer is built or loaded.
+//
+// Binary: each component is stored as its sign bit, 64 components to a
+// word, so a pack needs `dimensions / 8` bytes per chunk. The query is
+// binarized the same way and rows are ranked by Hamming distance, one XOR
+// and popcount per word. Hamming distance is not in the metric's units, so
+// the best candidates are always scored again against the f32 vectors. A
+// set bit decodes to the dimension's mean |x|, a clear bit to its negation.
+//
+// Decoding is exact at both ends of an int8 range, and a binary scale is
+// the mean of values that all decode to it, so retraining on
+// reconstructions gives back the same calibration and codes. A codes-only
+// pack that is compacted or patched is re-encoded byte for byte.
+
+use crate::math::DistanceMetric;
+use crate::options::QuantizationType;
+use serde::{Deserialize, Serialize};
+
+/// Largest code: the top of a dimension's calibration range.
+const LEVELS: f32 = 255.0;
+
+/// Stored codes for one of the quantization types.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) enum Quantizer {
+    Int8(ScalarQuantizer),
+    Binary(BinaryQuantizer),
+}
+
+impl Quantizer {
+    /// Calibrate for `kind` on `vectors`. `None` for `QuantizationType::None`.
+    pub(crate) fn train(
+        kind: QuantizationType,
+        dimensions: usize,
+        vectors: &[&[f32]],
+    ) -> Option<Self> {
+        match kind {
+            QuantizationType::None => None,
+            QuantizationType::Int8 => Some(Self::Int8(ScalarQuantizer::train(dimensions, vectors))),
+            QuantizationType::Binary => {
+                Some(Self::Binary(BinaryQuantizer::train(dimensions, vectors)))
+            }
+        }
+    }
+
+    pub(crate) fn kind(&self) -> QuantizationType {
+        match self {
+            Self::Int8(_) => QuantizationType::Int8,
+            Self::Binary(_) => QuantizationType::Binary,
+        }
+    }
+
+    /// Encode `vector` as the next row. Returns its reconstruction.
+    pub(crate) fn add(&mut self, vector: &[f32]) -> Vec<f32> {
+        match self {
+            Self::Int8(q) => q.add(vector),
+            Self::Binary(q) => q.add(vector),
+        }
+    }
+
+    /// `vector` as a loaded pack would see it: encoded, then decoded.
+    pub(crate) fn reconstructed(&self, vector: &[f32]) -> Vec<f32> {
+        match self {
+            Self::Int8(q) => q.reconstructed(vector),
+            Self::Binary(q) => q.reconstructed(vector),
+        }
+    }
+
+    /// Every row, decoded. Row i is chunks[i].
+    pub(crate) fn reconstruct(&self) -> Vec<Vec<f32>> {
+        match self {
+            Self::Int8(q) => q.reconstruct(),
+            Self::Binary(q) => q.reconstruct(),
+        }
+    }
+
+    /// Recompute what serialization drops.
+    pub(crate) fn restored(self) -> Self {
+        match self {
+            Self::Int8(q) => Self::Int8(q.restored()),
+            binary => binary,
+        }
+    }
+
+    pub(crate) fn dimensions(&self) -> usize {
+        match self {
+            Self::Int8(q) => q.dimensions,
+            Self::Binary(q) => q.dimensions,
+        }
+    }
+
+    /// Holds `len` rows of `dimensions` and a full calibration.
+    pub(crate) fn is_valid(&self, len: usize, dimensions: usize) -> bool {
+        match self {
+            Self::Int8(q) => q.is_valid(len, dimensions),
+            Self::Binary(q) => q.is_valid(len, dimensions),
+        }
+    }
+
+    /// Prepare `query` for a first pass over the codes under `metric`.
+    pub(crate) fn query(&self, metric: DistanceMetric, query: &[f32]) -> QuantizedQuery<'_> {
+        match self {
+            Self::Int8(q) => QuantizedQuery::Int8(q.query(metric, query)),
+            Self::Binary(q) => QuantizedQuery::Binary(q.query(query)),
+        }
+    }
+}
+
+/// A query prepared against one quantizer.
+pub(crate) enum QuantizedQuery<'a> {
+    Int8(ScalarQuery<'a>),
+    Binary(BinaryQuery<'a>),
+}
+
+impl QuantizedQuery<'_> {
+    /// First-pass score of row `row`, higher is closer: the approximate
+    /// metric score for int8, the negated Hamming distance for binary.
+    #[inline]
+    pub(crate) fn score(&self, row: usize) -> f32 {
+        match self {
+            Self::Int8(q) => q.score(row),
+            Self::Binary(q) => -(q.hamming(row) as f32),
+        }
+    }
+
+    /// True when `score` is in the metric's units, so a codes-only pack
+    /// can answer with it directly.
+    pub(crate) fn scores_metric(&self) -> bool {
+        matches!(self, Self::Int8(_))
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct ScalarQuantizer {
+    dimensions: usize,
+    /// Per-dimension calibration range.
+    min: Vec<f32>,
+    max: Vec<f32>,
+    /// codes[row * dimensions + j] = quantized j-th component of the row.
+    codes: Vec<u8>,
+    /// Squared norm of each row's reconstruction. Derived from the codes,
+    /// so not stored in the pack; see `restored`.
+    #[serde(skip)]
+    norms: Vec<f32>,
+}
+
+impl ScalarQuantizer {
+    /// Calibrate on `vectors`. Holds no rows until `add` is called.
+    pub(crate) fn train(dimensions: usize, vectors: &[&[f32]]) -> Self {
+        let mut min = vec![f32::INFINITY; dimensions];
+        let mut max = vec![f32::NEG_INFINITY; dimensions];
+        for vector in vectors {
+            for (j, &x) in vector.iter().enumerate() {
+                min[j] = min[j].min(x);
+                max[j] = max[j].max(x);
+            }
+        }
+        for (lo, hi) in min.iter_mut().zip(&mut max) {
+            if !lo.is_finite() || !hi.is_finite() {
+                (*lo, *hi) = (0.0, 0.0);
+            }
+        }
+        Self {
+            dimensions,
+            min,
+            max,
+            codes: Vec::new(),
+            norms: Vec::new(),
+        }
+    }
+
+    /// Encode `vector` as the next row. Returns its reconstruction.
+    /// Components outside the calibration range are clamped to it.
+    pub(crate) fn add(&mut self, vector: &[f32]) -> Vec<f32> {
+        let codes = self.encode(vector);
+        let decoded = self.decode(&codes);
+        self.codes.extend_from_slice(&codes);
+        self.norms.push(decoded.iter().map(|x| x * x).sum());
+        decoded
+    }
+
+    /// `vector` as a loaded pack would see it: encoded, then decoded.
+    pub(crate) fn reconstructed(&self, vector: &[f32]) -> Vec<f32> {
+        self.decode(&self.encode(vector))
+    }
+
+    /// Every row, decoded. Row i is chunks[i].
+    pub(crate) fn reconstruct(&self) -> Vec<Vec<f32>> {
+        self.codes
+            .chunks_exact(self.dimensions.max(1))
+            .map(|codes| self.decode(codes))
+            .collect()
+    }
+
+    /// Recompute the per-row norms, which serialization drops.
+    pub(crate) fn restored(mut self) -> Self {
+        self.norms = self
+            .reconstruct()
+            .iter()
+            .map(|row| row.iter().map(|x| x * x).sum())
+            .collect();
+        self
+    }
+
+    /// Holds `len` rows of `dimensions` and a full calibration range.
+    pub(crate) fn is_valid(&self, len: usize, dimensions: usize) -> bool {
+        self.dimensions == dimensions
+            && self.min.len() == dimensions
+            && self.max.len() == dimensions
+            && self.codes.len() == len * dimensions
+    }
+
+    /// Prepare `query` for scoring rows under `metric`.
+    pub(crate) fn query(&self, metric: DistanceMetric, query: &[f32]) -> ScalarQuery<'_> {
+        let mut offset = 0.0;
+        let mut weights = Vec::with_capacity(self.dimensions);
+        for (j, &q) in query.iter().enumerate() {
+            offset += q * self.min[j];
+            weights.push(q * (self.max[j] - self.min[j]) / LEVELS);
+        }
+        ScalarQuery {
+            quantizer: self,
+            metric,
+            weights,
+            offset,
+            norm: query.iter().map(|q| q * q).sum(),
+        }
+    }
+
+    fn encode(&self, vector: &[f32]) -> Vec<u8> {
+        vector
+            .iter()
+            .enumerate()
+            .map(|(j, &x)| {
+                let (lo, hi) = (self.min[j], self.max[j]);
+                if hi > lo {
+                    ((x - lo) / (hi - lo) * LEVELS).round().clamp(0.0, LEVELS) as u8
+                } else {
+                    0
+                }
+            })
+            .collect()
+    }
+
+    fn decode(&self, codes: &[u8]) -> Vec<f32> {
+        codes
+            .iter()
+            .enumerate()
+            .map(|(j, &code)| {
+                // Exact at code 0 (min) and LEVELS (max).
+                let t = code as f32 / LEVELS;
+                self.min[j] * (1.0 - t) + self.max[j] * t
+            })
+            .collect()
+    }
+
+    fn row(&self, row: usize) -> &[u8] {
+        &self.codes[row * self.dimensions..(row + 1) * self.dimensions]
+    }
+}
+
+/// A query prepared against an int8 quantizer's calibration.
+pub(crate) struct ScalarQuery<'a> {
+    quantizer: &'a ScalarQuantizer,
+    metric: DistanceMetric,
+    /// q_j * step_j.
+    weights: Vec<f32>,
+    /// q · min.
+    offset: f32,
+    /// Squared norm of the query.
+    norm: f32,
+}
+
+impl ScalarQuery<'_> {
+    /// Approximate `metric.score` against row `row`'s reconstruction.
+    #[inline]
+    pub(crate) fn score(&self, row: usize) -> f32 {
+        let dot = self.dot(row);
+        match self.metric {
+            DistanceMetric::Cosine => {
+                let denom = self.norm.sqrt() * self.quantizer.norms[row].sqrt();
+                if denom == 0.0 {
+                    0.0
+                } else {
+                    dot / denom
+                }
+            }
+            DistanceMetric::Euclidean => 1.0 / (1.0 + self.squared_distance(row, dot).sqrt()),
+            DistanceMetric::Dot => dot,
+        }
+    }
+
+    /// Approximate `metric.distance` against row `row`'s reconstruction.
+    #[inline]
+    pub(crate) fn distance(&self, row: usize) -> f32 {
+        match self.metric {
+            DistanceMetric::Cosine => 1.0 - self.score(row),
+            DistanceMetric::Euclidean => self.squared_distance(row, self.dot(row)).sqrt(),
+            DistanceMetric::Dot => -self.dot(row),
+        }
+    }
+
+    #[inline]
+    fn dot(&self, row: usize) -> f32 {
+        self.offset
+            + self
+                .weights
+                .iter()
+                .zip(self.quantizer.row(row))
+                .map(|(w, &code)| w * code as f32)
+                .sum::<f32>()
+    }
+
+    /// |q - x|², clamped at 0 against rounding.
+    fn squared_distance(&self, row: usize, dot: f32) -> f32 {
+        (self.norm - 2.0 * dot + self.quantizer.norms[row]).max(0.0)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct BinaryQuantizer {
+    dimensions: usize,
+    /// Per-dimension mean |x|: what a set bit decodes to, negated for a
+    /// clear one.
+    scale: Vec<f32>,
+    /// bits[row * words + j / 64] holds component j at bit j % 64, set when
+    /// the component is positive.
+    bits: Vec<u64>,
+}
+
+impl BinaryQuantizer {
+    /// Calibrate on `vectors`. Holds no rows until `add` is called.
+    pub(crate) fn train(dimensions: usize, vectors: &[&[f32]]) -> Self {
+        // f64 sums keep the mean of n equal values exactly that value.
+        let mut sums = vec![0.0f64; dimensions];
+        for vector in vectors {
+            for (sum, &x) in sums.iter_mut().zip(*vector) {
+                if x.is_finite() {
+                    *sum += x.abs() as f64;
+                }
+            }
+        }
+        let count = vectors.len().max(1) as f64;
+        Self {
+            dimensions,
+            scale: sums.iter().map(|sum| (sum / count) as f32).collect(),
+            bits: Vec::new(),
+        }
+    }
+
+    /// Encode `vector` as the next row. Returns its reconstruction.
+    pub(crate) fn add(&mut self, vector: &[f32]) -> Vec<f32> {
+        let start = self.bits.len();
+        self.bits.extend(sign_bits(vector));
+        self.decode(&self.bits[start..])
+    }
+
+    /// `vector` as a loaded pack would see it: encoded, then decoded.
+    pub(crate) fn reconstructed(&self, vector: &[f32]) -> Vec<f32> {
+        self.decode(&sign_bits(vector))
+    }
+
+    /// Every row, decoded. Row i is chunks[i].
+    pub(crate) fn reconstruct(&self) -> Vec<Vec<f32>> {
+        self.bits
+            .chunks_exact(self.words().max(1))
+            .map(|bits| self.decode(bits))
+            .collect()
+    }
+
+    /// Holds `len` rows of `dimensions` and a scale per dimension.
+    pub(crate) fn is_valid(&self, len: usize, dimensions: usize) -> bool {
+        self.dimensions == dimensions
+            && self.scale.len() == dimensions
+            && self.bits.len() == len * self.words()
+    }
+
+    /// Binarize `query` for Hamming distances to the rows.
+    pub(crate) fn query(&self, query: &[f32]) -> BinaryQuery<'_> {
+        BinaryQuery {
+            quantizer: self,
+            bits: sign_bits(query),
+        }
+    }
+
+    fn words(&self) -> usize {
+        self.dimensions.div_ceil(64)
+    }
+
+    fn decode(&self, bits: &[u64]) -> Vec<f32> {
+        self.scale
+            .iter()
+            .enumerate()
+            .map(|(j, &scale)| {
+                if bits[j / 64] >> (j % 64) & 1 == 1 {
+                    scale
+                } else {
+                    -scale
+                }
+            })
+            .collect()
+    }
+
+    fn row(&self, row: usize) -> &[u64] {
+        let words = self.words();
+        &self.bits[row * words..(row + 1) * words]
+    }
+}
+
+/// A query binarized against a binary quantizer's rows.
+pub(crate) struct BinaryQuery<'a> {
+    quantizer: &'a BinaryQuantizer,
+    bits: Vec<u64>,
+}
+
+impl BinaryQuery<'_> {
+    /// Components of row `row` whose sign differs from the query's.
+    #[inline]
+    pub(crate) fn hamming(&self, row: usize) -> u32 {
+        self.bits
+            .iter()
+            .zip(self.quantizer.row(row))
+            .map(|(a, b)| (a ^ b).count_ones())
+            .sum()
+    }
+}
+
+/// Sign bits of `vector`, 64 components to a word, low bit first.
+fn sign_bits(vector: &[f32]) -> Vec<u64> {
+    vector
+        .chunks(64)
+        .map(|chunk| {
+            chunk
+                .iter()
+                .enumerate()
+                .filter(|(_, &x)| x > 0.0)
+                .fold(0u64, |word, (bit, _)| word | 1 << bit)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reconstruction_is_stable_under_retraining() {
+        let vectors = [
+            vec![-1.0, 0.5, 2.0],
+            vec![0.25, 0.5, -3.0],
+            vec![1.0, 0.5, 0.1],
+        ];
+        let rows: Vec<&[f32]> = vectors.iter().map(|v| v.as_slice()).collect();
+        let mut first = ScalarQuantizer::train(3, &rows);
+        let decoded: Vec<Vec<f32>> = rows.iter().map(|v| first.add(v)).collect();
+
+        let rows: Vec<&[f32]> = decoded.iter().map(|v| v.as_slice()).collect();
+        let mut second = ScalarQuantizer::train(3, &rows);
+        for row in &rows {
+            assert_eq!(second.add(row), *row);
+        }
+        assert_eq!(first.codes, second.codes);
+        assert_eq!(first.reconstruct(), decoded);
+    }
+
+    #[test]
+    fn quantized_scores_track_exact_scores() {
+        let vectors: Vec<Vec<f32>> = (0..20)
+            .map(|i| {
+                (0..8)
+                    .map(|j| ((i * 7 + j * 3) % 11) as f32 / 5.0 - 1.0)
+                    .collect()
+            })
+            .collect();
+        let rows: Vec<&[f32]> = vectors.iter().map(|v| v.as_slice()).collect();
+        let mut quantizer = ScalarQuantizer::train(8, &rows);
+        let decoded: Vec<Vec<f32>> = rows.iter().map(|v| quantizer.add(v)).collect();
+
+        let query = [0.3, -0.2, 0.9, 0.1, -0.5, 0.4, 0.0, 0.7];
+        for metric in [
+            DistanceMetric::Cosine,
+            DistanceMetric::Euclidean,
+            DistanceMetric::Dot,
+        ] {
+            let prepared = quantizer.query(metric, &query);
+            for (row, vector) in decoded.iter().enumerate() {
+                let exact = metric.score(&query, vector);
+                assert!((prepared.score(row) - exact).abs() < 1e-4, "{metric:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn binary_codes_are_sign_bits_and_stable_under_retraining() {
+        let vectors: Vec<Vec<f32>> = (0..5)
+            .map(|i| {
+                (0..70)
+                    .map(|j| ((i * 13 + j * 5) % 9) as f32 - 4.0)
+                    .collect()
+            })
+            .collect();
+        let rows: Vec<&[f32]> = vectors.iter().map(|v| v.as_slice()).collect();
+        let mut first = BinaryQuantizer::train(70, &rows);
+        let decoded: Vec<Vec<f32>> = rows.iter().map(|v| first.add(v)).collect();
+        assert_eq!(first.bits.len(), 5 * 2);
+
+        let query = first.query(&vectors[0]);
+        assert_eq!(query.hamming(0), 0);
+        for (row, vector) in vectors.iter().enumerate() {
+            let differing = vector
+                .iter()
+                .zip(&vectors[0])
+                .filter(|(a, b)| (**a > 0.0) != (**b > 0.0))
+                .count();
+            assert_eq!(query.hamming(row), differing as u32);
+        }
+
+        let rows: Vec<&[f32]> = decoded.iter().map(|v| v.as_slice()).collect();
+        let mut second = BinaryQuantizer::train(70, &rows);
+        for row in &rows {
+            assert_eq!(second.add(row), *row);
+        }
+        assert_eq!(first.bits, second.bits);
+        assert_eq!(first.scale, second.scale);
+    }
+}
@@ -1,5 +1,6 @@
 use serde::{Deserialize, Serialize};
-use crate::chunk::Chunk;
+use crate::chunk::{Chunk, ChunkMetadata};
+use crate::filter::{CompiledFilter, Predicate};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MetadataFilter {
@@ -9,6 +10,36 @@ pub struct MetadataFilter {
     pub value: Option<serde_json::Value>,
 }
 
+/// Boolean combination of metadata filters, e.g.
+/// `{"and": [{"field": "source_plugin", "op": "eq", "value": "@vpack/source-notion"},
+///           {"not": {"field": "chunk_type", "op": "eq", "value": "changelog"}}]}`.
+/// A bare `MetadataFilter` is a valid expression, so single-filter JSON
+/// deserializes unchanged.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum FilterExpr {
+    /// True when every child matches. An empty list matches everything.
+    And { and: Vec<FilterExpr> },
+    /// True when any child matches. An empty list matches nothing.
+    Or { or: Vec<FilterExpr> },
+    Not { not: Box<FilterExpr> },
+    Leaf(MetadataFilter),
+}
+
+impl FilterExpr {
+    /// Evaluate against one chunk's metadata. To test many chunks, the query
+    /// path compiles the expression once instead.
+    pub fn matches(&self, metadata: &ChunkMetadata) -> bool {
+        CompiledFilter::new(self).matches(metadata)
+    }
+}
+
+impl From<MetadataFilter> for FilterExpr {
+    fn from(filter: MetadataFilter) -> Self {
+        FilterExpr::Leaf(filter)
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 pub enum FilterOp {
@@ -21,14 +52,66 @@ pub enum FilterOp {
     Exists,
 }
 
+/// Whether a query searches changelog chunks (RFC-0004 §4.2).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum History {
+    /// Content and changelog chunks, ranked together.
+    #[default]
+    Include,
+    /// Content chunks only.
+    Exclude,
+    /// Changelog chunks only.
+    Only,
+}
+
 #[derive(Debug, Clone, Deserialize)]
 #[serde(default)]
 #[serde(rename_all = "camelCase")]
 pub struct QueryOptions {
     pub top_k: usize,
+    /// Lower bound on `QueryResult::score`, in the pack's metric: cosine
+    /// similarity, dot product, or 1 / (1 + euclidean distance).
+    /// Applies to the adjusted score when `temporal_weight` or `recency` is set.
     pub min_score: Option<f32>,
-    pub filter: Option<MetadataFilter>,
+    pub filter: Option<FilterExpr>,
+    /// Return each result's stored vector. Cosine packs with f32 vectors
+    /// store them at unit length, so these are normalized, not the build input.
     pub include_vectors: bool,
+    /// HNSW candidate list width for this query. Defaults to the pack's ef_search.
+    pub ef_search: Option<usize>,
+    /// IVF-PQ clusters probed for this query. Defaults to the pack's nprobe.
+    pub nprobe: Option<usize>,
+    /// Filtered queries on HNSW and IVF-PQ packs score the matching chunks
+    /// exactly when the filter is estimated to match less than this fraction
+    /// of the chunks. Defaults to `index::BRUTE_FORCE_SELECTIVITY` (0.05).
+    pub brute_force_selectivity: Option<f32>,
+    /// Embedding model the caller used or expects. A model other than the
+    /// one pinned in the pack's manifest fails with `ModelMismatch`.
+    pub model: Option<String>,
+    /// Weights hash the caller's model was loaded with. A hash other than
+    /// the pack's pinned `model_hash` fails with `ModelHashMismatch`.
+    pub model_hash: Option<String>,
+    /// Search changelog chunks too, skip them, or search only them. The
+    /// filter applies to both. Expired changelog chunks are never returned.
+    pub history: History,
+    /// Scale changelog chunk scores by `1 - temporal_weight`, in [0, 1]
+    /// (RFC-0004 §11.3).
+    pub temporal_weight: Option<f32>,
+    /// Halve chunk scores for every half-life of age.
+    pub recency: Option<RecencyDecay>,
+}
+
+/// Exponential recency decay. A chunk's age is measured from its
+/// `updated_at`, else its `created_at`, else a changelog chunk's `built_at`.
+/// Chunks with no date do not decay.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RecencyDecay {
+    pub half_life_days: f64,
+    /// Reference time as an ISO 8601 date. Defaults to the system clock.
+    #[serde(default)]
+    pub now: Option<String>,
 }
 
 impl Default for QueryOptions {
@@ -38,6 +121,14 @@ impl Default for QueryOptions {
             min_score: None,
             filter: None,
             include_vectors: false,
+            ef_search: None,
+            nprobe: None,
+            brute_force_selectivity: None,
+            model: None,
+            model_hash: None,
+            history: History::Include,
+            temporal_weight: None,
+            recency: None,
         }
     }
 }
@@ -45,80 +136,20 @@ impl Default for QueryOptions {
 #[derive(Debug, Clone, Serialize)]
 pub struct QueryResult {
     pub chunk: Chunk,
+    /// `similarity` after `temporal_weight` and `recency`; results are
+    /// ranked by it. Equal to `similarity` when neither is set.
     pub score: f32,
+    /// Similarity under the pack's metric; higher is better.
+    pub similarity: f32,
     pub rank: usize,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub vector: Option<Vec<f32>>,
 }
 
 pub fn matches_filter(chunk: &Chunk, filter: &MetadataFilter) -> bool {
-    let meta_value = match serde_json::to_value(&chunk.metadata) {
-        Ok(value) => value,
-        Err(_) => return false,
-    };
-
-    let value = get_nested_value(&meta_value, &filter.field);
-
-    match filter.op {
-        FilterOp::Eq => {
-            if let Some(expected) = filter.value.as_ref() {
-                value.map_or(false, |v| v == expected)
-            } else {
-                value.is_none()
-            }
-        }
-        FilterOp::Neq => {
-            if let Some(expected) = filter.value.as_ref() {
-                value.map_or(true, |v| v != expected)
-            } else {
-                value.is_some()
-            }
-        }
-        FilterOp::In => {
-            if let Some(serde_json::Value::Array(values)) = filter.value.as_ref() {
-                value.map_or(false, |v| values.iter().any(|item| item == v))
-            } else {
-                false
-            }
-        }
-        FilterOp::Nin => {
-            if let Some(serde_json::Value::Array(values)) = filter.value.as_ref() {
-                value.map_or(false, |v| values.iter().all(|item| item != v))
-            } else {
-                false
-            }
-        }
-        FilterOp::Gte => compare_number(value, filter.value.as_ref(), |a, b| a >= b),
-        FilterOp::Lte => compare_number(value, filter.value.as_ref(), |a, b| a <= b),
-        FilterOp::Exists => match value {
-            Some(serde_json::Value::Null) | None => false,
-            Some(_) => true,
-        },
-    }
-}
-
-fn compare_number(
-    value: Option<&serde_json::Value>,
-    expected: Option<&serde_json::Value>,
-    cmp: fn(f64, f64) -> bool,
-) -> bool {
-    let lhs = value.and_then(|v| v.as_f64());
-    let rhs = expected.and_then(|v| v.as_f64());
-    match (lhs, rhs) {
-        (Some(a), Some(b)) => cmp(a, b),
-        _ => false,
-    }
+    matches_metadata(&chunk.metadata, filter)
 }
 
-fn get_nested_value<'a>(value: &'a serde_json::Value, path: &str) -> Option<&'a serde_json::Value> {
-    let mut current = value;
-    for key in path.split('.') {
-        match current {
-            serde_json::Value::Object(map) => {
-                current = map.get(key)?;
-            }
-            _ => return None,
-        }
-    }
-    Some(current)
+pub fn matches_metadata(metadata: &ChunkMetadata, filter: &MetadataFilter) -> bool {
+    Predicate::new(filter).matches(metadata)
 }
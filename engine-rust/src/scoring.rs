@@ -0,0 +1,88 @@
+// scoring.rs — query-time score modifiers
+//
+// `temporal_weight` scales down changelog chunks (RFC-0004 §11.3) and `recency`
+// halves a chunk's score every `half_life_days` of age. Both adjust the scores
+// of the candidates the index returned; they do not change how it is searched.
+// A penalty moves a score towards zero when positive and away from it when
+// negative, by the same fraction of its magnitude, so under dot product an
+// older chunk never gains by being older. A factor of zero (temporalWeight 1,
+// or an age far past the half-life) at most doubles a negative score.
+
+use crate::changelog::{now_ms, parse_timestamp, MILLIS_PER_DAY};
+use crate::chunk::ChunkMetadata;
+use crate::error::VPackError;
+use crate::query::QueryOptions;
+
+/// The modifiers of one query, validated.
+pub(crate) struct Scoring {
+    /// Factor applied to changelog chunks.
+    changelog: f32,
+    /// (half-life, reference time), in milliseconds.
+    recency: Option<(f64, i64)>,
+}
+
+impl Scoring {
+    /// None when the query sets no modifier.
+    pub(crate) fn new(options: &QueryOptions) -> Result<Option<Self>, VPackError> {
+        if options.temporal_weight.is_none() && options.recency.is_none() {
+            return Ok(None);
+        }
+        let weight = options.temporal_weight.unwrap_or(0.0);
+        if !(0.0..=1.0).contains(&weight) {
+            return Err(VPackError::InvalidQuery(format!(
+                "temporalWeight must be between 0 and 1, got {weight}"
+            )));
+        }
+        let recency = match &options.recency {
+            Some(decay) => {
+                if !(decay.half_life_days.is_finite() && decay.half_life_days > 0.0) {
+                    return Err(VPackError::InvalidQuery(format!(
+                        "recency.halfLifeDays must be positive, got {}",
+                        decay.half_life_days
+                    )));
+                }
+                let now = match &decay.now {
+                    Some(now) => parse_timestamp(now).ok_or_else(|| {
+                        VPackError::InvalidQuery(format!(
+                            "recency.now is not an ISO 8601 date: '{now}'"
+                        ))
+                    })?,
+                    None => now_ms(),
+                };
+                Some((decay.half_life_days * MILLIS_PER_DAY as f64, now))
+            }
+            None => None,
+        };
+        Ok(Some(Self {
+            changelog: 1.0 - weight,
+            recency,
+        }))
+    }
+
+    /// `score` after the modifiers for a chunk with `metadata`.
+    pub(crate) fn adjust(&self, score: f32, metadata: &ChunkMetadata, changelog: bool) -> f32 {
+        let mut factor = if changelog { self.changelog } else { 1.0 };
+        if let Some((half_life, now)) = self.recency {
+            if let Some(dated) = last_changed(metadata) {
+                // Dates in the future count as new.
+                let age = now.saturating_sub(dated).max(0) as f64;
+                factor *= 0.5f64.powf(age / half_life) as f32;
+            }
+        }
+        score - (1.0 - factor) * score.abs()
+    }
+}
+
+/// `updated_at`, else `created_at`, else a changelog chunk's `built_at`.
+/// Chunks with none of them do not decay.
+fn last_changed(metadata: &ChunkMetadata) -> Option<i64> {
+    let built_at = || metadata.extra.get("built_at")?.as_str();
+    [
+        metadata.updated_at.as_deref(),
+        metadata.created_at.as_deref(),
+    ]
+    .into_iter()
+    .flatten()
+    .chain(built_at())
+    .find_map(parse_timestamp)
+}
@@ -1,21 +1,106 @@
 // serialize.rs — .vpack binary format
 //
-// Phase 1: bincode over a flat struct. Functionally correct.
-// Phase 2: columnar layout per RFC-0001 §3.1 with section table,
-//          enabling partial reads and streaming query-on-registry.
+// v0x03: sectioned layout per RFC-0001 §3.1. A fixed 64-byte header, a section
+// table with offsets and CRC-32 checksums, then independent MANIFEST, CHUNKS,
+// VECTORS, INDEX and PROVENANCE sections, each starting on a 64-byte boundary.
+// Packs with changelog chunks add a CHANGELOG section after them (RFC-0004
+// §3), packs with metadata indexes a METADATA_INDEX section, quantized packs
+// a QUANTIZED section, and packs changed in place since their last build or
+// compaction a MUTATIONS section.
+// Tools can read the manifest or the vectors without decoding chunk text.
+//
+// v0x02 (magic `VPAK`): a single length-prefixed bincode payload plus an
+// optional index trailer. Still readable; no longer written.
 
+use crate::changelog::{self, Changelog};
 use crate::chunk::{Chunk, ChunkMetadata, EmbeddedChunk};
 use crate::error::VPackError;
-use crate::index::VPackIndex;
+use crate::hash::{crc32, manifest_hash, sha256, to_hex};
+use crate::hnsw::HnswGraph;
+use crate::index::{embedder_config, VPackIndex};
+use crate::ivf_pq::IvfPqIndex;
+use crate::metadata_index::MetadataIndex;
+use crate::options::{BuildOptions, VectorPrecision};
+use crate::quantize::Quantizer;
+use crate::store::{self, ChunkStore, StoredChunk, VectorStore};
+use memmap2::Mmap;
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
-use std::collections::HashMap;
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashMap};
+use std::fs::File;
+use std::path::Path;
+use std::sync::Arc;
+
+const MAGIC: &[u8; 5] = b"VPACK";
+const FORMAT_VERSION: u8 = 0x03;
+const HEADER_LEN: usize = 64;
+/// section_id: u8, offset: u64, length: u64, checksum: u32
+const SECTION_ENTRY_LEN: usize = 21;
+const SECTION_ALIGN: usize = 64;
+
+/// Header flag: the pack has a CHANGELOG section (RFC-0004).
+pub const FLAG_HAS_CHANGELOG: u16 = 0x0001;
+/// Header flag: VECTORS is empty; vectors are decoded from IVF-PQ codes, or
+/// from QUANTIZED codes.
+pub const FLAG_VECTORS_FROM_CODES: u16 = 0x0002;
+/// Header flag: VECTORS holds little-endian f16 components.
+pub const FLAG_VECTORS_F16: u16 = 0x0004;
+/// Header flag: VECTORS holds little-endian bf16 components.
+pub const FLAG_VECTORS_BF16: u16 = 0x0008;
+/// Header flag: a cosine pack whose vectors are stored at unit length.
+/// Cosine packs written without it are normalized on load, except
+/// codes-only packs, whose reconstructions are never normalized.
+pub const FLAG_VECTORS_NORMALIZED: u16 = 0x0010;
+
+const LEGACY_MAGIC: &[u8; 4] = b"VPAK";
+const LEGACY_VERSION: u8 = 0x02;
 
-const MAGIC: &[u8; 4] = b"VPAK";
-const FORMAT_VERSION: u8 = 0x02;
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum SectionId {
+    /// UTF-8 JSON manifest.
+    Manifest = 0x01,
+    /// `u64 table_len`, bincode chunk table, then the UTF-8 text blob.
+    Chunks = 0x02,
+    /// Raw little-endian f32, row-major, parallel to CHUNKS. f16 or bf16
+    /// when the header says so (FLAG_VECTORS_F16, FLAG_VECTORS_BF16).
+    Vectors = 0x03,
+    /// Build options and the serialized HNSW graph or IVF-PQ codebook.
+    Index = 0x04,
+    /// JSON source fingerprints and timestamps.
+    Provenance = 0x05,
+    /// bincode changelog chunks with their vectors, oldest `built_at` first.
+    Changelog = 0x06,
+    /// bincode postings for the metadata fields listed in the build options.
+    /// Only written when at least one field is indexed.
+    MetadataIndex = 0x07,
+    /// bincode quantizer: per-dimension calibration and the int8 codes or
+    /// sign bits of every vector, parallel to CHUNKS. Only written for
+    /// quantized packs.
+    Quantized = 0x08,
+    /// bincode rows deleted, and whether det-hnsw rows were appended, since
+    /// the last build or compaction. Only written for packs changed in place.
+    Mutations = 0x09,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SectionEntry {
+    pub id: u8,
+    pub offset: u64,
+    pub length: u64,
+    pub checksum: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PackHeader {
+    pub spec_version: u8,
+    pub flags: u16,
+    pub manifest_hash: [u8; 32],
+    pub sections: Vec<SectionEntry>,
+}
 
 #[derive(Serialize, Deserialize)]
-struct PackMetadata {
+pub(crate) struct PackMetadata {
     source_plugin: String,
     source_id: String,
     source_url: Option<String>,
@@ -23,73 +108,394 @@ struct PackMetadata {
     updated_at: Option<String>,
     pack_name: String,
     chunker_plugin: String,
-    extra: HashMap<String, Value>,
+    /// Plugin-defined fields as JSON text. bincode cannot decode
+    /// `serde_json::Value`, and a sorted map keeps the artifact byte-identical
+    /// across builds (RFC-0001 §4.3). Empty string = no extra fields, which
+    /// encodes exactly like the empty map earlier v0x02 writers produced.
+    extra_json: String,
 }
 
+/// One CHUNKS table row. Text lives in the blob after the table, so a reader
+/// can decode ids and metadata without touching it.
 #[derive(Serialize, Deserialize)]
-struct PackChunk {
+struct ChunkRecord {
     id: String,
-    text: String,
     metadata: PackMetadata,
-    vector: Vec<f32>,
+    text_offset: u64,
+    text_len: u64,
 }
 
+/// INDEX section, and the v0x02 index trailer.
 #[derive(Serialize, Deserialize)]
-struct PackPayload {
-    manifest_json: String,
-    chunks: Vec<PackChunk>,
-    // Phase 2: add manifest_hash: [u8; 32] and section table
+struct PackIndex {
+    /// BuildOptions as JSON, so new option fields don't break old packs.
+    options_json: String,
+    hnsw: Option<HnswGraph>,
+    ivf_pq: Option<IvfPqIndex>,
+}
+
+/// MUTATIONS section.
+#[derive(Serialize, Deserialize)]
+struct PackMutations {
+    /// Deleted rows, ascending. They stay in CHUNKS, VECTORS and INDEX.
+    deleted: Vec<u32>,
+    /// Det-HNSW rows were appended: the graph is not the canonical one.
+    appended: bool,
 }
 
+/// Serialize `index` as it is. Deleted and inserted chunks are written
+/// without rebuilding anything; `VPackIndex::compact` does that.
+/// Fails with `EmptyIndex` if every chunk was deleted.
 pub fn serialize(index: &VPackIndex) -> Result<Vec<u8>, VPackError> {
-    let manifest_json = serde_json::to_string(index.manifest())
-        .map_err(|err| VPackError::InvalidFormat(err.to_string()))?;
-    let chunks = index
-        .chunks
-        .iter()
-        .map(|embedded| PackChunk {
-            id: embedded.chunk.id.clone(),
-            text: embedded.chunk.text.clone(),
-            metadata: PackMetadata {
-                source_plugin: embedded.chunk.metadata.source_plugin.clone(),
-                source_id: embedded.chunk.metadata.source_id.clone(),
-                source_url: embedded.chunk.metadata.source_url.clone(),
-                created_at: embedded.chunk.metadata.created_at.clone(),
-                updated_at: embedded.chunk.metadata.updated_at.clone(),
-                pack_name: embedded.chunk.metadata.pack_name.clone(),
-                chunker_plugin: embedded.chunk.metadata.chunker_plugin.clone(),
-                extra: embedded.chunk.metadata.extra.clone(),
-            },
-            vector: embedded.vector.clone(),
-        })
-        .collect();
+    if index.chunk_count() == 0 {
+        return Err(VPackError::EmptyIndex);
+    }
+    // IVF-PQ without rerank, or quantization without rescore, stores codes
+    // only: that is where the size win is.
+    let codes_only = index.options().codes_only();
 
-    let pack = PackPayload {
-        manifest_json,
-        chunks,
+    let manifest = serde_json::to_vec(index.manifest()).map_err(invalid)?;
+    let chunks = encode_chunks(&index.chunks)?;
+    let vectors = if codes_only {
+        Vec::new()
+    } else {
+        index.vectors.to_le_bytes()
     };
+    let pack_index = bincode::serialize(&PackIndex {
+        options_json: serde_json::to_string(index.options()).map_err(invalid)?,
+        hnsw: index.hnsw.clone(),
+        ivf_pq: index.ivf_pq.clone(),
+    })?;
+    let provenance = serde_json::to_vec(&provenance(index)?).map_err(invalid)?;
 
-    let payload = bincode::serialize(&pack)?;
-    let mut buf = Vec::with_capacity(5 + payload.len());
-    buf.extend_from_slice(MAGIC);
-    buf.push(FORMAT_VERSION);
-    buf.extend_from_slice(&(payload.len() as u32).to_le_bytes());
-    buf.extend_from_slice(&payload);
+    let mut flags = match index.vectors.precision() {
+        VectorPrecision::F32 => 0,
+        VectorPrecision::F16 => FLAG_VECTORS_F16,
+        VectorPrecision::Bf16 => FLAG_VECTORS_BF16,
+    };
+    if codes_only {
+        flags |= FLAG_VECTORS_FROM_CODES;
+    }
+    if index.options().unit_vectors() {
+        flags |= FLAG_VECTORS_NORMALIZED;
+    }
+    let mut sections = vec![
+        (SectionId::Manifest, manifest),
+        (SectionId::Chunks, chunks),
+        (SectionId::Vectors, vectors),
+        (SectionId::Index, pack_index),
+        (SectionId::Provenance, provenance),
+    ];
+    if !index.changelog.is_empty() {
+        flags |= FLAG_HAS_CHANGELOG;
+        sections.push((SectionId::Changelog, index.changelog.encode()?));
+    }
+    if !index.metadata_index.is_empty() {
+        sections.push((
+            SectionId::MetadataIndex,
+            bincode::serialize(&index.metadata_index)?,
+        ));
+    }
+    if let Some(quantized) = &index.quantized {
+        sections.push((SectionId::Quantized, bincode::serialize(quantized)?));
+    }
+    if index.needs_compaction() {
+        let mutations = PackMutations {
+            deleted: index.tombstones.rows().map(|row| row as u32).collect(),
+            appended: index.appended,
+        };
+        sections.push((SectionId::Mutations, bincode::serialize(&mutations)?));
+    }
+    Ok(write_sections(
+        flags,
+        &manifest_hash(index.manifest()),
+        &sections,
+    ))
+}
 
-    Ok(buf)
+/// What the caller expects of a pack it loads.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+#[serde(rename_all = "camelCase")]
+pub struct LoadOptions {
+    /// Embedding model the caller queries with. A pack pinning another
+    /// model fails to load with `ModelMismatch`.
+    pub model: Option<String>,
+    /// Weights hash of the caller's model. A pack pinning another
+    /// `model_hash` fails to load with `ModelHashMismatch`; packs that pin
+    /// none are accepted.
+    pub model_hash: Option<String>,
 }
 
 pub fn deserialize(bytes: &[u8]) -> Result<VPackIndex, VPackError> {
-    if bytes.len() < 9 {
-        return Err(VPackError::InvalidFormat("file too short".to_string()));
+    if bytes.starts_with(MAGIC) {
+        deserialize_sections(bytes)
+    } else if bytes.starts_with(LEGACY_MAGIC) {
+        deserialize_legacy(bytes)
+    } else {
+        Err(VPackError::InvalidFormat("bad magic bytes".to_string()))
     }
+}
+
+/// `deserialize`, then check the pack against `options`.
+pub fn deserialize_with_options(
+    bytes: &[u8],
+    options: &LoadOptions,
+) -> Result<VPackIndex, VPackError> {
+    let index = deserialize(bytes)?;
+    index.check_model(options.model.as_deref(), options.model_hash.as_deref())?;
+    Ok(index)
+}
 
-    if &bytes[..4] != MAGIC {
+/// Parse the header and section table. Does not read any section.
+pub fn read_header(bytes: &[u8]) -> Result<PackHeader, VPackError> {
+    if bytes.len() < HEADER_LEN {
+        return Err(VPackError::InvalidFormat("file too short".to_string()));
+    }
+    if !bytes.starts_with(MAGIC) {
         return Err(VPackError::InvalidFormat("bad magic bytes".to_string()));
     }
+    let spec_version = bytes[5];
+    if spec_version != FORMAT_VERSION {
+        return Err(VPackError::InvalidFormat(format!(
+            "unsupported .vpack format version 0x{spec_version:02x} — rebuild with Rust engine",
+        )));
+    }
+
+    let flags = u16::from_le_bytes([bytes[6], bytes[7]]);
+    let section_count = bytes[8] as usize;
+    let manifest_hash: [u8; 32] = bytes[9..41].try_into().unwrap();
+
+    let table_end = HEADER_LEN + section_count * SECTION_ENTRY_LEN;
+    if bytes.len() < table_end {
+        return Err(VPackError::InvalidFormat(
+            "truncated section table".to_string(),
+        ));
+    }
+    let sections = bytes[HEADER_LEN..table_end]
+        .chunks_exact(SECTION_ENTRY_LEN)
+        .map(|entry| SectionEntry {
+            id: entry[0],
+            offset: u64::from_le_bytes(entry[1..9].try_into().unwrap()),
+            length: u64::from_le_bytes(entry[9..17].try_into().unwrap()),
+            checksum: u32::from_le_bytes(entry[17..21].try_into().unwrap()),
+        })
+        .collect();
+
+    Ok(PackHeader {
+        spec_version,
+        flags,
+        manifest_hash,
+        sections,
+    })
+}
+
+/// Borrow one section's bytes after verifying its checksum.
+/// `Ok(None)` if the pack has no such section.
+pub fn read_section(bytes: &[u8], id: SectionId) -> Result<Option<&[u8]>, VPackError> {
+    let header = read_header(bytes)?;
+    section(bytes, &header, id)
+}
+
+/// Read only the MANIFEST section. Accepts v0x02 packs too, which have to be
+/// decoded in full.
+pub fn read_manifest(bytes: &[u8]) -> Result<Value, VPackError> {
+    if bytes.starts_with(LEGACY_MAGIC) {
+        return Ok(deserialize_legacy(bytes)?.manifest().clone());
+    }
+    let manifest =
+        read_section(bytes, SectionId::Manifest)?.ok_or_else(|| missing(SectionId::Manifest))?;
+    serde_json::from_slice(manifest).map_err(invalid)
+}
+
+/// Read only the VECTORS section: row-major f32, `dimensions` per chunk.
+/// Half-precision packs are widened to f32. Empty for packs stored as codes
+/// only (FLAG_VECTORS_FROM_CODES).
+pub fn read_vectors(bytes: &[u8]) -> Result<Vec<f32>, VPackError> {
+    let header = read_header(bytes)?;
+    let vectors =
+        section(bytes, &header, SectionId::Vectors)?.ok_or_else(|| missing(SectionId::Vectors))?;
+    let store = decode_vectors(vectors, 1, vector_precision(header.flags)?)?;
+    Ok(store.all().to_vec())
+}
+
+/// Memory-map `path` and load it with vectors and chunk text left in place.
+pub(crate) fn open_mmap(path: &Path) -> Result<VPackIndex, VPackError> {
+    let cannot_open =
+        |err: std::io::Error| VPackError::InvalidFormat(format!("{}: {err}", path.display()));
+    let file = File::open(path).map_err(cannot_open)?;
+    // SAFETY: the map is read-only, and VPackIndex::open_mmap documents that
+    // the file must not be modified while the index is open.
+    let map = Arc::new(unsafe { Mmap::map(&file) }.map_err(cannot_open)?);
+    if !map.starts_with(MAGIC) {
+        return deserialize(&map);
+    }
+    load_sections(&map, Some(&map))
+}
+
+fn deserialize_sections(bytes: &[u8]) -> Result<VPackIndex, VPackError> {
+    load_sections(bytes, None)
+}
+
+/// Decode a v0x03 pack. With `map`, `bytes` is the mapped file: chunk text
+/// and (when aligned) vectors stay in the map instead of being copied.
+fn load_sections(bytes: &[u8], map: Option<&Arc<Mmap>>) -> Result<VPackIndex, VPackError> {
+    let header = read_header(bytes)?;
+    let required = |id| section(bytes, &header, id)?.ok_or_else(|| missing(id));
+
+    let manifest: Value =
+        serde_json::from_slice(required(SectionId::Manifest)?).map_err(invalid)?;
+    if manifest_hash(&manifest) != header.manifest_hash {
+        return Err(VPackError::InvalidFormat(
+            "manifest does not match header manifest_hash".to_string(),
+        ));
+    }
+    if let Some(provenance) = section(bytes, &header, SectionId::Provenance)? {
+        let provenance: Value = serde_json::from_slice(provenance).map_err(invalid)?;
+        check_embedder(&provenance["embedder"], &manifest)?;
+    }
+
+    let chunks_data = required(SectionId::Chunks)?;
+    let (records, text_start) = decode_chunk_table(chunks_data)?;
+    let text_len = chunks_data.len() - text_start;
+    let text_span = |record: &ChunkRecord| {
+        let start = usize::try_from(record.text_offset).ok()?;
+        let end = start.checked_add(usize::try_from(record.text_len).ok()?)?;
+        (end <= text_len).then_some(start + text_start..end + text_start)
+    };
+    let mut stored = Vec::with_capacity(records.len());
+    for record in records {
+        let span = text_span(&record)
+            .ok_or_else(|| VPackError::InvalidFormat("truncated CHUNKS section".to_string()))?;
+        let metadata = decode_metadata(record.metadata)?;
+        stored.push(match map {
+            Some(_) => {
+                let base = offset_in(bytes, chunks_data);
+                StoredChunk::mapped(record.id, metadata, base + span.start..base + span.end)
+            }
+            None => {
+                let text = String::from_utf8(chunks_data[span].to_vec()).map_err(invalid)?;
+                StoredChunk::owned(record.id, metadata, text)
+            }
+        });
+    }
+    let chunks = match map {
+        Some(map) => ChunkStore::mapped(stored, Arc::clone(map)),
+        None => ChunkStore::from_stored(stored),
+    };
+
+    let pack_index = match section(bytes, &header, SectionId::Index)? {
+        Some(index) => Some(bincode::deserialize::<PackIndex>(index)?),
+        None => None,
+    };
+    let metadata_index = match section(bytes, &header, SectionId::MetadataIndex)? {
+        Some(postings) => Some(bincode::deserialize::<MetadataIndex>(postings)?),
+        None => None,
+    };
+    let mut history = match section(bytes, &header, SectionId::Changelog)? {
+        Some(entries) => Changelog::decode(entries)?,
+        None => Changelog::default(),
+    };
+    history.expire(changelog::now_ms());
+    let quantized = match section(bytes, &header, SectionId::Quantized)? {
+        Some(codes) => Some(bincode::deserialize::<Quantizer>(codes)?),
+        None => None,
+    };
+    let mutations = match section(bytes, &header, SectionId::Mutations)? {
+        Some(mutations) => bincode::deserialize::<PackMutations>(mutations)?,
+        None => PackMutations {
+            deleted: Vec::new(),
+            appended: false,
+        },
+    };
+
+    let vectors_data = required(SectionId::Vectors)?;
+    let vectors = if header.flags & FLAG_VECTORS_FROM_CODES != 0 {
+        let ivf = pack_index
+            .as_ref()
+            .and_then(|index| index.ivf_pq.as_ref())
+            .filter(|ivf| ivf.is_valid(chunks.len(), ivf.dimensions()));
+        let quantizer = quantized
+            .as_ref()
+            .filter(|q| q.is_valid(chunks.len(), q.dimensions()));
+        match (ivf, quantizer) {
+            (Some(ivf), _) => VectorStore::from_rows(ivf.reconstruct(), ivf.dimensions()),
+            (None, Some(q)) => VectorStore::from_rows(q.reconstruct(), q.dimensions()),
+            (None, None) => {
+                return Err(VPackError::InvalidFormat(
+                    "vectors require a valid IVF-PQ index or quantized codes".to_string(),
+                ))
+            }
+        }
+    } else {
+        let precision = vector_precision(header.flags)?;
+        let components = vectors_data.len() / precision.size();
+        let dimensions = components.checked_div(chunks.len()).unwrap_or(0);
+        if !vectors_data.len().is_multiple_of(precision.size())
+            || dimensions * chunks.len() != components
+        {
+            return Err(VPackError::InvalidFormat(
+                "VECTORS length is not a multiple of the chunk count".to_string(),
+            ));
+        }
+        let start = offset_in(bytes, vectors_data);
+        map.and_then(|map| {
+            VectorStore::mapped(
+                Arc::clone(map),
+                start..start + vectors_data.len(),
+                dimensions,
+                precision,
+            )
+        })
+        .map_or_else(|| decode_vectors(vectors_data, dimensions, precision), Ok)?
+    };
+
+    let normalized = header.flags & FLAG_VECTORS_NORMALIZED != 0;
+    let index = match pack_index {
+        Some(pack_index) => restore(
+            chunks,
+            vectors,
+            normalized,
+            manifest,
+            pack_index,
+            metadata_index,
+        )
+        .and_then(|index| index.with_quantized(quantized))
+        .and_then(|index| index.with_mutations(&mutations.deleted, mutations.appended)),
+        None => {
+            // Tombstones name stored rows, which a det-hnsw or IVF-PQ rebuild
+            // re-sorts, so deleted chunks are left out before rebuilding.
+            let mut live = vec![true; chunks.len()];
+            for &row in &mutations.deleted {
+                if !live.get(row as usize).copied().unwrap_or(false) {
+                    return Err(VPackError::InvalidFormat(
+                        "deleted rows do not match chunk list".to_string(),
+                    ));
+                }
+                live[row as usize] = false;
+            }
+            let embedded = (0..chunks.len())
+                .filter(|&i| live[i])
+                .map(|i| {
+                    Ok(EmbeddedChunk {
+                        chunk: chunks.to_chunk(i)?,
+                        vector: vectors.get(i).to_vec(),
+                    })
+                })
+                .collect::<Result<_, VPackError>>()?;
+            VPackIndex::build(embedded, manifest)
+        }
+    };
+    index?.with_changelog(history)
+}
+
+fn deserialize_legacy(bytes: &[u8]) -> Result<VPackIndex, VPackError> {
+    if bytes.len() < 9 {
+        return Err(VPackError::InvalidFormat("file too short".to_string()));
+    }
 
     let version = bytes[4];
-    if version != FORMAT_VERSION {
+    if version != LEGACY_VERSION {
         return Err(VPackError::InvalidFormat(format!(
             "unsupported .vpack format version 0x{version:02x} — rebuild with Rust engine",
         )));
@@ -100,30 +506,614 @@ pub fn deserialize(bytes: &[u8]) -> Result<VPackIndex, VPackError> {
         return Err(VPackError::InvalidFormat("truncated payload".to_string()));
     }
 
-    let pack: PackPayload = bincode::deserialize(&bytes[9..9 + payload_len])?;
-    let manifest: Value = serde_json::from_str(&pack.manifest_json)
-        .map_err(|err| VPackError::InvalidFormat(err.to_string()))?;
-    let chunks = pack
+    let pack: LegacyPayload = bincode::deserialize(&bytes[9..9 + payload_len])?;
+    let manifest: Value = serde_json::from_str(&pack.manifest_json).map_err(invalid)?;
+    let mut chunks: Vec<EmbeddedChunk> = pack
         .chunks
         .into_iter()
-        .map(|chunk| EmbeddedChunk {
-            chunk: Chunk {
-                id: chunk.id,
-                text: chunk.text,
-                metadata: ChunkMetadata {
-                    source_plugin: chunk.metadata.source_plugin,
-                    source_id: chunk.metadata.source_id,
-                    source_url: chunk.metadata.source_url,
-                    created_at: chunk.metadata.created_at,
-                    updated_at: chunk.metadata.updated_at,
-                    pack_name: chunk.metadata.pack_name,
-                    chunker_plugin: chunk.metadata.chunker_plugin,
-                    extra: chunk.metadata.extra,
+        .map(|chunk| {
+            Ok(EmbeddedChunk {
+                chunk: Chunk {
+                    id: chunk.id,
+                    text: chunk.text,
+                    metadata: decode_metadata(chunk.metadata)?,
                 },
-            },
-            vector: chunk.vector,
+                vector: chunk.vector,
+            })
+        })
+        .collect::<Result<_, VPackError>>()?;
+
+    let rest = &bytes[9 + payload_len..];
+    if rest.len() < 4 {
+        return VPackIndex::build(chunks, manifest);
+    }
+
+    let index_len = u32::from_le_bytes(rest[..4].try_into().unwrap()) as usize;
+    if rest.len() < 4 + index_len {
+        return Err(VPackError::InvalidFormat("truncated index".to_string()));
+    }
+
+    let pack_index: PackIndex = bincode::deserialize(&rest[4..4 + index_len])?;
+    if let Some(ivf) = &pack_index.ivf_pq {
+        let decodable = ivf.is_valid(chunks.len(), ivf.dimensions());
+        if decodable && chunks.iter().all(|chunk| chunk.vector.is_empty()) {
+            for (chunk, vector) in chunks.iter_mut().zip(ivf.reconstruct()) {
+                chunk.vector = vector;
+            }
+        }
+    }
+    let dimensions = chunks.first().map_or(0, |chunk| chunk.vector.len());
+    let (chunks, vectors) = store::split(chunks, dimensions, VectorPrecision::F32);
+    restore(chunks, vectors, false, manifest, pack_index, None)
+}
+
+/// Reassemble an index from its stores and the stored INDEX data. Unless
+/// they are `normalized` already, vectors the options keep at unit length
+/// are scaled to it.
+fn restore(
+    chunks: ChunkStore,
+    mut vectors: VectorStore,
+    normalized: bool,
+    manifest: Value,
+    pack_index: PackIndex,
+    metadata_index: Option<MetadataIndex>,
+) -> Result<VPackIndex, VPackError> {
+    let options: BuildOptions = serde_json::from_str(&pack_index.options_json).map_err(invalid)?;
+    if options.unit_vectors() && !normalized {
+        let rows = (0..vectors.len()).map(|row| {
+            let mut vector = vectors.get(row).to_vec();
+            store::prepare(&mut vector, &options);
+            vector
+        });
+        vectors = VectorStore::from_flat(
+            rows.flatten().collect(),
+            vectors.dimensions(),
+            vectors.precision(),
+        );
+    }
+    VPackIndex::from_parts(
+        chunks,
+        vectors,
+        manifest,
+        options,
+        pack_index.hnsw,
+        pack_index.ivf_pq,
+        metadata_index,
+    )
+}
+
+fn write_sections(
+    flags: u16,
+    manifest_hash: &[u8; 32],
+    sections: &[(SectionId, Vec<u8>)],
+) -> Vec<u8> {
+    let table_end = HEADER_LEN + sections.len() * SECTION_ENTRY_LEN;
+    let mut offset = align(table_end);
+    let mut entries = Vec::with_capacity(sections.len());
+    for (id, data) in sections {
+        entries.push(SectionEntry {
+            id: *id as u8,
+            offset: offset as u64,
+            length: data.len() as u64,
+            checksum: crc32(data),
+        });
+        offset = align(offset + data.len());
+    }
+
+    let mut buf = Vec::with_capacity(offset);
+    buf.extend_from_slice(MAGIC);
+    buf.push(FORMAT_VERSION);
+    buf.extend_from_slice(&flags.to_le_bytes());
+    buf.push(sections.len() as u8);
+    buf.extend_from_slice(manifest_hash);
+    buf.resize(HEADER_LEN, 0);
+
+    for entry in &entries {
+        buf.push(entry.id);
+        buf.extend_from_slice(&entry.offset.to_le_bytes());
+        buf.extend_from_slice(&entry.length.to_le_bytes());
+        buf.extend_from_slice(&entry.checksum.to_le_bytes());
+    }
+    for ((_, data), entry) in sections.iter().zip(&entries) {
+        buf.resize(entry.offset as usize, 0);
+        buf.extend_from_slice(data);
+    }
+    buf
+}
+
+fn section<'a>(
+    bytes: &'a [u8],
+    header: &PackHeader,
+    id: SectionId,
+) -> Result<Option<&'a [u8]>, VPackError> {
+    let Some(entry) = header.sections.iter().find(|entry| entry.id == id as u8) else {
+        return Ok(None);
+    };
+    let start = usize::try_from(entry.offset).ok();
+    let end = start.and_then(|start| start.checked_add(usize::try_from(entry.length).ok()?));
+    let data = match (start, end) {
+        (Some(start), Some(end)) if end <= bytes.len() => &bytes[start..end],
+        _ => {
+            return Err(VPackError::InvalidFormat(format!(
+                "{id:?} section is out of bounds"
+            )))
+        }
+    };
+    if crc32(data) != entry.checksum {
+        return Err(VPackError::InvalidFormat(format!(
+            "{id:?} section checksum mismatch"
+        )));
+    }
+    Ok(Some(data))
+}
+
+fn encode_chunks(chunks: &ChunkStore) -> Result<Vec<u8>, VPackError> {
+    let mut text = Vec::new();
+    let mut records = Vec::with_capacity(chunks.len());
+    for (i, chunk) in chunks.iter().enumerate() {
+        let chunk_text = chunks.text(i)?;
+        records.push(ChunkRecord {
+            id: chunk.id.clone(),
+            metadata: encode_metadata(&chunk.metadata)?,
+            text_offset: text.len() as u64,
+            text_len: chunk_text.len() as u64,
+        });
+        text.extend_from_slice(chunk_text.as_bytes());
+    }
+
+    let table = bincode::serialize(&records)?;
+    let mut buf = Vec::with_capacity(8 + table.len() + text.len());
+    buf.extend_from_slice(&(table.len() as u64).to_le_bytes());
+    buf.extend_from_slice(&table);
+    buf.extend_from_slice(&text);
+    Ok(buf)
+}
+
+/// Decode the CHUNKS table. Returns the records and where the text blob
+/// starts within the section.
+fn decode_chunk_table(data: &[u8]) -> Result<(Vec<ChunkRecord>, usize), VPackError> {
+    let truncated = || VPackError::InvalidFormat("truncated CHUNKS section".to_string());
+    let table_len = data
+        .get(..8)
+        .and_then(|len| usize::try_from(u64::from_le_bytes(len.try_into().unwrap())).ok())
+        .ok_or_else(truncated)?;
+    let text_start = table_len.checked_add(8).ok_or_else(truncated)?;
+    let table = data.get(8..text_start).ok_or_else(truncated)?;
+    Ok((bincode::deserialize(table)?, text_start))
+}
+
+fn decode_vectors(
+    data: &[u8],
+    dimensions: usize,
+    precision: VectorPrecision,
+) -> Result<VectorStore, VPackError> {
+    VectorStore::from_le_bytes(data, dimensions, precision).ok_or_else(|| {
+        VPackError::InvalidFormat(format!(
+            "VECTORS length is not a multiple of {}",
+            precision.size()
+        ))
+    })
+}
+
+/// Component type of VECTORS, from the header flags.
+fn vector_precision(flags: u16) -> Result<VectorPrecision, VPackError> {
+    match flags & (FLAG_VECTORS_F16 | FLAG_VECTORS_BF16) {
+        0 => Ok(VectorPrecision::F32),
+        FLAG_VECTORS_F16 => Ok(VectorPrecision::F16),
+        FLAG_VECTORS_BF16 => Ok(VectorPrecision::Bf16),
+        _ => Err(VPackError::InvalidFormat(
+            "header sets both the f16 and bf16 VECTORS flags".to_string(),
+        )),
+    }
+}
+
+/// Per-source fingerprints: SHA-256 over the source's live chunk ids and texts
+/// in pack order, plus the latest `updated_at`, and the embedder model and
+/// weights hash the vectors came from. No build clock, so identical inputs
+/// still produce identical bytes.
+fn provenance(index: &VPackIndex) -> Result<Value, VPackError> {
+    let chunks = &index.chunks;
+    #[derive(Default)]
+    struct Source<'a> {
+        chunks: usize,
+        content: Vec<u8>,
+        updated_at: Option<&'a str>,
+    }
+
+    let mut sources: BTreeMap<(&str, &str), Source> = BTreeMap::new();
+    for (i, chunk) in chunks.iter().enumerate() {
+        if index.tombstones.contains(i) {
+            continue;
+        }
+        let meta = &chunk.metadata;
+        let source = sources
+            .entry((&meta.source_plugin, &meta.source_id))
+            .or_default();
+        source.chunks += 1;
+        source.content.extend_from_slice(chunk.id.as_bytes());
+        source.content.push(0);
+        source.content.extend_from_slice(chunks.text(i)?.as_bytes());
+        source.content.push(0);
+        source.updated_at = source.updated_at.max(meta.updated_at.as_deref());
+    }
+
+    let sources: Vec<Value> = sources
+        .into_iter()
+        .map(|((plugin, id), source)| {
+            json!({
+                "source_plugin": plugin,
+                "source_id": id,
+                "chunks": source.chunks,
+                "fingerprint": format!("sha256:{}", to_hex(&sha256(&source.content))),
+                "updated_at": source.updated_at,
+            })
+        })
+        .collect();
+    Ok(json!({
+        "generator": concat!("vpack-engine ", env!("CARGO_PKG_VERSION")),
+        "embedder": embedder_record(index.manifest()),
+        "sources": sources,
+    }))
+}
+
+fn embedder_record(manifest: &Value) -> Value {
+    let embedder = embedder_config(manifest);
+    let field = |key| embedder.and_then(|config| config.get(key)).cloned();
+    json!({ "model": field("model"), "model_hash": field("model_hash") })
+}
+
+/// The embedder recorded at build must still be the one the manifest pins.
+/// Packs written before the record existed carry none and are accepted.
+fn check_embedder(recorded: &Value, manifest: &Value) -> Result<(), VPackError> {
+    if recorded.is_null() {
+        return Ok(());
+    }
+    let pinned = embedder_record(manifest);
+    let field = |value: &Value, key: &str| value[key].as_str().unwrap_or_default().to_string();
+    if pinned["model"] != recorded["model"] {
+        return Err(VPackError::ModelMismatch {
+            expected: field(recorded, "model"),
+            got: field(&pinned, "model"),
+        });
+    }
+    if pinned["model_hash"] != recorded["model_hash"] {
+        return Err(VPackError::ModelHashMismatch {
+            model: field(recorded, "model"),
+            expected: field(recorded, "model_hash"),
+            got: field(&pinned, "model_hash"),
+        });
+    }
+    Ok(())
+}
+
+pub(crate) fn encode_metadata(metadata: &ChunkMetadata) -> Result<PackMetadata, VPackError> {
+    Ok(PackMetadata {
+        source_plugin: metadata.source_plugin.clone(),
+        source_id: metadata.source_id.clone(),
+        source_url: metadata.source_url.clone(),
+        created_at: metadata.created_at.clone(),
+        updated_at: metadata.updated_at.clone(),
+        pack_name: metadata.pack_name.clone(),
+        chunker_plugin: metadata.chunker_plugin.clone(),
+        extra_json: encode_extra(&metadata.extra)?,
+    })
+}
+
+pub(crate) fn decode_metadata(metadata: PackMetadata) -> Result<ChunkMetadata, VPackError> {
+    Ok(ChunkMetadata {
+        source_plugin: metadata.source_plugin,
+        source_id: metadata.source_id,
+        source_url: metadata.source_url,
+        created_at: metadata.created_at,
+        updated_at: metadata.updated_at,
+        pack_name: metadata.pack_name,
+        chunker_plugin: metadata.chunker_plugin,
+        extra: decode_extra(&metadata.extra_json)?,
+    })
+}
+
+fn encode_extra(extra: &HashMap<String, Value>) -> Result<String, VPackError> {
+    if extra.is_empty() {
+        return Ok(String::new());
+    }
+    let sorted: BTreeMap<&String, &Value> = extra.iter().collect();
+    serde_json::to_string(&sorted).map_err(invalid)
+}
+
+fn decode_extra(json: &str) -> Result<HashMap<String, Value>, VPackError> {
+    if json.is_empty() {
+        return Ok(HashMap::new());
+    }
+    serde_json::from_str(json).map_err(invalid)
+}
+
+fn align(offset: usize) -> usize {
+    offset.next_multiple_of(SECTION_ALIGN)
+}
+
+/// Byte offset of `part` within `whole`; `part` must be a subslice.
+fn offset_in(whole: &[u8], part: &[u8]) -> usize {
+    part.as_ptr() as usize - whole.as_ptr() as usize
+}
+
+fn missing(id: SectionId) -> VPackError {
+    VPackError::InvalidFormat(format!("missing {id:?} section"))
+}
+
+fn invalid(err: impl std::fmt::Display) -> VPackError {
+    VPackError::InvalidFormat(err.to_string())
+}
+
+// ── v0x02 ────────────────────────────────────────────────────────────────────
+
+#[derive(Serialize, Deserialize)]
+struct LegacyChunk {
+    id: String,
+    text: String,
+    metadata: PackMetadata,
+    /// Empty when the vectors are rebuilt from IVF-PQ codes on load.
+    vector: Vec<f32>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct LegacyPayload {
+    manifest_json: String,
+    chunks: Vec<LegacyChunk>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::query::QueryOptions;
+
+    fn make_index() -> VPackIndex {
+        let chunks = ["a", "b", "c"]
+            .iter()
+            .enumerate()
+            .map(|(i, id)| {
+                let mut vector = vec![0.0; 3];
+                vector[i] = 1.0;
+                EmbeddedChunk {
+                    chunk: Chunk {
+                        id: id.to_string(),
+                        text: format!("text for {id}"),
+                        metadata: ChunkMetadata {
+                            source_plugin: "@vpack/source-fs".to_string(),
+                            source_id: "doc".to_string(),
+                            source_url: None,
+                            created_at: None,
+                            updated_at: Some(format!("2026-01-0{}T00:00:00Z", i + 1)),
+                            pack_name: "test".to_string(),
+                            chunker_plugin: "@vpack/chunker-fixed".to_string(),
+                            extra: HashMap::new(),
+                        },
+                    },
+                    vector,
+                }
+            })
+            .collect();
+        let manifest = json!({
+            "vpack": "1.0",
+            "name": "@test/fixture",
+            "version": "1.0.0",
+            "plugins": [{
+                "kind": "embedder",
+                "use": "@vpack/embedder-xenova",
+                "model": "Xenova/all-MiniLM-L6-v2",
+                "model_hash": "sha256:3e4b",
+                "dimensions": 3
+            }]
+        });
+        VPackIndex::build(chunks, manifest).unwrap()
+    }
+
+    /// The v0x02 writer, kept to produce fixtures for the legacy reader.
+    fn serialize_legacy(index: &VPackIndex, with_trailer: bool) -> Vec<u8> {
+        let payload = bincode::serialize(&LegacyPayload {
+            manifest_json: serde_json::to_string(index.manifest()).unwrap(),
+            chunks: index
+                .chunks
+                .iter()
+                .enumerate()
+                .map(|(i, chunk)| LegacyChunk {
+                    id: chunk.id.clone(),
+                    text: index.chunks.text(i).unwrap().to_string(),
+                    metadata: encode_metadata(&chunk.metadata).unwrap(),
+                    vector: index.vectors.get(i).to_vec(),
+                })
+                .collect(),
+        })
+        .unwrap();
+
+        let mut buf = LEGACY_MAGIC.to_vec();
+        buf.push(LEGACY_VERSION);
+        buf.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+        buf.extend_from_slice(&payload);
+        if with_trailer {
+            let trailer = bincode::serialize(&PackIndex {
+                options_json: serde_json::to_string(index.options()).unwrap(),
+                hnsw: index.hnsw.clone(),
+                ivf_pq: index.ivf_pq.clone(),
+            })
+            .unwrap();
+            buf.extend_from_slice(&(trailer.len() as u32).to_le_bytes());
+            buf.extend_from_slice(&trailer);
+        }
+        buf
+    }
+
+    #[test]
+    fn header_and_sections_are_aligned_and_complete() {
+        let bytes = serialize(&make_index()).unwrap();
+        let header = read_header(&bytes).unwrap();
+
+        assert_eq!(header.spec_version, FORMAT_VERSION);
+        assert_eq!(header.flags, FLAG_VECTORS_NORMALIZED);
+        assert_eq!(header.manifest_hash, manifest_hash(make_index().manifest()));
+        let ids: Vec<u8> = header.sections.iter().map(|s| s.id).collect();
+        assert_eq!(ids, vec![1, 2, 3, 4, 5]);
+        assert!(header.sections.iter().all(|s| s.offset % 64 == 0));
+    }
+
+    #[test]
+    fn vectors_and_manifest_read_without_chunks() {
+        let index = make_index();
+        let bytes = serialize(&index).unwrap();
+
+        assert_eq!(read_manifest(&bytes).unwrap(), *index.manifest());
+        assert_eq!(read_vectors(&bytes).unwrap(), index.vectors.all().to_vec());
+    }
+
+    #[test]
+    fn corrupted_section_fails_checksum() {
+        let mut bytes = serialize(&make_index()).unwrap();
+        let header = read_header(&bytes).unwrap();
+        let chunks = &header.sections[1];
+        bytes[chunks.offset as usize + 9] ^= 0xff;
+
+        assert!(read_section(&bytes, SectionId::Manifest).is_ok());
+        let err = deserialize(&bytes).err().unwrap();
+        assert!(err.to_string().contains("checksum"), "{err}");
+    }
+
+    #[test]
+    fn provenance_fingerprints_sources() {
+        let bytes = serialize(&make_index()).unwrap();
+        let provenance: Value = serde_json::from_slice(
+            read_section(&bytes, SectionId::Provenance)
+                .unwrap()
+                .unwrap(),
+        )
+        .unwrap();
+        let source = &provenance["sources"][0];
+        assert_eq!(source["source_id"], "doc");
+        assert_eq!(source["chunks"], 3);
+        assert_eq!(source["updated_at"], "2026-01-03T00:00:00Z");
+        assert_eq!(provenance["embedder"]["model"], "Xenova/all-MiniLM-L6-v2");
+    }
+
+    #[test]
+    fn normalizes_cosine_vectors_of_packs_written_before_the_flag() {
+        let bytes = serialize(&make_index()).unwrap();
+        let header = read_header(&bytes).unwrap();
+        let sections: Vec<(SectionId, Vec<u8>)> = [
+            SectionId::Manifest,
+            SectionId::Chunks,
+            SectionId::Vectors,
+            SectionId::Index,
+            SectionId::Provenance,
+        ]
+        .into_iter()
+        .map(|id| {
+            let mut data = read_section(&bytes, id).unwrap().unwrap().to_vec();
+            if id == SectionId::Vectors {
+                data = data
+                    .chunks_exact(4)
+                    .flat_map(|b| (f32::from_le_bytes(b.try_into().unwrap()) * 3.0).to_le_bytes())
+                    .collect();
+            }
+            (id, data)
         })
         .collect();
+        let legacy = write_sections(0, &header.manifest_hash, &sections);
 
-    VPackIndex::build(chunks, manifest)
+        let index = deserialize(&legacy).unwrap();
+        assert_eq!(index.vectors.get(1).to_vec(), vec![0.0, 1.0, 0.0]);
+        let options = QueryOptions {
+            top_k: 1,
+            ..Default::default()
+        };
+        let results = index.query(&[0.0, 2.0, 0.0], options).unwrap();
+        assert_eq!(results[0].chunk.id, "b");
+        assert!((results[0].score - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn rejects_pack_whose_manifest_changed_model() {
+        let bytes = serialize(&make_index()).unwrap();
+        let header = read_header(&bytes).unwrap();
+        let sections: Vec<(SectionId, Vec<u8>)> = [
+            SectionId::Manifest,
+            SectionId::Chunks,
+            SectionId::Vectors,
+            SectionId::Index,
+            SectionId::Provenance,
+        ]
+        .into_iter()
+        .map(|id| (id, read_section(&bytes, id).unwrap().unwrap().to_vec()))
+        .collect();
+        let rewrite = |provenance: Value| {
+            let mut sections = sections.clone();
+            sections[4].1 = serde_json::to_vec(&provenance).unwrap();
+            write_sections(header.flags, &header.manifest_hash, &sections)
+        };
+        let recorded: Value = serde_json::from_slice(&sections[4].1).unwrap();
+
+        let mut provenance = recorded.clone();
+        provenance["embedder"]["model"] = json!("BAAI/bge-small-en-v1.5");
+        let err = deserialize(&rewrite(provenance)).err().unwrap();
+        assert!(matches!(err, VPackError::ModelMismatch { .. }), "{err}");
+
+        let mut provenance = recorded;
+        provenance["embedder"]["model_hash"] = json!("sha256:00");
+        let err = deserialize(&rewrite(provenance)).err().unwrap();
+        assert!(matches!(err, VPackError::ModelHashMismatch { .. }), "{err}");
+    }
+
+    #[test]
+    fn rebuild_without_index_keeps_deleted_chunks_gone() {
+        let index = make_index();
+        let mut manifest = index.manifest().clone();
+        manifest["index"] = json!({ "type": "det-hnsw" });
+        let chunk = |i| EmbeddedChunk {
+            chunk: index.chunks.to_chunk(i).unwrap(),
+            vector: index.vectors.get(i).to_vec(),
+        };
+        // "a" is appended after "b" and "c", so a rebuild moves it to row 0.
+        let mut mutated = VPackIndex::build(vec![chunk(1), chunk(2)], manifest).unwrap();
+        mutated.insert(vec![chunk(0)]).unwrap();
+        assert_eq!(mutated.delete(&["a"]), 1);
+
+        let bytes = serialize(&mutated).unwrap();
+        let header = read_header(&bytes).unwrap();
+        let sections: Vec<(SectionId, Vec<u8>)> = [
+            SectionId::Manifest,
+            SectionId::Chunks,
+            SectionId::Vectors,
+            SectionId::Provenance,
+            SectionId::Mutations,
+        ]
+        .into_iter()
+        .map(|id| (id, read_section(&bytes, id).unwrap().unwrap().to_vec()))
+        .collect();
+        let without_index = write_sections(header.flags, &header.manifest_hash, &sections);
+
+        let restored = deserialize(&without_index).unwrap();
+        assert_eq!(restored.chunk_count(), 2);
+        let options = QueryOptions {
+            top_k: 10,
+            ..Default::default()
+        };
+        let mut ids: Vec<String> = restored
+            .query(&[1.0, 1.0, 1.0], options)
+            .unwrap()
+            .into_iter()
+            .map(|result| result.chunk.id)
+            .collect();
+        ids.sort();
+        assert_eq!(ids, ["b", "c"]);
+    }
+
+    #[test]
+    fn reads_legacy_v2_packs() {
+        let index = make_index();
+        for with_trailer in [true, false] {
+            let bytes = serialize_legacy(&index, with_trailer);
+            let restored = deserialize(&bytes).unwrap();
+            assert_eq!(restored.chunk_count(), 3);
+            assert_eq!(restored.options(), index.options());
+            assert_eq!(read_manifest(&bytes).unwrap(), *index.manifest());
+            assert_eq!(serialize(&restored).unwrap(), serialize(&index).unwrap());
+        }
+    }
 }
@@ -0,0 +1,429 @@
+// simd.rs — f32 distance kernels with runtime CPU dispatch
+//
+// x86_64 gets AVX-512, AVX2 and SSE versions, picked once per process with
+// `is_x86_feature_detected!`. Other targets use the portable version, which
+// the compiler vectorizes as it can. `VPACK_SIMD=scalar|sse|avx2` forces a
+// narrower version, to benchmark it or to rule the kernels out of a bug.
+//
+// Every version computes the same thing in the same order. Lane i of 32
+// running sums takes components i, i + 32, ..., using a separate multiply and
+// add (no FMA). The lanes are reduced in a fixed tree, and the tail is added
+// last. Scores are therefore bit-identical on every CPU. Det-HNSW (RFC-0002)
+// depends on this: otherwise its graph would depend on the machine that
+// built it.
+
+use once_cell::sync::Lazy;
+
+/// Running sums per quantity. Two AVX-512, four AVX2 or eight SSE registers.
+const LANES: usize = 32;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Level {
+    Scalar,
+    Sse,
+    Avx2,
+    Avx512,
+}
+
+impl Level {
+    /// The widest level this CPU supports.
+    fn detect() -> Self {
+        #[cfg(target_arch = "x86_64")]
+        {
+            if is_x86_feature_detected!("avx512f") {
+                return Level::Avx512;
+            }
+            if is_x86_feature_detected!("avx2") {
+                return Level::Avx2;
+            }
+            // SSE2 is part of x86_64.
+            Level::Sse
+        }
+        #[cfg(not(target_arch = "x86_64"))]
+        Level::Scalar
+    }
+
+    /// `detect`, or the narrower level named by `VPACK_SIMD`.
+    fn selected() -> Self {
+        let best = Self::detect();
+        let requested = match std::env::var("VPACK_SIMD").as_deref() {
+            Ok("scalar") => Level::Scalar,
+            Ok("sse") => Level::Sse,
+            Ok("avx2") => Level::Avx2,
+            _ => best,
+        };
+        requested.min(best)
+    }
+
+    /// Every level this CPU supports, narrowest first.
+    #[cfg(test)]
+    fn supported() -> Vec<Self> {
+        let best = Self::detect();
+        [Level::Scalar, Level::Sse, Level::Avx2, Level::Avx512]
+            .into_iter()
+            .filter(|&level| level <= best)
+            .collect()
+    }
+}
+
+/// One implementation of each kernel.
+pub(crate) struct Kernels {
+    /// a · b
+    pub(crate) dot: fn(&[f32], &[f32]) -> f32,
+    /// |a - b|²
+    pub(crate) squared_distance: fn(&[f32], &[f32]) -> f32,
+    /// [a · b, |a|², |b|²]
+    pub(crate) cosine_sums: fn(&[f32], &[f32]) -> [f32; 3],
+}
+
+static KERNELS: Lazy<Kernels> = Lazy::new(|| Kernels::new(Level::selected()));
+
+/// The kernels for this CPU.
+#[inline]
+pub(crate) fn kernels() -> &'static Kernels {
+    &KERNELS
+}
+
+impl Kernels {
+    /// Kernels for `level`, which the CPU must support.
+    fn new(level: Level) -> Self {
+        // SAFETY: callers only ask for supported levels, so the CPU has the
+        // target features each level's kernels are compiled with.
+        match level {
+            Level::Scalar => Self {
+                dot: |a, b| unsafe { dot::<[f32; LANES]>(a, b) },
+                squared_distance: |a, b| unsafe { squared_distance::<[f32; LANES]>(a, b) },
+                cosine_sums: |a, b| unsafe { cosine_sums::<[f32; LANES]>(a, b) },
+            },
+            #[cfg(target_arch = "x86_64")]
+            Level::Sse => Self {
+                dot: |a, b| unsafe { x86::dot_sse(a, b) },
+                squared_distance: |a, b| unsafe { x86::squared_distance_sse(a, b) },
+                cosine_sums: |a, b| unsafe { x86::cosine_sums_sse(a, b) },
+            },
+            #[cfg(target_arch = "x86_64")]
+            Level::Avx2 => Self {
+                dot: |a, b| unsafe { x86::dot_avx2(a, b) },
+                squared_distance: |a, b| unsafe { x86::squared_distance_avx2(a, b) },
+                cosine_sums: |a, b| unsafe { x86::cosine_sums_avx2(a, b) },
+            },
+            #[cfg(target_arch = "x86_64")]
+            Level::Avx512 => Self {
+                dot: |a, b| unsafe { x86::dot_avx512(a, b) },
+                squared_distance: |a, b| unsafe { x86::squared_distance_avx512(a, b) },
+                cosine_sums: |a, b| unsafe { x86::cosine_sums_avx512(a, b) },
+            },
+            #[cfg(not(target_arch = "x86_64"))]
+            _ => Self::new(Level::Scalar),
+        }
+    }
+}
+
+/// `LANES` f32 lanes in whatever registers a level has. Methods are only
+/// called from kernels compiled with the level's target features.
+trait Lanes: Copy {
+    unsafe fn zero() -> Self;
+    /// Load `LANES` floats from `p`, unaligned.
+    unsafe fn load(p: *const f32) -> Self;
+    unsafe fn add(self, other: Self) -> Self;
+    unsafe fn sub(self, other: Self) -> Self;
+    unsafe fn mul(self, other: Self) -> Self;
+    unsafe fn store(self, out: &mut [f32; LANES]);
+}
+
+impl Lanes for [f32; LANES] {
+    #[inline(always)]
+    unsafe fn zero() -> Self {
+        [0.0; LANES]
+    }
+
+    #[inline(always)]
+    unsafe fn load(p: *const f32) -> Self {
+        std::ptr::read_unaligned(p as *const Self)
+    }
+
+    #[inline(always)]
+    unsafe fn add(mut self, other: Self) -> Self {
+        for (x, y) in self.iter_mut().zip(other) {
+            *x += y;
+        }
+        self
+    }
+
+    #[inline(always)]
+    unsafe fn sub(mut self, other: Self) -> Self {
+        for (x, y) in self.iter_mut().zip(other) {
+            *x -= y;
+        }
+        self
+    }
+
+    #[inline(always)]
+    unsafe fn mul(mut self, other: Self) -> Self {
+        for (x, y) in self.iter_mut().zip(other) {
+            *x *= y;
+        }
+        self
+    }
+
+    #[inline(always)]
+    unsafe fn store(self, out: &mut [f32; LANES]) {
+        *out = self;
+    }
+}
+
+/// Sum the lanes pairwise: lane i gets lane i + 16, then i + 8, ...
+#[inline(always)]
+unsafe fn reduce<L: Lanes>(lanes: L) -> f32 {
+    let mut sums = [0.0f32; LANES];
+    lanes.store(&mut sums);
+    let mut width = LANES / 2;
+    while width > 0 {
+        for i in 0..width {
+            sums[i] += sums[i + width];
+        }
+        width /= 2;
+    }
+    sums[0]
+}
+
+/// Components before `whole` fill complete lane blocks.
+#[inline(always)]
+fn whole(len: usize) -> usize {
+    len - len % LANES
+}
+
+#[inline(always)]
+unsafe fn dot<L: Lanes>(a: &[f32], b: &[f32]) -> f32 {
+    let n = a.len().min(b.len());
+    let (pa, pb) = (a.as_ptr(), b.as_ptr());
+    let mut sums = L::zero();
+    for i in (0..whole(n)).step_by(LANES) {
+        sums = sums.add(L::load(pa.add(i)).mul(L::load(pb.add(i))));
+    }
+    (whole(n)..n).fold(reduce(sums), |sum, i| sum + a[i] * b[i])
+}
+
+#[inline(always)]
+unsafe fn squared_distance<L: Lanes>(a: &[f32], b: &[f32]) -> f32 {
+    let n = a.len().min(b.len());
+    let (pa, pb) = (a.as_ptr(), b.as_ptr());
+    let mut sums = L::zero();
+    for i in (0..whole(n)).step_by(LANES) {
+        let diff = L::load(pa.add(i)).sub(L::load(pb.add(i)));
+        sums = sums.add(diff.mul(diff));
+    }
+    (whole(n)..n).fold(reduce(sums), |sum, i| {
+        let diff = a[i] - b[i];
+        sum + diff * diff
+    })
+}
+
+#[inline(always)]
+unsafe fn cosine_sums<L: Lanes>(a: &[f32], b: &[f32]) -> [f32; 3] {
+    let n = a.len().min(b.len());
+    let (pa, pb) = (a.as_ptr(), b.as_ptr());
+    let (mut dot, mut norm_a, mut norm_b) = (L::zero(), L::zero(), L::zero());
+    for i in (0..whole(n)).step_by(LANES) {
+        let (x, y) = (L::load(pa.add(i)), L::load(pb.add(i)));
+        dot = dot.add(x.mul(y));
+        norm_a = norm_a.add(x.mul(x));
+        norm_b = norm_b.add(y.mul(y));
+    }
+    (whole(n)..n).fold(
+        [reduce(dot), reduce(norm_a), reduce(norm_b)],
+        |[dot, norm_a, norm_b], i| {
+            let (x, y) = (a[i], b[i]);
+            [dot + x * y, norm_a + x * x, norm_b + y * y]
+        },
+    )
+}
+
+#[cfg(target_arch = "x86_64")]
+mod x86 {
+    use super::{Lanes, LANES};
+    use std::arch::x86_64::*;
+
+    /// Implements `Lanes` for `[$reg; LANES / $width]` with one intrinsic
+    /// per operation.
+    macro_rules! lanes {
+        ($reg:ty, $width:expr, $zero:ident, $load:ident, $store:ident,
+         $add:ident, $sub:ident, $mul:ident) => {
+            impl Lanes for [$reg; LANES / $width] {
+                #[inline(always)]
+                unsafe fn zero() -> Self {
+                    [$zero(); LANES / $width]
+                }
+
+                #[inline(always)]
+                unsafe fn load(p: *const f32) -> Self {
+                    let mut regs = Self::zero();
+                    for (r, reg) in regs.iter_mut().enumerate() {
+                        *reg = $load(p.add(r * $width));
+                    }
+                    regs
+                }
+
+                #[inline(always)]
+                unsafe fn add(mut self, other: Self) -> Self {
+                    for (reg, other) in self.iter_mut().zip(other) {
+                        *reg = $add(*reg, other);
+                    }
+                    self
+                }
+
+                #[inline(always)]
+                unsafe fn sub(mut self, other: Self) -> Self {
+                    for (reg, other) in self.iter_mut().zip(other) {
+                        *reg = $sub(*reg, other);
+                    }
+                    self
+                }
+
+                #[inline(always)]
+                unsafe fn mul(mut self, other: Self) -> Self {
+                    for (reg, other) in self.iter_mut().zip(other) {
+                        *reg = $mul(*reg, other);
+                    }
+                    self
+                }
+
+                #[inline(always)]
+                unsafe fn store(self, out: &mut [f32; LANES]) {
+                    for (r, reg) in self.into_iter().enumerate() {
+                        $store(out.as_mut_ptr().add(r * $width), reg);
+                    }
+                }
+            }
+        };
+    }
+
+    lanes!(
+        __m128,
+        4,
+        _mm_setzero_ps,
+        _mm_loadu_ps,
+        _mm_storeu_ps,
+        _mm_add_ps,
+        _mm_sub_ps,
+        _mm_mul_ps
+    );
+    lanes!(
+        __m256,
+        8,
+        _mm256_setzero_ps,
+        _mm256_loadu_ps,
+        _mm256_storeu_ps,
+        _mm256_add_ps,
+        _mm256_sub_ps,
+        _mm256_mul_ps
+    );
+    lanes!(
+        __m512,
+        16,
+        _mm512_setzero_ps,
+        _mm512_loadu_ps,
+        _mm512_storeu_ps,
+        _mm512_add_ps,
+        _mm512_sub_ps,
+        _mm512_mul_ps
+    );
+
+    /// Instantiates the generic kernels with a level's registers, compiled
+    /// with its target features.
+    macro_rules! kernels {
+        ($feature:literal, $lanes:ty, $dot:ident, $squared_distance:ident, $cosine_sums:ident) => {
+            #[target_feature(enable = $feature)]
+            pub(super) unsafe fn $dot(a: &[f32], b: &[f32]) -> f32 {
+                super::dot::<$lanes>(a, b)
+            }
+
+            #[target_feature(enable = $feature)]
+            pub(super) unsafe fn $squared_distance(a: &[f32], b: &[f32]) -> f32 {
+                super::squared_distance::<$lanes>(a, b)
+            }
+
+            #[target_feature(enable = $feature)]
+            pub(super) unsafe fn $cosine_sums(a: &[f32], b: &[f32]) -> [f32; 3] {
+                super::cosine_sums::<$lanes>(a, b)
+            }
+        };
+    }
+
+    kernels!(
+        "sse2",
+        [__m128; LANES / 4],
+        dot_sse,
+        squared_distance_sse,
+        cosine_sums_sse
+    );
+    kernels!(
+        "avx2",
+        [__m256; LANES / 8],
+        dot_avx2,
+        squared_distance_avx2,
+        cosine_sums_avx2
+    );
+    kernels!(
+        "avx512f",
+        [__m512; LANES / 16],
+        dot_avx512,
+        squared_distance_avx512,
+        cosine_sums_avx512
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vector(len: usize, seed: f32) -> Vec<f32> {
+        (0..len)
+            .map(|i| ((i as f32 + seed) * 0.731).sin() * 3.0)
+            .collect()
+    }
+
+    #[test]
+    fn every_level_is_bit_identical_to_scalar() {
+        let scalar = Kernels::new(Level::Scalar);
+        // Empty, tail only, whole blocks, and blocks plus a tail.
+        for len in [0, 7, 32, 64, 100, 384, 1_537] {
+            let (a, b) = (vector(len, 0.5), vector(len, 9.0));
+            for level in Level::supported() {
+                let kernels = Kernels::new(level);
+                let same = |x: f32, y: f32| x.to_bits() == y.to_bits();
+                assert!(
+                    same((kernels.dot)(&a, &b), (scalar.dot)(&a, &b)),
+                    "{level:?} {len}"
+                );
+                assert!(same(
+                    (kernels.squared_distance)(&a, &b),
+                    (scalar.squared_distance)(&a, &b)
+                ));
+                let (got, want) = ((kernels.cosine_sums)(&a, &b), (scalar.cosine_sums)(&a, &b));
+                assert!(
+                    got.iter().zip(want).all(|(&x, y)| same(x, y)),
+                    "{level:?} {len}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn kernels_match_sequential_sums() {
+        let (a, b) = (vector(1_000, 1.0), vector(1_000, 2.0));
+        let kernels = kernels();
+        let dot: f64 = a.iter().zip(&b).map(|(x, y)| *x as f64 * *y as f64).sum();
+        let squared: f64 = a
+            .iter()
+            .zip(&b)
+            .map(|(x, y)| (*x as f64 - *y as f64).powi(2))
+            .sum();
+        assert!(((kernels.dot)(&a, &b) as f64 - dot).abs() < 1e-3 * dot.abs().max(1.0));
+        assert!(((kernels.squared_distance)(&a, &b) as f64 - squared).abs() < 1e-3 * squared);
+        let [d, na, nb] = (kernels.cosine_sums)(&a, &b);
+        assert_eq!(d, (kernels.dot)(&a, &b));
+        assert_eq!(na, (kernels.dot)(&a, &a));
+        assert_eq!(nb, (kernels.dot)(&b, &b));
+    }
+}
@@ -0,0 +1,466 @@
+// store.rs — chunk and vector storage behind VPackIndex
+//
+// Built and deserialized indexes own their data. Indexes opened with
+// `VPackIndex::open_mmap` borrow from a read-only memory map instead: vectors
+// are read in place as `&[f32]`, or `&[f16]` / `&[bf16]` for half-precision
+// packs, and chunk text is validated and decoded only when a result needs it.
+// Ids and metadata are always decoded up front, since filters need them for
+// every chunk. Half-precision rows are widened to f32 as they are scored.
+//
+// Rows are only ever appended. Deleted rows stay in place, marked in
+// `Tombstones`, until the index is compacted.
+
+use crate::chunk::{Chunk, ChunkMetadata, EmbeddedChunk};
+use crate::error::VPackError;
+use crate::math::{self, DistanceMetric};
+use crate::options::{BuildOptions, VectorPrecision};
+use half::slice::HalfFloatSliceExt;
+use half::vec::HalfFloatVecExt;
+use half::{bf16, f16};
+use memmap2::Mmap;
+use std::ops::Range;
+use std::sync::Arc;
+
+pub(crate) struct VectorStore {
+    dimensions: usize,
+    precision: VectorPrecision,
+    data: VectorData,
+}
+
+enum VectorData {
+    F32(Vec<f32>),
+    F16(Vec<f16>),
+    Bf16(Vec<bf16>),
+    /// Byte range of the VECTORS section within the map, in the store's
+    /// precision.
+    Mapped {
+        map: Arc<Mmap>,
+        range: Range<usize>,
+    },
+}
+
+impl VectorStore {
+    pub(crate) fn from_rows(rows: Vec<Vec<f32>>, dimensions: usize) -> Self {
+        Self::from_flat(
+            rows.into_iter().flatten().collect(),
+            dimensions,
+            VectorPrecision::F32,
+        )
+    }
+
+    /// Store `data` in `precision`, rounding to nearest when narrowing.
+    pub(crate) fn from_flat(data: Vec<f32>, dimensions: usize, precision: VectorPrecision) -> Self {
+        let data = match precision {
+            VectorPrecision::F32 => VectorData::F32(data),
+            VectorPrecision::F16 => VectorData::F16(Vec::from_f32_slice(&data)),
+            VectorPrecision::Bf16 => VectorData::Bf16(Vec::from_f32_slice(&data)),
+        };
+        Self {
+            dimensions,
+            precision,
+            data,
+        }
+    }
+
+    /// Decode little-endian VECTORS bytes. `None` when they are not a whole
+    /// number of components.
+    pub(crate) fn from_le_bytes(
+        bytes: &[u8],
+        dimensions: usize,
+        precision: VectorPrecision,
+    ) -> Option<Self> {
+        if !bytes.len().is_multiple_of(precision.size()) {
+            return None;
+        }
+        let data = match precision {
+            VectorPrecision::F32 => VectorData::F32(
+                bytes
+                    .chunks_exact(4)
+                    .map(|b| f32::from_le_bytes(b.try_into().unwrap()))
+                    .collect(),
+            ),
+            VectorPrecision::F16 => VectorData::F16(
+                bytes
+                    .chunks_exact(2)
+                    .map(|b| f16::from_le_bytes([b[0], b[1]]))
+                    .collect(),
+            ),
+            VectorPrecision::Bf16 => VectorData::Bf16(
+                bytes
+                    .chunks_exact(2)
+                    .map(|b| bf16::from_le_bytes([b[0], b[1]]))
+                    .collect(),
+            ),
+        };
+        Some(Self {
+            dimensions,
+            precision,
+            data,
+        })
+    }
+
+    /// View `range` of the map in place. `None` when that is not possible —
+    /// misaligned bytes or a big-endian host — and the caller should decode
+    /// a copy instead.
+    pub(crate) fn mapped(
+        map: Arc<Mmap>,
+        range: Range<usize>,
+        dimensions: usize,
+        precision: VectorPrecision,
+    ) -> Option<Self> {
+        if cfg!(target_endian = "big") || view(&map[range.clone()], precision).is_none() {
+            return None;
+        }
+        Some(Self {
+            dimensions,
+            precision,
+            data: VectorData::Mapped { map, range },
+        })
+    }
+
+    pub(crate) fn dimensions(&self) -> usize {
+        self.dimensions
+    }
+
+    pub(crate) fn precision(&self) -> VectorPrecision {
+        self.precision
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.all().len() / self.dimensions.max(1)
+    }
+
+    #[inline]
+    pub(crate) fn get(&self, i: usize) -> VectorSlice<'_> {
+        self.all()
+            .range(i * self.dimensions..(i + 1) * self.dimensions)
+    }
+
+    /// All vectors, row-major.
+    pub(crate) fn all(&self) -> VectorSlice<'_> {
+        match &self.data {
+            VectorData::F32(data) => VectorSlice::F32(data),
+            VectorData::F16(data) => VectorSlice::F16(data),
+            VectorData::Bf16(data) => VectorSlice::Bf16(data),
+            VectorData::Mapped { map, range } => view(&map[range.clone()], self.precision)
+                .expect("alignment checked in VectorStore::mapped"),
+        }
+    }
+
+    /// All vectors as little-endian VECTORS bytes.
+    pub(crate) fn to_le_bytes(&self) -> Vec<u8> {
+        match self.all() {
+            VectorSlice::F32(data) => data.iter().flat_map(|x| x.to_le_bytes()).collect(),
+            VectorSlice::F16(data) => data.iter().flat_map(|x| x.to_le_bytes()).collect(),
+            VectorSlice::Bf16(data) => data.iter().flat_map(|x| x.to_le_bytes()).collect(),
+        }
+    }
+
+    pub(crate) fn is_mapped(&self) -> bool {
+        matches!(self.data, VectorData::Mapped { .. })
+    }
+
+    /// Append a row, rounded to the store's precision. A mapped store is
+    /// copied into memory first.
+    pub(crate) fn push(&mut self, vector: &[f32]) {
+        if let VectorData::Mapped { .. } = self.data {
+            self.data = match self.all() {
+                VectorSlice::F32(data) => VectorData::F32(data.to_vec()),
+                VectorSlice::F16(data) => VectorData::F16(data.to_vec()),
+                VectorSlice::Bf16(data) => VectorData::Bf16(data.to_vec()),
+            };
+        }
+        match &mut self.data {
+            VectorData::F32(data) => data.extend_from_slice(vector),
+            VectorData::F16(data) => data.extend(vector.iter().map(|&x| f16::from_f32(x))),
+            VectorData::Bf16(data) => data.extend(vector.iter().map(|&x| bf16::from_f32(x))),
+            VectorData::Mapped { .. } => unreachable!("copied above"),
+        }
+    }
+}
+
+/// Stored vectors, or one row of them, in the store's precision.
+#[derive(Clone, Copy, PartialEq)]
+pub(crate) enum VectorSlice<'a> {
+    F32(&'a [f32]),
+    F16(&'a [f16]),
+    Bf16(&'a [bf16]),
+}
+
+impl<'a> VectorSlice<'a> {
+    pub(crate) fn len(self) -> usize {
+        match self {
+            VectorSlice::F32(data) => data.len(),
+            VectorSlice::F16(data) => data.len(),
+            VectorSlice::Bf16(data) => data.len(),
+        }
+    }
+
+    #[inline]
+    fn range(self, range: Range<usize>) -> Self {
+        match self {
+            VectorSlice::F32(data) => VectorSlice::F32(&data[range]),
+            VectorSlice::F16(data) => VectorSlice::F16(&data[range]),
+            VectorSlice::Bf16(data) => VectorSlice::Bf16(&data[range]),
+        }
+    }
+
+    /// Widened to f32.
+    pub(crate) fn to_vec(self) -> Vec<f32> {
+        match self {
+            VectorSlice::F32(data) => data.to_vec(),
+            VectorSlice::F16(data) => data.to_f32_vec(),
+            VectorSlice::Bf16(data) => data.to_f32_vec(),
+        }
+    }
+
+    /// `metric.score` of `query` against these components. With `unit`,
+    /// cosine takes both to be unit length and is a dot product.
+    #[inline]
+    pub(crate) fn score(self, metric: DistanceMetric, unit: bool, query: &[f32]) -> f32 {
+        match self {
+            VectorSlice::F32(data) if !unit => metric.score(query, data),
+            VectorSlice::F32(data) => metric.score_prepared(query, data),
+            VectorSlice::F16(data) => metric.score_half(query, data),
+            VectorSlice::Bf16(data) => metric.score_half(query, data),
+        }
+    }
+
+    /// `metric.distance` of `query` to these components, with `unit` as for
+    /// `score`. Half-precision rows are never scaled to unit length.
+    #[inline]
+    pub(crate) fn distance(self, metric: DistanceMetric, unit: bool, query: &[f32]) -> f32 {
+        match self {
+            VectorSlice::F32(data) if !unit => metric.distance(query, data),
+            VectorSlice::F32(data) => metric.distance_prepared(query, data),
+            VectorSlice::F16(data) => metric.distance_half(query, data),
+            VectorSlice::Bf16(data) => metric.distance_half(query, data),
+        }
+    }
+
+    /// `metric.distance` between two stored rows.
+    pub(crate) fn distance_to(
+        self,
+        metric: DistanceMetric,
+        unit: bool,
+        other: VectorSlice<'_>,
+    ) -> f32 {
+        match self {
+            VectorSlice::F32(data) => other.distance(metric, unit, data),
+            _ => other.distance(metric, unit, &self.to_vec()),
+        }
+    }
+}
+
+/// Bring `vector` to the form a store built with `options` holds: unit
+/// length if `unit_vectors`, then rounded to the stored precision. Vectors
+/// read back from such a store are left unchanged, so rebuilds reproduce
+/// them.
+pub(crate) fn prepare(vector: &mut [f32], options: &BuildOptions) {
+    if options.unit_vectors() {
+        math::normalize(vector, UNIT_TOLERANCE);
+    }
+    round(vector, options.stored_precision());
+}
+
+/// Squared norms within this of 1 count as unit length.
+const UNIT_TOLERANCE: f32 = 1e-4;
+
+fn round(vector: &mut [f32], precision: VectorPrecision) {
+    match precision {
+        VectorPrecision::F32 => {}
+        VectorPrecision::F16 => {
+            for x in vector {
+                *x = f16::from_f32(*x).to_f32();
+            }
+        }
+        VectorPrecision::Bf16 => {
+            for x in vector {
+                *x = bf16::from_f32(*x).to_f32();
+            }
+        }
+    }
+}
+
+/// View little-endian bytes as components of `precision` in place.
+fn view(bytes: &[u8], precision: VectorPrecision) -> Option<VectorSlice<'_>> {
+    // SAFETY: every bit pattern is a valid f32, f16 and bf16, and align_to
+    // only yields the correctly aligned middle part.
+    unsafe {
+        match precision {
+            VectorPrecision::F32 => whole(bytes.align_to::<f32>()).map(VectorSlice::F32),
+            VectorPrecision::F16 => whole(bytes.align_to::<f16>()).map(VectorSlice::F16),
+            VectorPrecision::Bf16 => whole(bytes.align_to::<bf16>()).map(VectorSlice::Bf16),
+        }
+    }
+}
+
+fn whole<'a, T>((prefix, items, suffix): (&[u8], &'a [T], &[u8])) -> Option<&'a [T]> {
+    (prefix.is_empty() && suffix.is_empty()).then_some(items)
+}
+
+pub(crate) struct StoredChunk {
+    pub(crate) id: String,
+    pub(crate) metadata: ChunkMetadata,
+    text: Text,
+}
+
+enum Text {
+    Owned(String),
+    /// Byte range of the text within the map; UTF-8 is checked on read.
+    Mapped(Range<usize>),
+}
+
+impl StoredChunk {
+    pub(crate) fn owned(id: String, metadata: ChunkMetadata, text: String) -> Self {
+        Self {
+            id,
+            metadata,
+            text: Text::Owned(text),
+        }
+    }
+
+    pub(crate) fn mapped(id: String, metadata: ChunkMetadata, text: Range<usize>) -> Self {
+        Self {
+            id,
+            metadata,
+            text: Text::Mapped(text),
+        }
+    }
+}
+
+pub(crate) struct ChunkStore {
+    chunks: Vec<StoredChunk>,
+    map: Option<Arc<Mmap>>,
+}
+
+impl ChunkStore {
+    pub(crate) fn from_chunks(chunks: impl IntoIterator<Item = Chunk>) -> Self {
+        Self::from_stored(
+            chunks
+                .into_iter()
+                .map(|chunk| StoredChunk::owned(chunk.id, chunk.metadata, chunk.text))
+                .collect(),
+        )
+    }
+
+    pub(crate) fn from_stored(chunks: Vec<StoredChunk>) -> Self {
+        Self { chunks, map: None }
+    }
+
+    pub(crate) fn mapped(chunks: Vec<StoredChunk>, map: Arc<Mmap>) -> Self {
+        Self {
+            chunks,
+            map: Some(map),
+        }
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.chunks.len()
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.chunks.is_empty()
+    }
+
+    pub(crate) fn iter(&self) -> impl Iterator<Item = &StoredChunk> {
+        self.chunks.iter()
+    }
+
+    pub(crate) fn push(&mut self, chunk: Chunk) {
+        self.chunks
+            .push(StoredChunk::owned(chunk.id, chunk.metadata, chunk.text));
+    }
+
+    pub(crate) fn id(&self, i: usize) -> &str {
+        &self.chunks[i].id
+    }
+
+    pub(crate) fn metadata(&self, i: usize) -> &ChunkMetadata {
+        &self.chunks[i].metadata
+    }
+
+    pub(crate) fn text(&self, i: usize) -> Result<&str, VPackError> {
+        match &self.chunks[i].text {
+            Text::Owned(text) => Ok(text),
+            Text::Mapped(range) => {
+                let map = self.map.as_ref().expect("mapped text without a map");
+                std::str::from_utf8(&map[range.clone()]).map_err(|err| {
+                    VPackError::InvalidFormat(format!("chunk text is not UTF-8: {err}"))
+                })
+            }
+        }
+    }
+
+    /// Materialize chunk `i`, decoding its text.
+    pub(crate) fn to_chunk(&self, i: usize) -> Result<Chunk, VPackError> {
+        let stored = &self.chunks[i];
+        Ok(Chunk {
+            id: stored.id.clone(),
+            text: self.text(i)?.to_string(),
+            metadata: stored.metadata.clone(),
+        })
+    }
+}
+
+/// Split embedded chunks into the two stores.
+pub(crate) fn split(
+    chunks: Vec<EmbeddedChunk>,
+    dimensions: usize,
+    precision: VectorPrecision,
+) -> (ChunkStore, VectorStore) {
+    let mut vectors = Vec::with_capacity(chunks.len() * dimensions);
+    let chunks = chunks.into_iter().map(|embedded| {
+        vectors.extend_from_slice(&embedded.vector);
+        embedded.chunk
+    });
+    let store = ChunkStore::from_chunks(chunks);
+    (
+        store,
+        VectorStore::from_flat(vectors, dimensions, precision),
+    )
+}
+
+/// Deleted rows, waiting for compaction.
+#[derive(Default)]
+pub(crate) struct Tombstones {
+    deleted: Vec<bool>,
+    count: usize,
+}
+
+impl Tombstones {
+    #[inline]
+    pub(crate) fn contains(&self, row: usize) -> bool {
+        self.deleted.get(row).copied().unwrap_or(false)
+    }
+
+    /// Mark `row` deleted. False if it already was.
+    pub(crate) fn insert(&mut self, row: usize) -> bool {
+        if self.contains(row) {
+            return false;
+        }
+        if self.deleted.len() <= row {
+            self.deleted.resize(row + 1, false);
+        }
+        self.deleted[row] = true;
+        self.count += 1;
+        true
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.count
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    /// Deleted rows, ascending.
+    pub(crate) fn rows(&self) -> impl Iterator<Item = usize> + '_ {
+        self.deleted
+            .iter()
+            .enumerate()
+            .filter(|(_, &deleted)| deleted)
+            .map(|(row, _)| row)
+    }
+}
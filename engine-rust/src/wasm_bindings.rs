@@ -0,0 +1,81 @@
+// wasm_bindings.rs — wasm-bindgen bindings for browsers and edge runtimes
+//
+// Read-only: a pack is deserialized from a Uint8Array and queried with a
+// Float32Array. There is no embedder in wasm builds, so text queries are
+// embedded by the caller. Errors are thrown as "CODE|message", like the napi
+// addon, so @vpack/engine maps them to VPackError the same way.
+
+use serde::Serialize;
+use wasm_bindgen::prelude::*;
+
+use crate::error::VPackError;
+use crate::index::VPackIndex;
+use crate::query::QueryOptions;
+use crate::serialize::{deserialize_with_options, LoadOptions};
+
+fn js_error_from_vpack(err: VPackError) -> JsError {
+    JsError::new(&format!("{}|{}", err.code(), err))
+}
+
+fn js_error_from_serde(err: serde_wasm_bindgen::Error) -> JsError {
+    JsError::new(&format!("DESERIALIZE_FAILED|invalid value: {err}"))
+}
+
+/// Plain JS objects rather than `Map`s for JSON objects.
+fn to_js<T: Serialize>(value: &T) -> Result<JsValue, JsError> {
+    value
+        .serialize(&serde_wasm_bindgen::Serializer::json_compatible())
+        .map_err(js_error_from_serde)
+}
+
+#[wasm_bindgen]
+pub struct WasmIndex {
+    inner: VPackIndex,
+}
+
+/// `options` is a LoadOptions object, or undefined for none.
+#[wasm_bindgen(js_name = deserializeIndex)]
+pub fn deserialize_index(bytes: &[u8], options: JsValue) -> Result<WasmIndex, JsError> {
+    let options = if options.is_undefined() || options.is_null() {
+        LoadOptions::default()
+    } else {
+        serde_wasm_bindgen::from_value::<LoadOptions>(options).map_err(js_error_from_serde)?
+    };
+    let index = deserialize_with_options(bytes, &options).map_err(js_error_from_vpack)?;
+    Ok(WasmIndex { inner: index })
+}
+
+/// `options` is a QueryOptions object, or undefined for the defaults.
+/// Returns an array of QueryResult objects.
+#[wasm_bindgen(js_name = queryIndex)]
+pub fn query_index(
+    index: &WasmIndex,
+    vector: &[f32],
+    options: JsValue,
+) -> Result<JsValue, JsError> {
+    let options = if options.is_undefined() || options.is_null() {
+        QueryOptions::default()
+    } else {
+        serde_wasm_bindgen::from_value::<QueryOptions>(options).map_err(js_error_from_serde)?
+    };
+    let results = index
+        .inner
+        .query(vector, options)
+        .map_err(js_error_from_vpack)?;
+    to_js(&results)
+}
+
+#[wasm_bindgen]
+pub fn manifest(index: &WasmIndex) -> Result<JsValue, JsError> {
+    to_js(index.inner.manifest())
+}
+
+#[wasm_bindgen(js_name = chunkCount)]
+pub fn chunk_count(index: &WasmIndex) -> u32 {
+    index.inner.chunk_count() as u32
+}
+
+#[wasm_bindgen]
+pub fn dimensions(index: &WasmIndex) -> u32 {
+    index.inner.dimensions() as u32
+}
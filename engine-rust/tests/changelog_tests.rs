@@ -0,0 +1,216 @@
+mod common;
+
+use common::{make_changelog, make_chunks, make_manifest};
+use serde_json::json;
+use vpack_engine::serialize::FLAG_HAS_CHANGELOG;
+use vpack_engine::{History, QueryOptions, SectionId, VPackIndex};
+
+const DIMENSIONS: usize = 3;
+
+fn ids(index: &VPackIndex, history: History, filter: Option<serde_json::Value>) -> Vec<String> {
+    let options = QueryOptions {
+        history,
+        filter: filter.map(|filter| serde_json::from_value(filter).unwrap()),
+        ..QueryOptions::default()
+    };
+    index
+        .query(&[1.0, 0.0, 0.0], options)
+        .unwrap()
+        .into_iter()
+        .map(|r| r.chunk.id)
+        .collect()
+}
+
+/// Two content chunks, and changelog chunks around and between them.
+fn build() -> VPackIndex {
+    let mut chunks = make_chunks(vec![vec![0.9, 0.1, 0.0], vec![0.0, 1.0, 0.0]]);
+    chunks.push(make_changelog(
+        "1.1.0",
+        "2026-03-01T06:00:00Z",
+        None,
+        vec![1.0, 0.0, 0.0],
+    ));
+    chunks.push(make_changelog(
+        "1.0.0",
+        "2026-02-01T06:00:00Z",
+        None,
+        vec![0.5, 0.5, 0.0],
+    ));
+    VPackIndex::build(chunks, make_manifest(DIMENSIONS)).unwrap()
+}
+
+#[test]
+fn changelog_chunks_live_in_their_own_section() {
+    let index = build();
+    assert_eq!(index.chunk_count(), 2);
+    let history: Vec<&str> = index
+        .changelog()
+        .iter()
+        .map(|c| c.chunk.id.as_str())
+        .collect();
+    assert_eq!(history, ["changelog-1.0.0", "changelog-1.1.0"]);
+
+    let bytes = vpack_engine::serialize(&index).unwrap();
+    let header = vpack_engine::read_header(&bytes).unwrap();
+    // RFC-0004 §3: section 0x06, flag bit 0.
+    assert_eq!(header.flags & 0x0001, FLAG_HAS_CHANGELOG);
+    assert!(header.sections.iter().any(|entry| entry.id == 0x06));
+    assert!(vpack_engine::read_section(&bytes, SectionId::Changelog)
+        .unwrap()
+        .is_some());
+    assert_eq!(
+        vpack_engine::read_vectors(&bytes).unwrap().len(),
+        2 * DIMENSIONS
+    );
+
+    let loaded = vpack_engine::deserialize(&bytes).unwrap();
+    assert_eq!(loaded.changelog(), index.changelog());
+    assert_eq!(vpack_engine::serialize(&loaded).unwrap(), bytes);
+
+    let plain = VPackIndex::build(
+        make_chunks(vec![vec![1.0, 0.0, 0.0]]),
+        make_manifest(DIMENSIONS),
+    )
+    .unwrap();
+    let bytes = vpack_engine::serialize(&plain).unwrap();
+    let flags = vpack_engine::read_header(&bytes).unwrap().flags;
+    assert_eq!(flags & FLAG_HAS_CHANGELOG, 0);
+}
+
+#[test]
+fn history_option_includes_excludes_or_only_searches_changelog() {
+    let index = build();
+    assert_eq!(
+        ids(&index, History::Include, None),
+        [
+            "changelog-1.1.0",
+            "chunk-00000",
+            "changelog-1.0.0",
+            "chunk-00001"
+        ]
+    );
+    assert_eq!(
+        ids(&index, History::Exclude, None),
+        ["chunk-00000", "chunk-00001"]
+    );
+    assert_eq!(
+        ids(&index, History::Only, None),
+        ["changelog-1.1.0", "changelog-1.0.0"]
+    );
+
+    // The filter applies to changelog chunks too.
+    let filter = json!({ "field": "pack_version", "op": "eq", "value": "1.0.0" });
+    assert_eq!(
+        ids(&index, History::Include, Some(filter)),
+        ["changelog-1.0.0"]
+    );
+
+    let options: QueryOptions =
+        serde_json::from_value(json!({ "history": "only", "topK": 1 })).unwrap();
+    let results = index.query(&[1.0, 0.0, 0.0], options).unwrap();
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0].chunk.id, "changelog-1.1.0");
+    assert_eq!(results[0].rank, 0);
+}
+
+#[test]
+fn expired_changelog_chunks_are_hidden_at_load() {
+    let mut chunks = make_chunks(vec![vec![0.0, 1.0, 0.0]]);
+    chunks.push(make_changelog(
+        "1.0.0",
+        "2020-01-01T00:00:00Z",
+        Some(30),
+        vec![1.0, 0.0, 0.0],
+    ));
+    chunks.push(make_changelog(
+        "1.1.0",
+        "2020-01-02",
+        None,
+        vec![0.9, 0.1, 0.0],
+    ));
+    let index = VPackIndex::build(chunks, make_manifest(DIMENSIONS)).unwrap();
+    assert_eq!(index.changelog().len(), 2);
+
+    let bytes = vpack_engine::serialize(&index).unwrap();
+    let mut loaded = vpack_engine::deserialize(&bytes).unwrap();
+    assert_eq!(ids(&loaded, History::Only, None), ["changelog-1.1.0"]);
+    // Still in the section: the pack re-serializes unchanged.
+    assert_eq!(vpack_engine::serialize(&loaded).unwrap(), bytes);
+
+    // 2020-01-31T00:00:00Z is the last moment before the TTL runs out.
+    loaded.expire_changelog(1_580_428_800_000);
+    assert_eq!(loaded.changelog().len(), 2);
+    loaded.expire_changelog(1_580_428_800_001);
+    assert_eq!(loaded.changelog().len(), 1);
+}
+
+#[test]
+fn mutations_route_changelog_chunks() {
+    let mut index = build();
+    let entry = make_changelog("1.2.0", "2026-04-01T06:00:00Z", None, vec![0.0, 0.0, 1.0]);
+    index.insert(vec![entry.clone()]).unwrap();
+    assert_eq!(index.chunk_count(), 2);
+    assert_eq!(index.changelog().len(), 3);
+    assert!(index.insert(vec![entry.clone()]).is_err());
+
+    let mut revised = entry;
+    revised.chunk.text = "Revised summary".to_string();
+    index.upsert(vec![revised]).unwrap();
+    assert_eq!(index.changelog()[2].chunk.text, "Revised summary");
+
+    assert_eq!(index.delete(&["changelog-1.0.0", "chunk-00001"]), 2);
+    assert_eq!(index.chunk_count(), 1);
+    assert_eq!(index.changelog().len(), 2);
+
+    let loaded = vpack_engine::deserialize(&vpack_engine::serialize(&index).unwrap()).unwrap();
+    assert_eq!(loaded.changelog(), index.changelog());
+    assert_eq!(loaded.chunk_count(), 1);
+}
+
+#[test]
+fn temporal_weight_down_weights_changelog_chunks() {
+    let index = build();
+    let options: QueryOptions = serde_json::from_value(json!({ "temporalWeight": 0.3 })).unwrap();
+    let results = index.query(&[1.0, 0.0, 0.0], options).unwrap();
+    let ids: Vec<&str> = results.iter().map(|r| r.chunk.id.as_str()).collect();
+    assert_eq!(
+        ids,
+        [
+            "chunk-00000",
+            "changelog-1.1.0",
+            "changelog-1.0.0",
+            "chunk-00001"
+        ]
+    );
+    assert!((results[1].similarity - 1.0).abs() < 1e-6);
+    assert!((results[1].score - 0.7).abs() < 1e-6);
+    assert_eq!(results[0].score, results[0].similarity);
+}
+
+#[test]
+fn full_temporal_weight_keeps_negative_scores_finite() {
+    let mut chunks = make_chunks(vec![vec![1.0, 0.0, 0.0]]);
+    chunks.push(make_changelog(
+        "1.0.0",
+        "2026-02-01T06:00:00Z",
+        None,
+        vec![-1.0, 0.0, 0.0],
+    ));
+    let index = VPackIndex::build(chunks, make_manifest(DIMENSIONS)).unwrap();
+
+    let options: QueryOptions = serde_json::from_value(json!({ "temporalWeight": 1.0 })).unwrap();
+    let results = index.query(&[1.0, 0.0, 0.0], options).unwrap();
+    assert_eq!(results[1].chunk.id, "changelog-1.0.0");
+    assert!((results[1].similarity + 1.0).abs() < 1e-6);
+    assert!((results[1].score + 2.0).abs() < 1e-6);
+
+    let options: QueryOptions =
+        serde_json::from_value(json!({ "temporalWeight": 1.0, "minScore": -1.5 })).unwrap();
+    let ids: Vec<String> = index
+        .query(&[1.0, 0.0, 0.0], options)
+        .unwrap()
+        .into_iter()
+        .map(|r| r.chunk.id)
+        .collect();
+    assert_eq!(ids, ["chunk-00000"]);
+}
@@ -0,0 +1,119 @@
+#![allow(dead_code)]
+
+use serde_json::json;
+use std::collections::HashMap;
+use vpack_engine::{Chunk, ChunkMetadata, EmbeddedChunk, QueryOptions, VPackIndex};
+
+pub fn make_manifest(dimensions: usize) -> serde_json::Value {
+    json!({
+        "vpack": "1.0",
+        "name": "@test/fixture",
+        "version": "1.0.0",
+        "plugins": [
+            { "kind": "source", "use": "@vpack/source-fs", "path": "./docs" },
+            { "kind": "chunker", "use": "@vpack/chunker-fixed", "size": 512, "overlap": 64, "min_size": 1 },
+            { "kind": "embedder", "use": "@vpack/embedder-xenova", "model": "Xenova/all-MiniLM-L6-v2", "dimensions": dimensions, "provider": "huggingface" }
+        ]
+    })
+}
+
+/// Deterministic pseudo-random vectors (xorshift), clustered so the
+/// neighborhood structure resembles real embeddings.
+pub fn random_vectors(count: usize, dimensions: usize, seed: u64) -> Vec<Vec<f32>> {
+    let mut state = seed.max(1);
+    let mut next = move || {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        (state >> 40) as f32 / (1u64 << 24) as f32 - 0.5
+    };
+    let centers: Vec<Vec<f32>> = (0..16)
+        .map(|_| (0..dimensions).map(|_| next() * 4.0).collect())
+        .collect();
+    (0..count)
+        .map(|i| {
+            centers[i % centers.len()]
+                .iter()
+                .map(|c| c + next())
+                .collect()
+        })
+        .collect()
+}
+
+pub fn make_chunks(vectors: Vec<Vec<f32>>) -> Vec<EmbeddedChunk> {
+    vectors
+        .into_iter()
+        .enumerate()
+        .map(|(i, vector)| EmbeddedChunk {
+            chunk: Chunk {
+                id: format!("chunk-{i:05}"),
+                text: format!("text {i}"),
+                metadata: ChunkMetadata {
+                    source_plugin: "@vpack/source-fs".to_string(),
+                    source_id: format!("doc-{}", i / 10),
+                    source_url: None,
+                    created_at: None,
+                    updated_at: None,
+                    pack_name: "@test/fixture".to_string(),
+                    chunker_plugin: "@vpack/chunker-fixed".to_string(),
+                    extra: HashMap::new(),
+                },
+            },
+            vector,
+        })
+        .collect()
+}
+
+/// An RFC-0004 changelog chunk for `version`, built at `built_at`.
+pub fn make_changelog(
+    version: &str,
+    built_at: &str,
+    ttl_days: Option<u64>,
+    vector: Vec<f32>,
+) -> EmbeddedChunk {
+    let mut chunk = make_chunks(vec![vector]).remove(0);
+    chunk.chunk.id = format!("changelog-{version}");
+    chunk.chunk.text = format!("What changed in {version}");
+    chunk.chunk.metadata.source_plugin = "@vpack/source-self".to_string();
+    chunk.chunk.metadata.source_id = "changelog".to_string();
+    let extra = &mut chunk.chunk.metadata.extra;
+    extra.insert("chunk_type".to_string(), json!("changelog"));
+    extra.insert("pack_version".to_string(), json!(version));
+    extra.insert("built_at".to_string(), json!(built_at));
+    extra.insert("style".to_string(), json!("daily"));
+    if let Some(days) = ttl_days {
+        extra.insert("ttl_days".to_string(), json!(days));
+    }
+    chunk
+}
+
+pub fn top_ids(index: &VPackIndex, query: &[f32], top_k: usize) -> Vec<String> {
+    let options = QueryOptions {
+        top_k,
+        ..QueryOptions::default()
+    };
+    index
+        .query(query, options)
+        .unwrap()
+        .into_iter()
+        .map(|r| r.chunk.id)
+        .collect()
+}
+
+/// Fraction of `exact`'s top-k that `approx` also returns.
+pub fn recall_at(
+    exact: &VPackIndex,
+    approx: &VPackIndex,
+    queries: &[Vec<f32>],
+    top_k: usize,
+) -> f32 {
+    let mut hits = 0;
+    for query in queries {
+        let truth: Vec<String> = top_ids(exact, query, top_k);
+        hits += top_ids(approx, query, top_k)
+            .into_iter()
+            .filter(|id| truth.contains(id))
+            .count();
+    }
+    hits as f32 / (queries.len() * top_k) as f32
+}
@@ -1,5 +1,8 @@
+// The original tests set QueryOptions fields one at a time.
+#![allow(clippy::field_reassign_with_default)]
+
 use serde_json::json;
-use vpack_engine::{Chunk, ChunkMetadata, EmbeddedChunk, QueryOptions, VPackIndex};
+use vpack_engine::{Chunk, ChunkMetadata, EmbeddedChunk, LoadOptions, QueryOptions, VPackError, VPackIndex};
 use std::collections::HashMap;
 
 fn make_manifest(dimensions: usize) -> serde_json::Value {
@@ -59,6 +62,241 @@ fn min_score_filters_results() {
     assert_eq!(results.len(), 1);
 }
 
+#[test]
+fn recency_decay_ranks_newer_chunks_higher() {
+    let dated = |id: &str, vector: Vec<f32>, updated_at: Option<&str>| {
+        let mut chunk = make_chunk(id, vector, id);
+        chunk.chunk.metadata.updated_at = updated_at.map(str::to_string);
+        chunk
+    };
+    let chunks = vec![
+        dated("old", vec![1.0, 0.0, 0.0], Some("2025-01-01T00:00:00Z")),
+        dated("new", vec![0.8, 0.6, 0.0], Some("2026-01-01T00:00:00Z")),
+        dated("undated", vec![0.0, 1.0, 0.0], None),
+    ];
+    let index = VPackIndex::build(chunks, make_manifest(3)).unwrap();
+    let query = |options: serde_json::Value| {
+        let options: QueryOptions = serde_json::from_value(options).unwrap();
+        index.query(&[1.0, 0.0, 0.0], options).unwrap()
+    };
+
+    let plain = query(json!({}));
+    assert_eq!(plain[0].chunk.id, "old");
+    assert!(plain.iter().all(|r| r.score == r.similarity));
+
+    // One year old at a 180-day half-life: about a quarter of its similarity.
+    let decayed = query(json!({ "recency": { "halfLifeDays": 180, "now": "2026-01-01" } }));
+    let ids: Vec<&str> = decayed.iter().map(|r| r.chunk.id.as_str()).collect();
+    assert_eq!(ids, ["new", "old", "undated"]);
+    assert!((decayed[0].score - 0.8).abs() < 1e-6);
+    assert!((decayed[1].similarity - 1.0).abs() < 1e-6);
+    assert!((decayed[1].score - 0.5f32.powf(365.0 / 180.0)).abs() < 1e-4);
+    assert_eq!(decayed[2].score, decayed[2].similarity);
+
+    // min_score applies to the adjusted score.
+    let kept = query(json!({
+        "recency": { "halfLifeDays": 180, "now": "2026-01-01" },
+        "minScore": 0.5
+    }));
+    assert_eq!(kept.len(), 1);
+
+    let invalid = |options: serde_json::Value| {
+        let options: QueryOptions = serde_json::from_value(options).unwrap();
+        matches!(
+            index.query(&[1.0, 0.0, 0.0], options),
+            Err(VPackError::InvalidQuery(_))
+        )
+    };
+    assert!(invalid(json!({ "recency": { "halfLifeDays": 0 } })));
+    assert!(invalid(json!({ "recency": { "halfLifeDays": 30, "now": "soon" } })));
+    assert!(invalid(json!({ "temporalWeight": 1.5 })));
+}
+
+#[test]
+fn recency_decay_keeps_negative_scores_finite() {
+    let mut chunk = make_chunk("ancient", vec![-1.0, 0.0, 0.0], "ancient");
+    chunk.chunk.metadata.updated_at = Some("1900-01-01T00:00:00Z".to_string());
+    let index = VPackIndex::build(vec![chunk], make_manifest(3)).unwrap();
+
+    // The decay factor underflows to zero at this age.
+    let options: QueryOptions = serde_json::from_value(json!({
+        "recency": { "halfLifeDays": 0.001, "now": "2026-01-01" }
+    }))
+    .unwrap();
+    let results = index.query(&[1.0, 0.0, 0.0], options).unwrap();
+    assert!((results[0].similarity + 1.0).abs() < 1e-6);
+    assert!((results[0].score + 2.0).abs() < 1e-6);
+
+    let options: QueryOptions = serde_json::from_value(json!({
+        "recency": { "halfLifeDays": 0.001, "now": "2026-01-01" },
+        "minScore": -2.5
+    }))
+    .unwrap();
+    assert_eq!(index.query(&[1.0, 0.0, 0.0], options).unwrap().len(), 1);
+}
+
+#[test]
+fn euclidean_metric_scores_by_distance() {
+    let mut manifest = make_manifest(3);
+    manifest["index"] = json!({ "type": "flat", "metric": "euclidean" });
+    let chunks = vec![
+        make_chunk("near", vec![1.0, 0.0, 0.0], "near"),
+        make_chunk("far", vec![4.0, 5.0, 0.0], "far"),
+    ];
+    let index = VPackIndex::build(chunks, manifest).unwrap();
+
+    let results = index.query(&[1.0, 1.0, 0.0], QueryOptions::default()).unwrap();
+    assert_eq!(results[0].chunk.id, "near");
+    // score = 1 / (1 + distance): distance 1 -> 0.5, distance 5 -> 1/6
+    assert!((results[0].score - 0.5).abs() < 1e-6);
+    assert!((results[1].score - 1.0 / 6.0).abs() < 1e-6);
+
+    // min_score 0.25 keeps results within distance 3.
+    let options = QueryOptions {
+        min_score: Some(0.25),
+        ..QueryOptions::default()
+    };
+    let results = index.query(&[1.0, 1.0, 0.0], options).unwrap();
+    assert_eq!(results.len(), 1);
+}
+
+#[test]
+fn dot_metric_ranks_by_magnitude() {
+    let mut manifest = make_manifest(2);
+    manifest["index"] = json!({ "metric": "dot" });
+    let chunks = vec![
+        make_chunk("short", vec![1.0, 0.0], "short"),
+        make_chunk("long", vec![3.0, 3.0], "long"),
+    ];
+    let index = VPackIndex::build(chunks, manifest).unwrap();
+
+    let results = index.query(&[1.0, 0.0], QueryOptions::default()).unwrap();
+    assert_eq!(results[0].chunk.id, "long");
+    assert!((results[0].score - 3.0).abs() < 1e-6);
+
+    let bytes = vpack_engine::serialize(&index).unwrap();
+    let restored = vpack_engine::deserialize(&bytes).unwrap();
+    assert_eq!(restored.options().metric, vpack_engine::DistanceMetric::Dot);
+    let results = restored.query(&[1.0, 0.0], QueryOptions::default()).unwrap();
+    assert_eq!(results[0].chunk.id, "long");
+}
+
+#[test]
+fn cosine_packs_return_unit_length_vectors() {
+    let chunks = vec![
+        make_chunk("pricing", vec![3.0, 4.0, 0.0], "Pricing should reflect value delivered"),
+        make_chunk("deployment", vec![0.0, 0.0, 2.0], "Deploy using blue-green strategy"),
+    ];
+    let old = VPackIndex::build(chunks[..1].to_vec(), make_manifest(3)).unwrap();
+    let index = VPackIndex::build(chunks.clone(), make_manifest(3)).unwrap();
+    let options = QueryOptions {
+        include_vectors: true,
+        ..QueryOptions::default()
+    };
+
+    // Stored, returned and diffed vectors are unit length, not the build input.
+    let bytes = vpack_engine::serialize(&index).unwrap();
+    let restored = vpack_engine::deserialize(&bytes).unwrap();
+    for index in [&index, &restored] {
+        let results = index.query(&[1.0, 0.0, 0.0], options.clone()).unwrap();
+        assert_eq!(results[0].vector.as_deref(), Some(&[0.6, 0.8, 0.0][..]));
+        assert_eq!(results[1].vector.as_deref(), Some(&[0.0, 0.0, 1.0][..]));
+    }
+    let diff = vpack_engine::diff(&old, &restored).unwrap();
+    assert_eq!(diff.added[0].vector, [0.0, 0.0, 1.0]);
+
+    // Other metrics keep the vectors as built.
+    let mut manifest = make_manifest(3);
+    manifest["index"] = json!({ "metric": "euclidean" });
+    let index = VPackIndex::build(chunks, manifest).unwrap();
+    let results = index.query(&[3.0, 4.0, 0.0], options).unwrap();
+    assert_eq!(results[0].vector.as_deref(), Some(&[3.0, 4.0, 0.0][..]));
+}
+
+#[test]
+fn naming_another_model_is_a_model_mismatch() {
+    let index = VPackIndex::build(chunks_3d(), make_manifest(3)).unwrap();
+    assert_eq!(index.model(), Some("Xenova/all-MiniLM-L6-v2"));
+
+    let options = || QueryOptions {
+        model: Some("BAAI/bge-small-en-v1.5".to_string()),
+        ..QueryOptions::default()
+    };
+    let result = index.query(&[1.0, 0.0, 0.0], options());
+    assert!(matches!(result, Err(VPackError::ModelMismatch { .. })));
+    // Checked before embedding, so no model is loaded.
+    #[cfg(feature = "embed")]
+    {
+        let result = index.query_text("pricing", options());
+        assert!(matches!(result, Err(VPackError::ModelMismatch { .. })));
+    }
+
+    let options = QueryOptions {
+        model: Some("Xenova/all-MiniLM-L6-v2".to_string()),
+        ..QueryOptions::default()
+    };
+    let results = index.query(&[1.0, 0.0, 0.0], options).unwrap();
+    assert_eq!(results[0].chunk.id, "pricing");
+}
+
+#[test]
+fn naming_other_weights_is_a_model_hash_mismatch() {
+    let mut manifest = make_manifest(3);
+    manifest["plugins"][2]["model_hash"] = json!("sha256:3e4b");
+    let index = VPackIndex::build(chunks_3d(), manifest).unwrap();
+    assert_eq!(index.model_hash(), Some("sha256:3e4b"));
+
+    let options = QueryOptions {
+        model_hash: Some("sha256:ffff".to_string()),
+        ..QueryOptions::default()
+    };
+    let result = index.query(&[1.0, 0.0, 0.0], options);
+    assert!(matches!(result, Err(VPackError::ModelHashMismatch { .. })));
+
+    let options = QueryOptions {
+        model_hash: Some("3E4B".to_string()),
+        ..QueryOptions::default()
+    };
+    assert!(index.query(&[1.0, 0.0, 0.0], options).is_ok());
+}
+
+#[test]
+fn loading_with_another_model_fails() {
+    let mut manifest = make_manifest(3);
+    manifest["plugins"][2]["model_hash"] = json!("sha256:3e4b");
+    let index = VPackIndex::build(chunks_3d(), manifest).unwrap();
+    let bytes = vpack_engine::serialize(&index).unwrap();
+    let path = std::env::temp_dir().join(format!("vpack-load-{}.vpack", std::process::id()));
+    std::fs::write(&path, &bytes).unwrap();
+
+    let other_model = LoadOptions {
+        model: Some("BAAI/bge-small-en-v1.5".to_string()),
+        ..LoadOptions::default()
+    };
+    let result = vpack_engine::deserialize_with_options(&bytes, &other_model);
+    assert!(matches!(result, Err(VPackError::ModelMismatch { .. })));
+    let result = VPackIndex::open_mmap_with_options(&path, &other_model);
+    assert!(matches!(result, Err(VPackError::ModelMismatch { .. })));
+
+    let other_weights = LoadOptions {
+        model_hash: Some("sha256:ffff".to_string()),
+        ..LoadOptions::default()
+    };
+    let result = vpack_engine::deserialize_with_options(&bytes, &other_weights);
+    assert!(matches!(result, Err(VPackError::ModelHashMismatch { .. })));
+    let result = VPackIndex::open_mmap_with_options(&path, &other_weights);
+    assert!(matches!(result, Err(VPackError::ModelHashMismatch { .. })));
+
+    let same = LoadOptions {
+        model: Some("Xenova/all-MiniLM-L6-v2".to_string()),
+        model_hash: Some("sha256:3e4b".to_string()),
+    };
+    assert!(vpack_engine::deserialize_with_options(&bytes, &same).is_ok());
+    assert!(VPackIndex::open_mmap_with_options(&path, &same).is_ok());
+
+    std::fs::remove_file(&path).unwrap();
+}
+
 #[test]
 fn filter_ops_match() {
     let mut finance = HashMap::new();
@@ -124,6 +362,105 @@ fn filter_ops_match() {
     assert_eq!(results.len(), 1);
 }
 
+#[test]
+fn boolean_filter_expressions() {
+    let tagged = |id: &str, vector: Vec<f32>, chunk_type: &str, year: i64| {
+        let mut chunk = make_chunk(id, vector, id);
+        chunk.chunk.metadata.extra.insert("chunk_type".to_string(), json!(chunk_type));
+        chunk.chunk.metadata.extra.insert("year".to_string(), json!(year));
+        chunk
+    };
+    let chunks = vec![
+        tagged("guide-old", vec![1.0, 0.0, 0.0], "guide", 2023),
+        tagged("guide-new", vec![0.9, 0.1, 0.0], "guide", 2025),
+        tagged("changelog-new", vec![0.8, 0.2, 0.0], "changelog", 2025),
+    ];
+    let index = VPackIndex::build(chunks, make_manifest(3)).unwrap();
+    let ids = |filter: serde_json::Value| -> Vec<String> {
+        let options = QueryOptions {
+            filter: Some(serde_json::from_value(filter).unwrap()),
+            ..QueryOptions::default()
+        };
+        let results = index.query(&[1.0, 0.0, 0.0], options).unwrap();
+        results.into_iter().map(|r| r.chunk.id).collect()
+    };
+
+    let recent_docs = json!({ "and": [
+        { "field": "source_plugin", "op": "eq", "value": "@vpack/source-fs" },
+        { "field": "year", "op": "gte", "value": 2024 },
+        { "not": { "field": "chunk_type", "op": "eq", "value": "changelog" } }
+    ]});
+    assert_eq!(ids(recent_docs), ["guide-new"]);
+
+    let either = json!({ "or": [
+        { "field": "year", "op": "lte", "value": 2023 },
+        { "field": "chunk_type", "op": "eq", "value": "changelog" }
+    ]});
+    assert_eq!(ids(either), ["guide-old", "changelog-new"]);
+
+    assert_eq!(ids(json!({ "and": [] })).len(), 3);
+    assert!(ids(json!({ "or": [] })).is_empty());
+    assert_eq!(ids(json!({ "field": "year", "op": "eq", "value": 2023 })), ["guide-old"]);
+}
+
+#[test]
+fn metadata_index_answers_like_a_scan() {
+    let chunks: Vec<EmbeddedChunk> = (0..200)
+        .map(|i| {
+            let angle = i as f32 * 0.05;
+            let mut chunk = make_chunk(
+                &format!("c{i:03}"),
+                vec![angle.cos(), angle.sin(), 0.3],
+                "text",
+            );
+            chunk.chunk.metadata.source_id = format!("doc-{}", i / 4);
+            chunk
+                .chunk
+                .metadata
+                .extra
+                .insert("year".to_string(), json!(2020 + i % 5));
+            chunk
+        })
+        .collect();
+    let mut manifest = make_manifest(3);
+    let plain = VPackIndex::build(chunks.clone(), manifest.clone()).unwrap();
+    manifest["index"] = json!({ "metadata_index": ["source_id", "year"] });
+    let indexed = VPackIndex::build(chunks, manifest).unwrap();
+    let bytes = vpack_engine::serialize(&indexed).unwrap();
+    assert!(
+        vpack_engine::read_section(&bytes, vpack_engine::SectionId::MetadataIndex)
+            .unwrap()
+            .is_some()
+    );
+    let restored = vpack_engine::deserialize(&bytes).unwrap();
+
+    let filters = [
+        json!({ "field": "source_id", "op": "in", "value": ["doc-3", "doc-17", "doc-40"] }),
+        json!({ "and": [
+            { "field": "year", "op": "eq", "value": 2022 },
+            { "field": "source_id", "op": "neq", "value": "doc-7" }
+        ]}),
+        json!({ "or": [
+            { "field": "source_id", "op": "eq", "value": "doc-0" },
+            { "field": "year", "op": "gte", "value": 2024 }
+        ]}),
+    ];
+    for filter in filters {
+        let ids = |index: &VPackIndex| -> Vec<String> {
+            let options = QueryOptions {
+                filter: Some(serde_json::from_value(filter.clone()).unwrap()),
+                top_k: 20,
+                ..QueryOptions::default()
+            };
+            let results = index.query(&[1.0, 0.5, 0.3], options).unwrap();
+            results.into_iter().map(|r| r.chunk.id).collect()
+        };
+        assert!(!ids(&plain).is_empty());
+        assert_eq!(ids(&indexed), ids(&plain), "{filter}");
+        assert_eq!(ids(&restored), ids(&plain), "{filter}");
+    }
+}
+
 #[test]
 fn serialize_round_trip() {
     let index = VPackIndex::build(chunks_3d(), make_manifest(3)).unwrap();
@@ -139,3 +476,34 @@ fn deserialize_rejects_old_version() {
     let result = vpack_engine::deserialize(&bytes);
     assert!(result.is_err());
 }
+
+#[test]
+fn open_mmap_queries_like_deserialize() {
+    let index = VPackIndex::build(chunks_3d(), make_manifest(3)).unwrap();
+    let bytes = vpack_engine::serialize(&index).unwrap();
+    let path = std::env::temp_dir().join(format!("vpack-mmap-{}.vpack", std::process::id()));
+    std::fs::write(&path, &bytes).unwrap();
+
+    let mapped = VPackIndex::open_mmap(&path).unwrap();
+    assert!(mapped.is_mapped());
+    let options = || QueryOptions {
+        include_vectors: true,
+        ..QueryOptions::default()
+    };
+    let expected = index.query(&[1.0, 0.2, 0.0], options()).unwrap();
+    let results = mapped.query(&[1.0, 0.2, 0.0], options()).unwrap();
+    assert_eq!(
+        serde_json::to_value(&results).unwrap(),
+        serde_json::to_value(&expected).unwrap()
+    );
+    assert_eq!(vpack_engine::serialize(&mapped).unwrap(), bytes);
+
+    drop(mapped);
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn open_mmap_reports_missing_file() {
+    let result = VPackIndex::open_mmap("/nonexistent/pack.vpack");
+    assert!(matches!(result, Err(VPackError::InvalidFormat(_))));
+}
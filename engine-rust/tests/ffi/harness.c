@@ -0,0 +1,136 @@
+/*
+ * C test harness for include/vpack_engine.h.
+ *
+ * Usage: harness <pack.vpack>
+ * The pack is the 3-dimensional fixture written by tests/ffi_tests.rs: the
+ * nearest chunk to [1, 0, 0] is chunk-00000. Exits non-zero on the first
+ * failed check.
+ */
+
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+
+#include "vpack_engine.h"
+
+static int failures = 0;
+
+#define CHECK(cond)                                                        \
+  do {                                                                     \
+    if (!(cond)) {                                                         \
+      fprintf(stderr, "%s:%d: check failed: %s\n", __FILE__, __LINE__,     \
+              #cond);                                                      \
+      failures++;                                                          \
+    }                                                                      \
+  } while (0)
+
+static unsigned char *read_file(const char *path, size_t *len) {
+  FILE *file = fopen(path, "rb");
+  if (!file) return NULL;
+  fseek(file, 0, SEEK_END);
+  long size = ftell(file);
+  fseek(file, 0, SEEK_SET);
+  unsigned char *bytes = malloc((size_t)size);
+  if (bytes && fread(bytes, 1, (size_t)size, file) != (size_t)size) {
+    free(bytes);
+    bytes = NULL;
+  }
+  fclose(file);
+  *len = (size_t)size;
+  return bytes;
+}
+
+int main(int argc, char **argv) {
+  if (argc != 2) {
+    fprintf(stderr, "usage: %s <pack.vpack>\n", argv[0]);
+    return 2;
+  }
+
+  size_t len = 0;
+  unsigned char *bytes = read_file(argv[1], &len);
+  CHECK(bytes != NULL);
+  if (!bytes) return 1;
+
+  VPackIndex *index = NULL;
+  CHECK(vpack_index_from_bytes(bytes, len, &index) == VPACK_STATUS_OK);
+  free(bytes);
+  CHECK(index != NULL);
+  CHECK(vpack_index_chunk_count(index) == 3);
+  CHECK(vpack_index_dimensions(index) == 3);
+
+  char *manifest = NULL;
+  CHECK(vpack_index_manifest_json(index, &manifest) == VPACK_STATUS_OK);
+  CHECK(manifest && strstr(manifest, "\"@test/fixture\"") != NULL);
+  vpack_string_free(manifest);
+
+  /* Structured results. */
+  const float query[3] = {1.0f, 0.0f, 0.0f};
+  VPackResults *results = NULL;
+  CHECK(vpack_index_query(index, query, 3, "{\"topK\": 2}", &results) ==
+        VPACK_STATUS_OK);
+  CHECK(vpack_results_len(results) == 2);
+  const VPackResult *best = vpack_results_get(results, 0);
+  CHECK(best != NULL);
+  if (best) {
+    CHECK(strcmp(best->chunk_id, "chunk-00000") == 0);
+    CHECK(best->rank == 0);
+    CHECK(best->score > 0.99f && best->score == best->similarity);
+    CHECK(strstr(best->metadata_json, "\"source_plugin\"") != NULL);
+  }
+  CHECK(vpack_results_get(results, 2) == NULL);
+  vpack_results_free(results);
+
+  /* JSON results, default options. */
+  char *json = NULL;
+  CHECK(vpack_index_query_json(index, query, 3, NULL, &json) ==
+        VPACK_STATUS_OK);
+  CHECK(json && json[0] == '[' && strstr(json, "\"chunk-00000\"") != NULL);
+  vpack_string_free(json);
+
+  /* Errors carry the VPackError code. */
+  results = NULL;
+  CHECK(vpack_index_query(index, query, 2, NULL, &results) ==
+        VPACK_STATUS_DIMENSION_MISMATCH);
+  CHECK(results == NULL);
+  CHECK(strcmp(vpack_last_error_code(), "DIMENSION_MISMATCH") == 0);
+  CHECK(strstr(vpack_last_error_message(), "3d") != NULL);
+
+  CHECK(vpack_index_query(index, query, 3, "{\"topK\": \"x\"}", &results) ==
+        VPACK_STATUS_INVALID_ARGUMENT);
+  CHECK(strcmp(vpack_last_error_code(), "INVALID_ARGUMENT") == 0);
+
+  const unsigned char garbage[4] = {'n', 'o', 'p', 'e'};
+  VPackIndex *bad = NULL;
+  CHECK(vpack_index_from_bytes(garbage, sizeof garbage, &bad) ==
+        VPACK_STATUS_DESERIALIZE_FAILED);
+  CHECK(bad == NULL);
+  CHECK(strcmp(vpack_last_error_code(), "DESERIALIZE_FAILED") == 0);
+
+  /* Memory-mapped open. */
+  VPackIndex *mapped = NULL;
+  CHECK(vpack_index_open(argv[1], &mapped) == VPACK_STATUS_OK);
+  CHECK(vpack_index_chunk_count(mapped) == 3);
+  vpack_index_free(mapped);
+
+  mapped = NULL;
+  CHECK(vpack_index_open_with_options(
+            argv[1], "{\"model\": \"BAAI/bge-small-en-v1.5\"}", &mapped) ==
+        VPACK_STATUS_MODEL_MISMATCH);
+  CHECK(mapped == NULL);
+  CHECK(strcmp(vpack_last_error_code(), "MODEL_MISMATCH") == 0);
+  CHECK(vpack_index_open_with_options(argv[1], NULL, &mapped) ==
+        VPACK_STATUS_OK);
+  vpack_index_free(mapped);
+
+  vpack_index_free(index);
+  vpack_index_free(NULL);
+  vpack_results_free(NULL);
+  vpack_string_free(NULL);
+
+  if (failures) {
+    fprintf(stderr, "%d check(s) failed\n", failures);
+    return 1;
+  }
+  printf("ffi harness: ok\n");
+  return 0;
+}
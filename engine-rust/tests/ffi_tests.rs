@@ -0,0 +1,197 @@
+#![cfg(feature = "ffi")]
+
+mod common;
+
+use std::ffi::{CStr, CString};
+use std::path::Path;
+use std::process::Command;
+use std::ptr;
+
+use common::{make_chunks, make_manifest};
+use vpack_engine::ffi::*;
+use vpack_engine::VPackIndex;
+
+fn fixture_bytes() -> Vec<u8> {
+    let chunks = make_chunks(vec![
+        vec![1.0, 0.0, 0.0],
+        vec![0.7, 0.7, 0.0],
+        vec![0.0, 0.0, 1.0],
+    ]);
+    let index = VPackIndex::build(chunks, make_manifest(3)).unwrap();
+    vpack_engine::serialize(&index).unwrap()
+}
+
+fn last_error_code() -> String {
+    unsafe { CStr::from_ptr(vpack_last_error_code()) }
+        .to_str()
+        .unwrap()
+        .to_string()
+}
+
+#[test]
+fn query_through_the_c_abi() {
+    let bytes = fixture_bytes();
+    let mut index = ptr::null_mut();
+    unsafe {
+        assert_eq!(
+            vpack_index_from_bytes(bytes.as_ptr(), bytes.len(), &mut index),
+            VPackStatus::Ok
+        );
+        assert_eq!(vpack_index_chunk_count(index), 3);
+        assert_eq!(vpack_index_dimensions(index), 3);
+
+        let query = [0.0f32, 0.0, 1.0];
+        let options = CString::new(r#"{"topK": 2}"#).unwrap();
+        let mut results = ptr::null_mut();
+        assert_eq!(
+            vpack_index_query(index, query.as_ptr(), 3, options.as_ptr(), &mut results),
+            VPackStatus::Ok
+        );
+        assert_eq!(vpack_results_len(results), 2);
+        let best = &*vpack_results_get(results, 0);
+        assert_eq!(CStr::from_ptr(best.chunk_id).to_str(), Ok("chunk-00002"));
+        assert_eq!(CStr::from_ptr(best.text).to_str(), Ok("text 2"));
+        assert_eq!(best.rank, 0);
+        assert!(vpack_results_get(results, 2).is_null());
+        vpack_results_free(results);
+
+        let mut json = ptr::null_mut();
+        assert_eq!(
+            vpack_index_query_json(index, query.as_ptr(), 3, ptr::null(), &mut json),
+            VPackStatus::Ok
+        );
+        let parsed: serde_json::Value =
+            serde_json::from_str(CStr::from_ptr(json).to_str().unwrap()).unwrap();
+        assert_eq!(parsed[0]["chunk"]["id"], "chunk-00002");
+        vpack_string_free(json);
+
+        vpack_index_free(index);
+    }
+}
+
+#[test]
+fn failures_report_vpack_error_codes() {
+    let bytes = fixture_bytes();
+    let mut index = ptr::null_mut();
+    unsafe {
+        vpack_index_from_bytes(bytes.as_ptr(), bytes.len(), &mut index);
+
+        let query = [1.0f32, 0.0];
+        let mut results = ptr::null_mut();
+        assert_eq!(
+            vpack_index_query(index, query.as_ptr(), 2, ptr::null(), &mut results),
+            VPackStatus::DimensionMismatch
+        );
+        assert!(results.is_null());
+        assert_eq!(last_error_code(), "DIMENSION_MISMATCH");
+
+        let options = CString::new(r#"{"temporalWeight": 2}"#).unwrap();
+        assert_eq!(
+            vpack_index_query(index, query.as_ptr(), 2, options.as_ptr(), &mut results),
+            VPackStatus::InvalidQuery
+        );
+        assert_eq!(last_error_code(), "INVALID_QUERY");
+
+        assert_eq!(
+            vpack_index_query(ptr::null(), query.as_ptr(), 2, ptr::null(), &mut results),
+            VPackStatus::InvalidArgument
+        );
+        assert_eq!(last_error_code(), "INVALID_ARGUMENT");
+
+        let mut bad = ptr::null_mut();
+        assert_eq!(
+            vpack_index_from_bytes(bytes.as_ptr(), 16, &mut bad),
+            VPackStatus::DeserializeFailed
+        );
+        assert!(bad.is_null());
+        assert_eq!(last_error_code(), "DESERIALIZE_FAILED");
+
+        vpack_index_free(index);
+    }
+}
+
+#[test]
+fn load_options_check_the_pinned_model() {
+    let bytes = fixture_bytes();
+    let load = |options: &str| unsafe {
+        let options = CString::new(options).unwrap();
+        let mut index = ptr::null_mut();
+        let status = vpack_index_from_bytes_with_options(
+            bytes.as_ptr(),
+            bytes.len(),
+            options.as_ptr(),
+            &mut index,
+        );
+        vpack_index_free(index);
+        status
+    };
+    assert_eq!(
+        load(r#"{"model": "Xenova/all-MiniLM-L6-v2"}"#),
+        VPackStatus::Ok
+    );
+    assert_eq!(
+        load(r#"{"model": "BAAI/bge-small-en-v1.5"}"#),
+        VPackStatus::ModelMismatch
+    );
+    assert_eq!(last_error_code(), "MODEL_MISMATCH");
+    assert_eq!(load(r#"{"model": 7}"#), VPackStatus::InvalidArgument);
+
+    let mut index = ptr::null_mut();
+    unsafe {
+        assert_eq!(
+            vpack_index_from_bytes_with_options(
+                bytes.as_ptr(),
+                bytes.len(),
+                ptr::null(),
+                &mut index
+            ),
+            VPackStatus::Ok
+        );
+        vpack_index_free(index);
+    }
+}
+
+/// Compiles tests/ffi/harness.c against include/vpack_engine.h and the
+/// cdylib from this build, then runs it. Skipped without a C compiler.
+#[test]
+fn c_harness_passes() {
+    let compiler = std::env::var("CC").unwrap_or_else(|_| "cc".to_string());
+    if Command::new(&compiler).arg("--version").output().is_err() {
+        eprintln!("skipping: no C compiler ({compiler})");
+        return;
+    }
+    let manifest_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let tmp = Path::new(env!("CARGO_TARGET_TMPDIR"));
+    // The cdylib is built next to the test binary. It has no SONAME, so
+    // linking it by path pins the harness to this exact file.
+    let exe = std::env::current_exe().unwrap();
+    let lib = ["libvpack_engine.so", "libvpack_engine.dylib"]
+        .iter()
+        .map(|name| exe.with_file_name(name))
+        .find(|path| path.exists())
+        .expect("cdylib not found next to the test binary");
+
+    let pack = tmp.join("ffi_fixture.vpack");
+    std::fs::write(&pack, fixture_bytes()).unwrap();
+    let harness = tmp.join("ffi_harness");
+    let status = Command::new(&compiler)
+        .arg("-std=c99")
+        .arg("-Wall")
+        .arg("-Werror")
+        .arg("-I")
+        .arg(manifest_dir.join("include"))
+        .arg(manifest_dir.join("tests/ffi/harness.c"))
+        .arg("-o")
+        .arg(&harness)
+        .arg(&lib)
+        .status()
+        .unwrap();
+    assert!(status.success(), "harness failed to compile");
+
+    let output = Command::new(&harness).arg(&pack).output().unwrap();
+    assert!(
+        output.status.success(),
+        "harness failed:\n{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
@@ -0,0 +1,305 @@
+mod common;
+
+use common::{make_chunks, make_manifest, random_vectors, top_ids};
+use serde_json::json;
+use vpack_engine::{EmbeddedChunk, QueryOptions, VPackError, VPackIndex};
+
+const DIMENSIONS: usize = 16;
+
+fn manifest(index: serde_json::Value) -> serde_json::Value {
+    let mut manifest = make_manifest(DIMENSIONS);
+    manifest["index"] = index;
+    manifest
+}
+
+fn live_ids(index: &VPackIndex) -> Vec<String> {
+    let options = QueryOptions {
+        top_k: usize::MAX,
+        ef_search: Some(1_000),
+        ..QueryOptions::default()
+    };
+    let mut ids: Vec<String> = index
+        .query(&[0.1; DIMENSIONS], options)
+        .unwrap()
+        .into_iter()
+        .map(|r| r.chunk.id)
+        .collect();
+    ids.sort();
+    ids
+}
+
+#[test]
+fn insert_upsert_and_delete_change_what_queries_return() {
+    let mut chunks = make_chunks(random_vectors(200, DIMENSIONS, 51));
+    let added = chunks.split_off(150);
+    let mut index = VPackIndex::build(chunks, make_manifest(DIMENSIONS)).unwrap();
+
+    index.insert(added.clone()).unwrap();
+    assert_eq!(index.chunk_count(), 200);
+    let target = &added[7];
+    assert_eq!(top_ids(&index, &target.vector, 1)[0], target.chunk.id);
+
+    // chunk-00000..00009 are doc-0.
+    assert_eq!(index.delete_source("doc-0"), 10);
+    assert_eq!(index.delete(&["chunk-00010", "chunk-00010", "missing"]), 1);
+    assert_eq!(index.delete(&["chunk-00010"]), 0);
+    assert_eq!(index.chunk_count(), 189);
+    assert_eq!(index.deleted_count(), 11);
+    let ids = live_ids(&index);
+    assert_eq!(ids.len(), 189);
+    assert!(!ids.iter().any(|id| id.as_str() <= "chunk-00010"));
+
+    // Upsert moves chunk-00020 onto another chunk's vector.
+    let mut moved = EmbeddedChunk {
+        vector: added[3].vector.clone(),
+        ..index_chunk(&index, "chunk-00020")
+    };
+    moved.chunk.text = "replaced".to_string();
+    index.upsert(vec![moved]).unwrap();
+    assert_eq!(index.chunk_count(), 189);
+    let options = QueryOptions {
+        top_k: 2,
+        ..QueryOptions::default()
+    };
+    let results = index.query(&added[3].vector, options).unwrap();
+    let replaced = results
+        .iter()
+        .find(|r| r.chunk.id == "chunk-00020")
+        .expect("upserted chunk is a top hit");
+    assert_eq!(replaced.chunk.text, "replaced");
+    assert_eq!(live_ids(&index).len(), 189);
+}
+
+fn index_chunk(index: &VPackIndex, id: &str) -> EmbeddedChunk {
+    let options = QueryOptions {
+        top_k: usize::MAX,
+        include_vectors: true,
+        ..QueryOptions::default()
+    };
+    let result = index
+        .query(&[0.1; DIMENSIONS], options)
+        .unwrap()
+        .into_iter()
+        .find(|r| r.chunk.id == id)
+        .unwrap();
+    EmbeddedChunk {
+        chunk: result.chunk,
+        vector: result.vector.unwrap(),
+    }
+}
+
+#[test]
+fn rejected_changes_leave_the_index_unchanged() {
+    let chunks = make_chunks(random_vectors(40, DIMENSIONS, 52));
+    let mut index = VPackIndex::build(chunks.clone(), make_manifest(DIMENSIONS)).unwrap();
+    let before = vpack_engine::serialize(&index).unwrap();
+
+    let mut fresh = make_chunks(random_vectors(41, DIMENSIONS, 53)).split_off(40);
+    fresh[0].chunk.id = "fresh".to_string();
+    let mut short = fresh[0].clone();
+    short.vector.pop();
+    let result = index.insert(vec![fresh[0].clone(), short]);
+    assert!(matches!(
+        result,
+        Err(VPackError::DimensionMismatch {
+            expected: DIMENSIONS,
+            got
+        }) if got == DIMENSIONS - 1
+    ));
+
+    let result = index.insert(vec![fresh[0].clone(), chunks[5].clone()]);
+    assert!(matches!(result, Err(VPackError::DuplicateChunkId(id)) if id == "chunk-00005"));
+    let result = index.upsert(vec![fresh[0].clone(), fresh[0].clone()]);
+    assert!(matches!(result, Err(VPackError::DuplicateChunkId(id)) if id == "fresh"));
+
+    assert_eq!(vpack_engine::serialize(&index).unwrap(), before);
+}
+
+#[test]
+fn compacted_det_hnsw_pack_equals_a_fresh_build() {
+    let mut chunks = make_chunks(random_vectors(300, DIMENSIONS, 54));
+    let added = chunks.split_off(250);
+    let manifest = manifest(json!({ "type": "det-hnsw", "metadata_index": ["source_id"] }));
+    let mut index = VPackIndex::build(chunks.clone(), manifest.clone()).unwrap();
+    index.insert(added.clone()).unwrap();
+    index.delete_source("doc-3");
+    index.delete(&["chunk-00100", "chunk-00260"]);
+
+    let live: Vec<EmbeddedChunk> = chunks
+        .into_iter()
+        .chain(added)
+        .filter(|c| c.chunk.metadata.source_id != "doc-3")
+        .filter(|c| !["chunk-00100", "chunk-00260"].contains(&c.chunk.id.as_str()))
+        .collect();
+    let fresh = VPackIndex::build(live, manifest).unwrap();
+    let bytes = vpack_engine::serialize(&fresh).unwrap();
+
+    // Serializing keeps the changes pending; compacting restores the
+    // canonical build.
+    assert_ne!(vpack_engine::serialize(&index).unwrap(), bytes);
+    index.compact().unwrap();
+    assert_eq!(index.deleted_count(), 0);
+    assert!(!index.needs_compaction());
+    assert_eq!(vpack_engine::serialize(&index).unwrap(), bytes);
+}
+
+#[test]
+fn det_hnsw_inserts_compact_canonically_after_a_round_trip() {
+    let mut chunks = make_chunks(random_vectors(120, DIMENSIONS, 59));
+    let manifest = manifest(json!({ "type": "det-hnsw" }));
+    let fresh = VPackIndex::build(chunks.clone(), manifest.clone()).unwrap();
+
+    // Insert the lowest chunk ids last, against chunk-ID order.
+    let first = chunks.drain(..20).collect();
+    let mut index = VPackIndex::build(chunks, manifest).unwrap();
+    index.insert(first).unwrap();
+    assert!(index.needs_compaction());
+
+    let bytes = vpack_engine::serialize(&index).unwrap();
+    let mut restored = vpack_engine::deserialize(&bytes).unwrap();
+    assert!(restored.needs_compaction());
+    assert_eq!(vpack_engine::serialize(&restored).unwrap(), bytes);
+    restored.compact().unwrap();
+    assert_eq!(
+        vpack_engine::serialize(&restored).unwrap(),
+        vpack_engine::serialize(&fresh).unwrap()
+    );
+}
+
+#[test]
+fn serializing_keeps_deletions_and_inserts_in_place() {
+    let mut chunks = make_chunks(random_vectors(300, DIMENSIONS, 62));
+    let added = chunks.split_off(250);
+    let mut index = VPackIndex::build(chunks, manifest(json!({ "type": "hnsw" }))).unwrap();
+    index.insert(added.clone()).unwrap();
+    index.delete_source("doc-4");
+    index.delete(&["chunk-00270"]);
+
+    let bytes = vpack_engine::serialize(&index).unwrap();
+    let restored = vpack_engine::deserialize(&bytes).unwrap();
+    assert_eq!(restored.chunk_count(), 289);
+    assert_eq!(restored.deleted_count(), 11);
+    assert_eq!(vpack_engine::serialize(&restored).unwrap(), bytes);
+    assert_eq!(live_ids(&restored), live_ids(&index));
+    for query in random_vectors(10, DIMENSIONS, 63) {
+        assert_eq!(top_ids(&restored, &query, 10), top_ids(&index, &query, 10));
+    }
+}
+
+#[test]
+fn compaction_keeps_the_row_order_of_flat_and_hnsw_packs() {
+    // Input order is not chunk-ID order.
+    let mut chunks = make_chunks(random_vectors(200, DIMENSIONS, 64));
+    chunks.reverse();
+    for index in [json!({ "type": "flat" }), json!({ "type": "hnsw" })] {
+        let manifest = manifest(index);
+        let mut kept = chunks.clone();
+        let added = kept.split_off(150);
+        let mut index = VPackIndex::build(kept, manifest.clone()).unwrap();
+        index.insert(added).unwrap();
+        index.delete_source("doc-17");
+        index.delete_source("doc-3");
+        index.compact().unwrap();
+
+        let live: Vec<EmbeddedChunk> = chunks
+            .iter()
+            .filter(|c| !["doc-17", "doc-3"].contains(&c.chunk.metadata.source_id.as_str()))
+            .cloned()
+            .collect();
+        let fresh = VPackIndex::build(live, manifest).unwrap();
+        assert_eq!(
+            vpack_engine::serialize(&index).unwrap(),
+            vpack_engine::serialize(&fresh).unwrap()
+        );
+    }
+}
+
+#[test]
+fn ivf_pq_mutations_survive_serialize_round_trip() {
+    let mut chunks = make_chunks(random_vectors(400, DIMENSIONS, 55));
+    let added = chunks.split_off(350);
+    let manifest = manifest(json!({
+        "type": "ivf-pq",
+        "ivf_pq": { "nlist": 8, "nprobe": 8, "m": 4, "nbits": 6, "kmeans_iters": 10, "rerank": 50 }
+    }));
+    let mut index = VPackIndex::build(chunks, manifest).unwrap();
+    index.insert(added.clone()).unwrap();
+    index.delete_source("doc-1");
+
+    let bytes = vpack_engine::serialize(&index).unwrap();
+    let mut restored = vpack_engine::deserialize(&bytes).unwrap();
+    assert_eq!(restored.chunk_count(), 390);
+    assert_eq!(live_ids(&restored), live_ids(&index));
+    for query in random_vectors(10, DIMENSIONS, 56) {
+        assert_eq!(top_ids(&restored, &query, 10), top_ids(&index, &query, 10));
+    }
+    assert_eq!(top_ids(&index, &added[0].vector, 1)[0], added[0].chunk.id);
+
+    index.compact().unwrap();
+    restored.compact().unwrap();
+    assert_eq!(
+        vpack_engine::serialize(&index).unwrap(),
+        vpack_engine::serialize(&restored).unwrap()
+    );
+}
+
+#[test]
+fn quantized_mutations_survive_serialize_round_trip() {
+    let mut chunks = make_chunks(random_vectors(300, DIMENSIONS, 59));
+    let added = chunks.split_off(250);
+    let manifest = manifest(json!({ "type": "hnsw", "quantization": { "type": "int8" } }));
+    let mut index = VPackIndex::build(chunks, manifest).unwrap();
+    index.insert(added.clone()).unwrap();
+    index.delete_source("doc-2");
+    assert_eq!(top_ids(&index, &added[5].vector, 1)[0], added[5].chunk.id);
+
+    let bytes = vpack_engine::serialize(&index).unwrap();
+    let mut restored = vpack_engine::deserialize(&bytes).unwrap();
+    assert_eq!(restored.chunk_count(), 290);
+    assert_eq!(live_ids(&restored), live_ids(&index));
+
+    // Compaction recalibrates over the live chunks, loaded or not.
+    index.compact().unwrap();
+    restored.compact().unwrap();
+    assert_eq!(
+        vpack_engine::serialize(&index).unwrap(),
+        vpack_engine::serialize(&restored).unwrap()
+    );
+    for query in random_vectors(10, DIMENSIONS, 60) {
+        assert_eq!(top_ids(&restored, &query, 10), top_ids(&index, &query, 10));
+    }
+}
+
+#[test]
+fn mapped_index_accepts_inserts() {
+    let mut chunks = make_chunks(random_vectors(60, DIMENSIONS, 57));
+    let added = chunks.split_off(50);
+    let index = VPackIndex::build(chunks, manifest(json!({ "type": "flat" }))).unwrap();
+    let path = std::env::temp_dir().join(format!("vpack-mutate-{}.vpack", std::process::id()));
+    std::fs::write(&path, vpack_engine::serialize(&index).unwrap()).unwrap();
+
+    let mut mapped = VPackIndex::open_mmap(&path).unwrap();
+    mapped.insert(added.clone()).unwrap();
+    assert!(!mapped.is_mapped());
+    assert_eq!(mapped.chunk_count(), 60);
+    assert_eq!(top_ids(&mapped, &added[4].vector, 1)[0], added[4].chunk.id);
+
+    drop(mapped);
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn deleting_everything_leaves_nothing_to_serialize() {
+    let chunks = make_chunks(random_vectors(20, DIMENSIONS, 58));
+    let mut index = VPackIndex::build(chunks, make_manifest(DIMENSIONS)).unwrap();
+    assert_eq!(
+        index.delete_source("doc-0") + index.delete_source("doc-1"),
+        20
+    );
+    assert!(live_ids(&index).is_empty());
+    assert!(matches!(index.compact(), Err(VPackError::EmptyIndex)));
+    assert!(matches!(
+        vpack_engine::serialize(&index),
+        Err(VPackError::EmptyIndex)
+    ));
+}
@@ -0,0 +1,85 @@
+#![cfg(feature = "parallel")]
+
+mod common;
+
+use common::{make_chunks, make_manifest, random_vectors};
+use rayon::ThreadPoolBuilder;
+use serde_json::json;
+use vpack_engine::{QueryOptions, QueryResult, VPackIndex};
+
+const DIMENSIONS: usize = 32;
+
+/// Run `f` on a pool of `threads` threads. One thread visits items in order,
+/// like the build without the feature.
+fn with_threads<T: Send>(threads: usize, f: impl FnOnce() -> T + Send) -> T {
+    ThreadPoolBuilder::new()
+        .num_threads(threads)
+        .build()
+        .unwrap()
+        .install(f)
+}
+
+fn build_and_query(index: &serde_json::Value) -> (Vec<u8>, Vec<QueryResult>) {
+    let mut manifest = make_manifest(DIMENSIONS);
+    manifest["index"] = index.clone();
+    let built =
+        VPackIndex::build(make_chunks(random_vectors(600, DIMENSIONS, 91)), manifest).unwrap();
+    let query = random_vectors(1, DIMENSIONS, 92).remove(0);
+    let options = QueryOptions {
+        top_k: 20,
+        include_vectors: true,
+        // Every filtered query takes the exact scan.
+        brute_force_selectivity: Some(1.0),
+        filter: Some(
+            serde_json::from_value(json!({
+                "not": { "field": "source_id", "op": "eq", "value": "doc-3" }
+            }))
+            .unwrap(),
+        ),
+        ..QueryOptions::default()
+    };
+    let results = built.query(&query, options).unwrap();
+    (vpack_engine::serialize(&built).unwrap(), results)
+}
+
+#[test]
+fn builds_and_scans_match_a_single_thread() {
+    for index in [
+        json!({ "type": "flat" }),
+        json!({ "type": "hnsw" }),
+        json!({ "type": "det-hnsw", "metric": "euclidean" }),
+        json!({ "type": "ivf-pq", "ivf_pq": { "nlist": 8, "m": 4, "rerank": 0 } }),
+        json!({ "type": "flat", "quantization": { "type": "int8", "rescore": 40 } }),
+        json!({ "type": "hnsw", "precision": "bf16" }),
+    ] {
+        let (serial_bytes, serial_results) = with_threads(1, || build_and_query(&index));
+        let (bytes, results) = with_threads(8, || build_and_query(&index));
+        assert_eq!(bytes, serial_bytes, "{index}");
+        assert_eq!(
+            serde_json::to_value(&results).unwrap(),
+            serde_json::to_value(&serial_results).unwrap(),
+            "{index}"
+        );
+        assert_eq!(results.len(), 20, "{index}");
+    }
+}
+
+#[test]
+fn first_mismatched_vector_is_reported() {
+    let mut chunks = make_chunks(random_vectors(5_000, DIMENSIONS, 93));
+    chunks[1_234].vector.truncate(7);
+    chunks[4_321].vector.truncate(3);
+    let err = with_threads(8, || VPackIndex::build(chunks, make_manifest(DIMENSIONS)))
+        .err()
+        .unwrap();
+    assert!(
+        matches!(
+            err,
+            vpack_engine::VPackError::DimensionMismatch {
+                expected: DIMENSIONS,
+                got: 7
+            }
+        ),
+        "{err}"
+    );
+}
@@ -0,0 +1,352 @@
+mod common;
+
+use common::{make_changelog, make_chunks, make_manifest, random_vectors, top_ids};
+use serde_json::json;
+use vpack_engine::patch::{FLAG_CODEBOOK_CHANGED, FLAG_HAS_CHANGELOG_ENTRY, FLAG_HAS_INDEX_PATCH};
+use vpack_engine::{EmbeddedChunk, VPackError, VPackIndex};
+
+const DIMENSIONS: usize = 16;
+
+fn manifest(index: serde_json::Value, version: &str) -> serde_json::Value {
+    let mut manifest = make_manifest(DIMENSIONS);
+    manifest["index"] = index;
+    manifest["version"] = json!(version);
+    manifest
+}
+
+fn ivf_pq(rerank: usize) -> serde_json::Value {
+    json!({
+        "type": "ivf-pq",
+        "ivf_pq": { "nlist": 16, "nprobe": 4, "m": 4, "nbits": 6, "kmeans_iters": 10, "rerank": rerank }
+    })
+}
+
+/// The base chunks with 5 removed, 6 modified and 10 added.
+fn next_chunks(base: &[EmbeddedChunk]) -> Vec<EmbeddedChunk> {
+    let fresh = random_vectors(base.len() + 10, DIMENSIONS, 72);
+    let mut chunks: Vec<EmbeddedChunk> = base
+        .iter()
+        .filter(|c| {
+            ![
+                "chunk-00003",
+                "chunk-00150",
+                "chunk-00151",
+                "chunk-00270",
+                "chunk-00499",
+            ]
+            .contains(&c.chunk.id.as_str())
+        })
+        .cloned()
+        .collect();
+    for (i, chunk) in chunks.iter_mut().enumerate().filter(|(i, _)| i % 97 == 1) {
+        chunk.chunk.text = format!("revised {i}");
+        chunk.vector = fresh[i].clone();
+    }
+    chunks.extend(make_chunks(fresh).split_off(base.len()));
+    chunks
+}
+
+#[test]
+fn diff_reports_added_modified_and_removed_chunks() {
+    let chunks = make_chunks(random_vectors(500, DIMENSIONS, 70));
+    let flat = json!({ "type": "flat" });
+    let old = VPackIndex::build(chunks.clone(), manifest(flat.clone(), "1.0.0")).unwrap();
+    let mut new = VPackIndex::build(next_chunks(&chunks), manifest(flat, "1.1.0")).unwrap();
+
+    let diff = vpack_engine::diff(&old, &new).unwrap();
+    assert_eq!(diff.base_version.as_deref(), Some("1.0.0"));
+    let ids = |ids: Vec<&str>| ids.windows(2).all(|pair| pair[0] < pair[1]);
+    assert_eq!(diff.added.len(), 10);
+    assert!(ids(diff
+        .added
+        .iter()
+        .map(|c| c.chunk.id.as_str())
+        .collect()));
+    assert_eq!(
+        diff.removed
+            .iter()
+            .map(|c| c.id.as_str())
+            .collect::<Vec<_>>(),
+        [
+            "chunk-00003",
+            "chunk-00150",
+            "chunk-00151",
+            "chunk-00270",
+            "chunk-00499"
+        ]
+    );
+    assert_eq!(diff.modified.len(), 6);
+    for modified in &diff.modified {
+        assert_eq!(modified.before.id, modified.after.chunk.id);
+        assert_ne!(modified.before.text, modified.after.chunk.text);
+    }
+
+    // Deleted chunks are left out, compacted or not.
+    let added = diff.added[0].chunk.id.clone();
+    new.delete(&[added.as_str()]);
+    let diff = vpack_engine::diff(&old, &new).unwrap();
+    assert_eq!(diff.added.len(), 9);
+    assert!(vpack_engine::diff(&new, &new).unwrap().added.is_empty());
+    assert_eq!(
+        vpack_engine::diff(&old, &old).unwrap(),
+        vpack_engine::ChunkDiff {
+            base_version: Some("1.0.0".to_string()),
+            ..Default::default()
+        }
+    );
+}
+
+#[test]
+fn ivf_pq_patch_reproduces_the_result_artifact() {
+    let chunks = make_chunks(random_vectors(500, DIMENSIONS, 71));
+    let base = VPackIndex::build(chunks.clone(), manifest(ivf_pq(0), "1.0.0")).unwrap();
+    let result =
+        VPackIndex::build_with_codebook(next_chunks(&chunks), manifest(ivf_pq(0), "1.1.0"), &base)
+            .unwrap();
+    let result_bytes = vpack_engine::serialize(&result).unwrap();
+
+    let patch = vpack_engine::create_patch(&base, &result).unwrap();
+    let header = vpack_engine::read_patch_header(&patch).unwrap();
+    assert_eq!(header.flags, FLAG_HAS_INDEX_PATCH);
+    assert_eq!(header.base_version, "1.0.0");
+    assert_eq!(header.result_version, "1.1.0");
+    assert!(patch.len() * 4 < result_bytes.len());
+
+    let base_bytes = vpack_engine::serialize(&base).unwrap();
+    let mut local = vpack_engine::deserialize(&base_bytes).unwrap();
+    assert_eq!(
+        vpack_engine::apply_patch(&mut local, &patch).unwrap(),
+        result_bytes
+    );
+    assert_eq!(vpack_engine::serialize(&local).unwrap(), result_bytes);
+    assert_eq!(local.manifest()["version"], "1.1.0");
+    for query in random_vectors(10, DIMENSIONS, 73) {
+        assert_eq!(top_ids(&local, &query, 10), top_ids(&result, &query, 10));
+    }
+}
+
+#[test]
+fn codes_only_ivf_pq_patch_carries_a_changelog_entry() {
+    let chunks = make_chunks(random_vectors(500, DIMENSIONS, 84));
+    let base = VPackIndex::build(chunks.clone(), manifest(ivf_pq(0), "1.0.0")).unwrap();
+    let changelog = make_changelog(
+        "1.1.0",
+        "2026-01-07T06:00:00Z",
+        None,
+        random_vectors(1, DIMENSIONS, 86).remove(0),
+    );
+    let mut next = next_chunks(&chunks);
+    next.push(changelog.clone());
+    let result =
+        VPackIndex::build_with_codebook(next, manifest(ivf_pq(0), "1.1.0"), &base).unwrap();
+    assert_eq!(result.chunk_count(), 505);
+    assert_eq!(result.changelog().len(), 1);
+    let result_bytes = vpack_engine::serialize(&result).unwrap();
+
+    let patch = vpack_engine::create_patch(&base, &result).unwrap();
+    let header = vpack_engine::read_patch_header(&patch).unwrap();
+    assert_eq!(
+        header.flags,
+        FLAG_HAS_INDEX_PATCH | FLAG_HAS_CHANGELOG_ENTRY
+    );
+    let entry = vpack_engine::read_changelog_entry(&patch).unwrap().unwrap();
+    assert_eq!(entry.chunk, changelog.chunk);
+
+    let mut local = vpack_engine::deserialize(&vpack_engine::serialize(&base).unwrap()).unwrap();
+    assert_eq!(
+        vpack_engine::apply_patch(&mut local, &patch).unwrap(),
+        result_bytes
+    );
+    for query in random_vectors(10, DIMENSIONS, 85) {
+        assert_eq!(top_ids(&local, &query, 10), top_ids(&result, &query, 10));
+    }
+}
+
+#[test]
+fn patches_rebuild_graph_indexes_and_carry_a_changelog_entry() {
+    let chunks = make_chunks(random_vectors(500, DIMENSIONS, 74));
+    let index = json!({ "type": "det-hnsw" });
+    let history = make_changelog(
+        "2.0.0",
+        "2026-01-05T06:00:00Z",
+        None,
+        random_vectors(1, DIMENSIONS, 79).remove(0),
+    );
+    let mut base_chunks = chunks.clone();
+    base_chunks.push(history.clone());
+    let base = VPackIndex::build(base_chunks, manifest(index.clone(), "2.0.0")).unwrap();
+
+    let changelog = make_changelog(
+        "2.0.1",
+        "2026-01-06T06:00:00Z",
+        None,
+        random_vectors(1, DIMENSIONS, 75).remove(0),
+    );
+    let mut result_chunks = next_chunks(&chunks);
+    result_chunks.extend([history, changelog.clone()]);
+    let result = VPackIndex::build(result_chunks, manifest(index, "2.0.1")).unwrap();
+
+    let patch = vpack_engine::create_patch(&base, &result).unwrap();
+    let header = vpack_engine::read_patch_header(&patch).unwrap();
+    assert_eq!(header.flags, FLAG_HAS_CHANGELOG_ENTRY);
+    let entry = vpack_engine::read_changelog_entry(&patch).unwrap().unwrap();
+    assert_eq!(entry, changelog);
+
+    let mut local = vpack_engine::deserialize(&vpack_engine::serialize(&base).unwrap()).unwrap();
+    let result_bytes = vpack_engine::apply_patch(&mut local, &patch).unwrap();
+    assert_eq!(result_bytes, vpack_engine::serialize(&result).unwrap());
+    let ids: Vec<&str> = local
+        .changelog()
+        .iter()
+        .map(|c| c.chunk.id.as_str())
+        .collect();
+    assert_eq!(ids, ["changelog-2.0.0", "changelog-2.0.1"]);
+}
+
+#[test]
+fn patches_made_from_mutations_apply() {
+    let chunks = make_chunks(random_vectors(300, DIMENSIONS, 76));
+    let base = VPackIndex::build(chunks, manifest(ivf_pq(20), "1.0.0")).unwrap();
+    let mut next = vpack_engine::deserialize(&vpack_engine::serialize(&base).unwrap()).unwrap();
+    let mut added = make_chunks(random_vectors(4, DIMENSIONS, 77));
+    for (i, chunk) in added.iter_mut().enumerate() {
+        chunk.chunk.id = format!("chunk-00010-{i}");
+    }
+    next.upsert(added).unwrap();
+    next.delete_source("doc-7");
+    next.compact().unwrap();
+
+    let patch = vpack_engine::create_patch(&base, &next).unwrap();
+    let mut local = vpack_engine::deserialize(&vpack_engine::serialize(&base).unwrap()).unwrap();
+    vpack_engine::apply_patch(&mut local, &patch).unwrap();
+    assert_eq!(
+        vpack_engine::serialize(&local).unwrap(),
+        vpack_engine::serialize(&next).unwrap()
+    );
+}
+
+#[test]
+fn quantized_codes_only_patches_apply() {
+    for kind in ["int8", "binary"] {
+        let chunks = make_chunks(random_vectors(200, DIMENSIONS, 78));
+        let index = json!({ "type": "flat", "quantization": { "type": kind } });
+        let base = VPackIndex::build(chunks, manifest(index, "1.0.0")).unwrap();
+        let mut next = vpack_engine::deserialize(&vpack_engine::serialize(&base).unwrap()).unwrap();
+        // Spread past the base's calibration, so the result recalibrates.
+        let mut added = make_chunks(random_vectors(3, DIMENSIONS, 79));
+        for (i, chunk) in added.iter_mut().enumerate() {
+            chunk.chunk.id = format!("chunk-00200-{i}");
+            chunk.vector.iter_mut().for_each(|x| *x *= 3.0);
+        }
+        next.insert(added).unwrap();
+        next.delete_source("doc-3");
+        next.compact().unwrap();
+
+        let patch = vpack_engine::create_patch(&base, &next).unwrap();
+        let mut local =
+            vpack_engine::deserialize(&vpack_engine::serialize(&base).unwrap()).unwrap();
+        vpack_engine::apply_patch(&mut local, &patch).unwrap();
+        assert_eq!(
+            vpack_engine::serialize(&local).unwrap(),
+            vpack_engine::serialize(&next).unwrap(),
+            "{kind}"
+        );
+    }
+}
+
+#[test]
+fn flat_patches_keep_the_input_order() {
+    // Pipeline order, not chunk-ID order.
+    let mut chunks = make_chunks(random_vectors(300, DIMENSIONS, 80));
+    chunks.reverse();
+    let flat = json!({ "type": "flat" });
+    let base = VPackIndex::build(chunks.clone(), manifest(flat.clone(), "1.0.0")).unwrap();
+    let result = VPackIndex::build(next_chunks(&chunks), manifest(flat.clone(), "1.1.0")).unwrap();
+
+    let patch = vpack_engine::create_patch(&base, &result).unwrap();
+    assert_eq!(vpack_engine::read_patch_header(&patch).unwrap().flags, 0);
+    let mut local = vpack_engine::deserialize(&vpack_engine::serialize(&base).unwrap()).unwrap();
+    let result_bytes = vpack_engine::apply_patch(&mut local, &patch).unwrap();
+    assert_eq!(result_bytes, vpack_engine::serialize(&result).unwrap());
+
+    // The same chunks in another row order are not reproducible.
+    let mut sorted = next_chunks(&chunks);
+    sorted.sort_by(|a, b| a.chunk.id.cmp(&b.chunk.id));
+    let reordered = VPackIndex::build(sorted, manifest(flat, "1.1.0")).unwrap();
+    assert!(matches!(
+        vpack_engine::create_patch(&base, &reordered),
+        Err(VPackError::PatchFailed(_))
+    ));
+}
+
+#[test]
+fn default_hnsw_packs_are_not_patched() {
+    // The default index type, in pipeline order.
+    let mut chunks = make_chunks(random_vectors(300, DIMENSIONS, 81));
+    chunks.reverse();
+    let versioned = |version: &str| {
+        let mut manifest = make_manifest(DIMENSIONS);
+        manifest["version"] = json!(version);
+        manifest
+    };
+    let base = VPackIndex::build(chunks.clone(), versioned("1.0.0")).unwrap();
+    let result = VPackIndex::build(next_chunks(&chunks), versioned("1.1.0")).unwrap();
+    let err = vpack_engine::create_patch(&base, &result).err().unwrap();
+    assert!(
+        matches!(&err, VPackError::PatchFailed(message) if message.contains("hnsw")),
+        "{err}"
+    );
+
+    // A flat patch does not apply to an hnsw pack holding the same chunks.
+    let flat = json!({ "type": "flat" });
+    let flat_base = VPackIndex::build(chunks.clone(), manifest(flat.clone(), "1.0.0")).unwrap();
+    let flat_result = VPackIndex::build(next_chunks(&chunks), manifest(flat, "1.1.0")).unwrap();
+    let patch = vpack_engine::create_patch(&flat_base, &flat_result).unwrap();
+    let mut local = base;
+    assert!(matches!(
+        vpack_engine::apply_patch(&mut local, &patch),
+        Err(VPackError::PatchFailed(_))
+    ));
+}
+
+#[test]
+fn rejected_patches_leave_the_index_unchanged() {
+    let chunks = make_chunks(random_vectors(300, DIMENSIONS, 78));
+    let base = VPackIndex::build(chunks.clone(), manifest(ivf_pq(0), "1.0.0")).unwrap();
+    let result =
+        VPackIndex::build_with_codebook(next_chunks(&chunks), manifest(ivf_pq(0), "1.1.0"), &base)
+            .unwrap();
+    let patch = vpack_engine::create_patch(&base, &result).unwrap();
+
+    // Applying twice: the second time the base is the result.
+    let mut local = vpack_engine::deserialize(&vpack_engine::serialize(&base).unwrap()).unwrap();
+    vpack_engine::apply_patch(&mut local, &patch).unwrap();
+    let before = vpack_engine::serialize(&local).unwrap();
+    assert!(matches!(
+        vpack_engine::apply_patch(&mut local, &patch),
+        Err(VPackError::PatchBaseMismatch { .. })
+    ));
+    assert_eq!(vpack_engine::serialize(&local).unwrap(), before);
+
+    let mut local = vpack_engine::deserialize(&vpack_engine::serialize(&base).unwrap()).unwrap();
+    let mut corrupt = patch.clone();
+    *corrupt.last_mut().unwrap() ^= 0xff;
+    assert!(matches!(
+        vpack_engine::apply_patch(&mut local, &corrupt),
+        Err(VPackError::InvalidFormat(_))
+    ));
+
+    // A retrained codebook cannot be patched.
+    let retrained = VPackIndex::build(next_chunks(&chunks), manifest(ivf_pq(0), "1.1.0")).unwrap();
+    let patch = vpack_engine::create_patch(&base, &retrained).unwrap();
+    let header = vpack_engine::read_patch_header(&patch).unwrap();
+    assert_eq!(header.flags, FLAG_CODEBOOK_CHANGED);
+    assert!(matches!(
+        vpack_engine::apply_patch(&mut local, &patch),
+        Err(VPackError::PatchFailed(_))
+    ));
+    assert_eq!(
+        vpack_engine::serialize(&local).unwrap(),
+        vpack_engine::serialize(&base).unwrap()
+    );
+}
@@ -0,0 +1,515 @@
+mod common;
+
+use common::{make_chunks, make_manifest, random_vectors, recall_at, top_ids};
+use serde_json::json;
+use std::collections::HashSet;
+use vpack_engine::{BuildOptions, HnswConfig, IndexType, QueryOptions, VPackIndex};
+
+const DIMENSIONS: usize = 32;
+
+#[test]
+fn hnsw_recall_matches_exact_scan() {
+    let chunks = make_chunks(random_vectors(2_000, DIMENSIONS, 7));
+    let queries = random_vectors(50, DIMENSIONS, 99);
+
+    let exact = VPackIndex::build_with_options(
+        chunks.clone(),
+        make_manifest(DIMENSIONS),
+        BuildOptions {
+            index_type: IndexType::Flat,
+            ..BuildOptions::default()
+        },
+    )
+    .unwrap();
+    let approx = VPackIndex::build(chunks, make_manifest(DIMENSIONS)).unwrap();
+
+    let recall = recall_at(&exact, &approx, &queries, 10);
+    assert!(recall >= 0.95, "recall@10 = {recall}");
+}
+
+#[test]
+fn hnsw_parameters_come_from_manifest() {
+    let chunks = make_chunks(random_vectors(300, DIMENSIONS, 3));
+    let mut manifest = make_manifest(DIMENSIONS);
+    manifest["index"] = json!({
+        "type": "hnsw",
+        "hnsw": { "m": 8, "ef_construction": 64, "ef_search": 32 }
+    });
+
+    let index = VPackIndex::build(chunks, manifest).unwrap();
+    assert_eq!(
+        index.options().hnsw,
+        HnswConfig {
+            m: 8,
+            ef_construction: 64,
+            ef_search: 32,
+            ..HnswConfig::default()
+        }
+    );
+}
+
+#[test]
+fn hnsw_graph_survives_serialize_round_trip() {
+    let chunks = make_chunks(random_vectors(500, DIMENSIONS, 11));
+    let queries = random_vectors(10, DIMENSIONS, 12);
+    let index = VPackIndex::build(chunks, make_manifest(DIMENSIONS)).unwrap();
+
+    let bytes = vpack_engine::serialize(&index).unwrap();
+    let restored = vpack_engine::deserialize(&bytes).unwrap();
+
+    assert_eq!(restored.options(), index.options());
+    for query in &queries {
+        assert_eq!(top_ids(&index, query, 10), top_ids(&restored, query, 10));
+    }
+}
+
+#[test]
+fn higher_ef_search_never_lowers_recall() {
+    let chunks = make_chunks(random_vectors(1_000, DIMENSIONS, 21));
+    let queries = random_vectors(20, DIMENSIONS, 22);
+    let mut manifest = make_manifest(DIMENSIONS);
+    manifest["index"] = json!({ "hnsw": { "m": 4, "ef_construction": 16, "ef_search": 10 } });
+
+    let exact = VPackIndex::build_with_options(
+        chunks.clone(),
+        manifest.clone(),
+        BuildOptions {
+            index_type: IndexType::Flat,
+            ..BuildOptions::default()
+        },
+    )
+    .unwrap();
+    let approx = VPackIndex::build(chunks, manifest).unwrap();
+
+    let mut low = 0;
+    let mut high = 0;
+    for query in &queries {
+        let truth: HashSet<String> = top_ids(&exact, query, 10).into_iter().collect();
+        let wide = QueryOptions {
+            ef_search: Some(400),
+            ..QueryOptions::default()
+        };
+        low += top_ids(&approx, query, 10)
+            .iter()
+            .filter(|id| truth.contains(*id))
+            .count();
+        high += approx
+            .query(query, wide)
+            .unwrap()
+            .iter()
+            .filter(|r| truth.contains(&r.chunk.id))
+            .count();
+    }
+    assert!(high >= low);
+}
+
+fn ivf_pq_manifest(ivf_pq: serde_json::Value) -> serde_json::Value {
+    let mut manifest = make_manifest(DIMENSIONS);
+    manifest["index"] = json!({ "type": "ivf-pq", "ivf_pq": ivf_pq });
+    manifest
+}
+
+#[test]
+fn ivf_pq_recall_with_rerank_matches_exact_scan() {
+    let chunks = make_chunks(random_vectors(1_000, DIMENSIONS, 31));
+    let queries = random_vectors(50, DIMENSIONS, 32);
+    let manifest = ivf_pq_manifest(json!({
+        "nlist": 16, "nprobe": 4, "m": 8, "nbits": 6, "kmeans_iters": 10, "rerank": 100
+    }));
+
+    let exact = VPackIndex::build_with_options(
+        chunks.clone(),
+        manifest.clone(),
+        BuildOptions {
+            index_type: IndexType::Flat,
+            ..BuildOptions::default()
+        },
+    )
+    .unwrap();
+    let approx = VPackIndex::build(chunks, manifest).unwrap();
+
+    let recall = recall_at(&exact, &approx, &queries, 10);
+    assert!(recall >= 0.95, "recall@10 = {recall}");
+}
+
+#[test]
+fn ivf_pq_codes_only_recall() {
+    let chunks = make_chunks(random_vectors(1_000, DIMENSIONS, 33));
+    let queries = random_vectors(50, DIMENSIONS, 34);
+    let manifest = ivf_pq_manifest(json!({
+        "nlist": 16, "nprobe": 4, "m": 16, "nbits": 6, "kmeans_iters": 10
+    }));
+
+    let exact = VPackIndex::build_with_options(
+        chunks.clone(),
+        manifest.clone(),
+        BuildOptions {
+            index_type: IndexType::Flat,
+            ..BuildOptions::default()
+        },
+    )
+    .unwrap();
+    let approx = VPackIndex::build(chunks, manifest).unwrap();
+
+    let recall = recall_at(&exact, &approx, &queries, 10);
+    assert!(recall >= 0.8, "recall@10 = {recall}");
+}
+
+#[test]
+fn ivf_pq_pack_is_much_smaller_than_flat() {
+    let chunks = make_chunks(random_vectors(1_500, 128, 35));
+    let mut manifest = make_manifest(128);
+    manifest["index"] = json!({
+        "type": "ivf-pq",
+        "ivf_pq": { "nlist": 32, "m": 16, "nbits": 6, "kmeans_iters": 10 }
+    });
+
+    let flat = VPackIndex::build_with_options(
+        chunks.clone(),
+        make_manifest(128),
+        BuildOptions {
+            index_type: IndexType::Flat,
+            ..BuildOptions::default()
+        },
+    )
+    .unwrap();
+    let ivf_pq = VPackIndex::build(chunks, manifest).unwrap();
+
+    let flat_bytes = vpack_engine::serialize(&flat).unwrap().len();
+    let ivf_pq_bytes = vpack_engine::serialize(&ivf_pq).unwrap().len();
+    assert!(
+        ivf_pq_bytes * 3 < flat_bytes,
+        "flat = {flat_bytes}, ivf-pq = {ivf_pq_bytes}"
+    );
+}
+
+#[test]
+fn ivf_pq_survives_serialize_round_trip() {
+    let chunks = make_chunks(random_vectors(500, DIMENSIONS, 36));
+    let queries = random_vectors(10, DIMENSIONS, 37);
+    let index = VPackIndex::build(
+        chunks,
+        ivf_pq_manifest(json!({ "nlist": 16, "nprobe": 4, "m": 8 })),
+    )
+    .unwrap();
+
+    let bytes = vpack_engine::serialize(&index).unwrap();
+    let restored = vpack_engine::deserialize(&bytes).unwrap();
+
+    assert_eq!(restored.options(), index.options());
+    for query in &queries {
+        assert_eq!(top_ids(&index, query, 10), top_ids(&restored, query, 10));
+    }
+}
+
+#[test]
+fn ivf_pq_rejects_m_that_does_not_divide_dimensions() {
+    let chunks = make_chunks(random_vectors(10, DIMENSIONS, 38));
+    let result = VPackIndex::build(chunks, ivf_pq_manifest(json!({ "m": 5 })));
+    assert!(matches!(
+        result,
+        Err(vpack_engine::VPackError::ManifestInvalid(_))
+    ));
+}
+
+#[test]
+fn approximate_indexes_follow_the_metric() {
+    let chunks = make_chunks(random_vectors(600, DIMENSIONS, 41));
+    let queries = random_vectors(20, DIMENSIONS, 42);
+
+    for metric in ["euclidean", "dot"] {
+        let flat = |mut manifest: serde_json::Value| {
+            manifest["index"]["type"] = json!("flat");
+            VPackIndex::build(chunks.clone(), manifest).unwrap()
+        };
+
+        let mut hnsw = make_manifest(DIMENSIONS);
+        hnsw["index"] = json!({ "metric": metric });
+        let exact = flat(hnsw.clone());
+        let approx = VPackIndex::build(chunks.clone(), hnsw).unwrap();
+        let recall = recall_at(&exact, &approx, &queries, 10);
+        assert!(recall >= 0.95, "{metric} hnsw recall@10 = {recall}");
+
+        let mut ivf_pq = ivf_pq_manifest(json!({
+            "nlist": 16, "nprobe": 8, "m": 8, "nbits": 6, "kmeans_iters": 10, "rerank": 100
+        }));
+        ivf_pq["index"]["metric"] = json!(metric);
+        let exact = flat(ivf_pq.clone());
+        let approx = VPackIndex::build(chunks.clone(), ivf_pq).unwrap();
+        let recall = recall_at(&exact, &approx, &queries, 10);
+        assert!(recall >= 0.95, "{metric} ivf-pq recall@10 = {recall}");
+    }
+}
+
+/// Filtered recall@10 against an exact scan with the same filter.
+fn filtered_recall(
+    exact: &VPackIndex,
+    approx: &VPackIndex,
+    queries: &[Vec<f32>],
+    filter: &serde_json::Value,
+    brute_force_selectivity: Option<f32>,
+) -> f32 {
+    let ids = |index: &VPackIndex, query: &[f32]| -> Vec<String> {
+        let options = QueryOptions {
+            filter: Some(serde_json::from_value(filter.clone()).unwrap()),
+            brute_force_selectivity,
+            ..QueryOptions::default()
+        };
+        let results = index.query(query, options).unwrap();
+        results.into_iter().map(|r| r.chunk.id).collect()
+    };
+    let mut hits = 0;
+    for query in queries {
+        let truth = ids(exact, query);
+        assert_eq!(truth.len(), 10);
+        let found = ids(approx, query);
+        assert_eq!(found.len(), 10, "{filter}");
+        hits += found.iter().filter(|id| truth.contains(id)).count();
+    }
+    hits as f32 / (queries.len() * 10) as f32
+}
+
+#[test]
+fn filtered_search_keeps_recall_at_every_selectivity() {
+    let mut chunks = make_chunks(random_vectors(3_000, DIMENSIONS, 41));
+    for (i, chunk) in chunks.iter_mut().enumerate() {
+        // Spread buckets across the vector clusters.
+        let bucket = (i * 37) % 100;
+        chunk
+            .chunk
+            .metadata
+            .extra
+            .insert("bucket".to_string(), json!(bucket));
+    }
+    let queries = random_vectors(20, DIMENSIONS, 42);
+    let exact = VPackIndex::build_with_options(
+        chunks.clone(),
+        make_manifest(DIMENSIONS),
+        BuildOptions {
+            index_type: IndexType::Flat,
+            ..BuildOptions::default()
+        },
+    )
+    .unwrap();
+    let hnsw = VPackIndex::build(chunks.clone(), make_manifest(DIMENSIONS)).unwrap();
+    let ivf_pq = VPackIndex::build(
+        chunks,
+        ivf_pq_manifest(json!({
+            "nlist": 32, "nprobe": 4, "m": 8, "nbits": 6, "kmeans_iters": 10, "rerank": 100
+        })),
+    )
+    .unwrap();
+
+    for percent in [1, 2, 10, 50] {
+        let filter = json!({ "field": "bucket", "op": "lte", "value": percent - 1 });
+        for (name, index) in [("hnsw", &hnsw), ("ivf-pq", &ivf_pq)] {
+            // Below the default threshold the matching chunks are scored exactly.
+            let adaptive = filtered_recall(&exact, index, &queries, &filter, None);
+            if percent < 5 {
+                assert_eq!(adaptive, 1.0, "{name} at {percent}%");
+            }
+            // Forced onto the ANN index, filtering during the search still
+            // finds a full, accurate top-k.
+            let traversal = filtered_recall(&exact, index, &queries, &filter, Some(0.0));
+            assert!(
+                traversal >= 0.85,
+                "{name} at {percent}%: recall@10 = {traversal}"
+            );
+        }
+    }
+}
+
+#[test]
+fn quantization_recall_vs_size() {
+    let dimensions = 128;
+    // Queries from the same clusters as the chunks, as real queries land
+    // near the content they ask about.
+    let mut vectors = random_vectors(1_550, dimensions, 43);
+    let queries = vectors.split_off(1_500);
+    let chunks = make_chunks(vectors);
+    let build = |index_type: &str, quantization: serde_json::Value| {
+        let mut manifest = make_manifest(dimensions);
+        manifest["index"] = json!({ "type": index_type, "quantization": quantization });
+        VPackIndex::build(chunks.clone(), manifest).unwrap()
+    };
+    let exact = build("flat", json!({ "type": "none" }));
+
+    let mut report = vec![format!(
+        "{:<22} {:>9} {:>7} {:>9}",
+        "pack", "bytes", "vs f32", "recall@10"
+    )];
+    for index_type in ["flat", "hnsw"] {
+        let f32_bytes = vpack_engine::serialize(&build(index_type, json!({ "type": "none" })))
+            .unwrap()
+            .len();
+        // Recall@10 each pack may lose against the same index over f32.
+        let mut f32_recall = 0.0;
+        for (label, quantization, max_loss) in [
+            ("f32", json!({ "type": "none" }), 0.0),
+            ("int8", json!({ "type": "int8" }), 0.1),
+            (
+                "int8 rescore 50",
+                json!({ "type": "int8", "rescore": 50 }),
+                0.02,
+            ),
+            // Sign bits alone only rank; kept for its size, not its recall.
+            ("binary", json!({ "type": "binary" }), 1.0),
+            (
+                "binary rescore 100",
+                json!({ "type": "binary", "rescore": 100 }),
+                0.02,
+            ),
+        ] {
+            let index = build(index_type, quantization);
+            let bytes = vpack_engine::serialize(&index).unwrap().len();
+            let recall = recall_at(&exact, &index, &queries, 10);
+            let name = format!("{index_type} {label}");
+            report.push(format!(
+                "{name:<22} {bytes:>9} {:>7.2} {recall:>9.3}",
+                bytes as f32 / f32_bytes as f32
+            ));
+            if !index.options().quantization.is_enabled() {
+                f32_recall = recall;
+            }
+            assert!(
+                recall >= f32_recall - max_loss,
+                "{name}: recall@10 = {recall}, f32 = {f32_recall}"
+            );
+            if index.options().quantization.is_enabled() {
+                if index.options().quantization.rescore == 0 {
+                    // One byte per component instead of four saves 3 * 1500 * 128
+                    // bytes; chunk text, metadata and the graph are unchanged.
+                    assert!(
+                        bytes * 10 < f32_bytes * 6,
+                        "{name}: {bytes} vs {f32_bytes} bytes"
+                    );
+                } else {
+                    assert!(bytes > f32_bytes, "{name}: {bytes} vs {f32_bytes} bytes");
+                }
+            }
+        }
+    }
+    println!("{}", report.join("\n"));
+}
+
+#[test]
+fn quantization_survives_serialize_round_trip() {
+    let chunks = make_chunks(random_vectors(500, DIMENSIONS, 45));
+    let queries = random_vectors(10, DIMENSIONS, 46);
+    for quantization in [
+        json!({ "type": "int8" }),
+        json!({ "type": "int8", "rescore": 20 }),
+        json!({ "type": "binary" }),
+        json!({ "type": "binary", "rescore": 40 }),
+    ] {
+        let mut manifest = make_manifest(DIMENSIONS);
+        manifest["index"] = json!({ "type": "det-hnsw", "quantization": quantization });
+        let index = VPackIndex::build(chunks.clone(), manifest).unwrap();
+
+        let bytes = vpack_engine::serialize(&index).unwrap();
+        let restored = vpack_engine::deserialize(&bytes).unwrap();
+        assert_eq!(restored.options(), index.options());
+        assert_eq!(vpack_engine::serialize(&restored).unwrap(), bytes);
+        for query in &queries {
+            assert_eq!(top_ids(&index, query, 10), top_ids(&restored, query, 10));
+        }
+    }
+}
+
+#[test]
+fn quantization_rejects_ivf_pq() {
+    let chunks = make_chunks(random_vectors(10, DIMENSIONS, 47));
+    let mut manifest = ivf_pq_manifest(json!({ "m": 8 }));
+    manifest["index"]["quantization"] = json!({ "type": "int8" });
+    assert!(matches!(
+        VPackIndex::build(chunks, manifest),
+        Err(vpack_engine::VPackError::ManifestInvalid(_))
+    ));
+}
+
+#[test]
+fn half_precision_halves_vectors_and_keeps_recall() {
+    let dimensions = 128;
+    let mut vectors = random_vectors(1_050, dimensions, 48);
+    let queries = vectors.split_off(1_000);
+    let chunks = make_chunks(vectors);
+    let build = |precision: &str| {
+        let mut manifest = make_manifest(dimensions);
+        manifest["index"] = json!({ "type": "flat", "precision": precision });
+        VPackIndex::build(chunks.clone(), manifest).unwrap()
+    };
+    let exact = build("f32");
+    let vectors_len = |index: &VPackIndex| {
+        let bytes = vpack_engine::serialize(index).unwrap();
+        vpack_engine::read_section(&bytes, vpack_engine::SectionId::Vectors)
+            .unwrap()
+            .unwrap()
+            .len()
+    };
+
+    for precision in ["f16", "bf16"] {
+        let index = build(precision);
+        assert_eq!(vectors_len(&index) * 2, vectors_len(&exact), "{precision}");
+        let recall = recall_at(&exact, &index, &queries, 10);
+        assert!(recall >= 0.98, "{precision}: recall@10 = {recall}");
+
+        // Returned vectors are f32, within the type's rounding of the input.
+        let tolerance = if precision == "f16" { 1e-3 } else { 8e-3 };
+        let options = QueryOptions {
+            top_k: 5,
+            include_vectors: true,
+            ..QueryOptions::default()
+        };
+        for result in index.query(&queries[0], options).unwrap() {
+            let original = &chunks
+                .iter()
+                .find(|chunk| chunk.chunk.id == result.chunk.id)
+                .unwrap()
+                .vector;
+            let vector = result.vector.unwrap();
+            assert_eq!(vector.len(), dimensions);
+            for (got, want) in vector.iter().zip(original) {
+                assert!((got - want).abs() <= tolerance * want.abs().max(1.0));
+            }
+        }
+    }
+}
+
+#[test]
+fn half_precision_survives_serialize_and_mmap() {
+    let chunks = make_chunks(random_vectors(300, DIMENSIONS, 49));
+    let queries = random_vectors(10, DIMENSIONS, 50);
+    for (precision, index_type) in [("f16", "det-hnsw"), ("bf16", "hnsw"), ("f16", "ivf-pq")] {
+        let mut manifest = make_manifest(DIMENSIONS);
+        manifest["index"] = json!({
+            "type": index_type,
+            "precision": precision,
+            "ivf_pq": { "nlist": 8, "m": 8, "rerank": 20 },
+        });
+        let index = VPackIndex::build(chunks.clone(), manifest).unwrap();
+        let bytes = vpack_engine::serialize(&index).unwrap();
+        let restored = vpack_engine::deserialize(&bytes).unwrap();
+        assert_eq!(vpack_engine::serialize(&restored).unwrap(), bytes);
+
+        let path = std::env::temp_dir().join(format!(
+            "vpack-{precision}-{index_type}-{}.vpack",
+            std::process::id()
+        ));
+        std::fs::write(&path, &bytes).unwrap();
+        let mapped = VPackIndex::open_mmap(&path).unwrap();
+        assert!(mapped.is_mapped());
+        let options = || QueryOptions {
+            include_vectors: true,
+            ..QueryOptions::default()
+        };
+        for query in &queries {
+            let expected = serde_json::to_value(index.query(query, options()).unwrap()).unwrap();
+            for loaded in [&restored, &mapped] {
+                let results = loaded.query(query, options()).unwrap();
+                assert_eq!(serde_json::to_value(results).unwrap(), expected);
+            }
+        }
+        drop(mapped);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,108 @@
+mod common;
+
+use common::{make_chunks, make_manifest, random_vectors, recall_at};
+use serde_json::json;
+use vpack_engine::{BuildOptions, EmbeddedChunk, IndexType, VPackIndex};
+
+const DIMENSIONS: usize = 16;
+
+fn det_manifest() -> serde_json::Value {
+    let mut manifest = make_manifest(DIMENSIONS);
+    manifest["index"] = json!({ "type": "det-hnsw" });
+    manifest
+}
+
+/// Chunks with several `extra` keys, so map ordering is exercised too.
+fn chunks_with_extra(count: usize) -> Vec<EmbeddedChunk> {
+    let mut chunks = make_chunks(random_vectors(count, DIMENSIONS, 5));
+    for (i, chunk) in chunks.iter_mut().enumerate() {
+        let extra = &mut chunk.chunk.metadata.extra;
+        extra.insert("category".to_string(), json!(["a", "b", "c"][i % 3]));
+        extra.insert("position".to_string(), json!(i));
+        extra.insert("heading".to_string(), json!(format!("Section {}", i / 7)));
+    }
+    chunks
+}
+
+#[test]
+fn det_hnsw_builds_are_byte_identical() {
+    let first = VPackIndex::build(chunks_with_extra(400), det_manifest()).unwrap();
+    let second = VPackIndex::build(chunks_with_extra(400), det_manifest()).unwrap();
+
+    let a = vpack_engine::serialize(&first).unwrap();
+    let b = vpack_engine::serialize(&second).unwrap();
+    assert_eq!(a, b);
+}
+
+#[test]
+fn det_hnsw_ignores_input_order() {
+    let chunks = chunks_with_extra(400);
+    let mut shuffled = chunks.clone();
+    shuffled.reverse();
+    shuffled.rotate_left(137);
+
+    let a = vpack_engine::serialize(&VPackIndex::build(chunks, det_manifest()).unwrap()).unwrap();
+    let b = vpack_engine::serialize(&VPackIndex::build(shuffled, det_manifest()).unwrap()).unwrap();
+    assert_eq!(a, b);
+}
+
+#[test]
+fn det_hnsw_round_trip_reserializes_identically() {
+    let index = VPackIndex::build(chunks_with_extra(200), det_manifest()).unwrap();
+    let bytes = vpack_engine::serialize(&index).unwrap();
+    let restored = vpack_engine::deserialize(&bytes).unwrap();
+    assert_eq!(vpack_engine::serialize(&restored).unwrap(), bytes);
+}
+
+#[test]
+fn manifest_hash_ignores_key_order() {
+    let a = json!({ "name": "@acme/docs", "version": "1.0.0", "vpack": "1.0" });
+    let b: serde_json::Value =
+        serde_json::from_str(r#"{ "vpack": "1.0", "version": "1.0.0", "name": "@acme/docs" }"#)
+            .unwrap();
+    assert_eq!(
+        vpack_engine::manifest_hash(&a),
+        vpack_engine::manifest_hash(&b)
+    );
+}
+
+#[test]
+fn det_hnsw_recall_matches_exact_scan() {
+    let chunks = make_chunks(random_vectors(1_500, DIMENSIONS, 41));
+    let queries = random_vectors(40, DIMENSIONS, 42);
+
+    let exact = VPackIndex::build_with_options(
+        chunks.clone(),
+        det_manifest(),
+        BuildOptions {
+            index_type: IndexType::Flat,
+            ..BuildOptions::default()
+        },
+    )
+    .unwrap();
+    let det = VPackIndex::build(chunks, det_manifest()).unwrap();
+
+    let recall = recall_at(&exact, &det, &queries, 10);
+    assert!(recall >= 0.92, "recall@10 = {recall}");
+}
+
+#[test]
+fn ivf_pq_builds_are_byte_identical_and_ignore_input_order() {
+    let mut manifest = make_manifest(DIMENSIONS);
+    manifest["index"] = json!({ "type": "ivf-pq", "ivf_pq": { "nlist": 8, "m": 4, "nbits": 6 } });
+    let chunks = chunks_with_extra(400);
+    let mut shuffled = chunks.clone();
+    shuffled.reverse();
+    shuffled.rotate_left(91);
+
+    let first = VPackIndex::build(chunks.clone(), manifest.clone()).unwrap();
+    let second = VPackIndex::build(chunks, manifest.clone()).unwrap();
+    let reordered = VPackIndex::build(shuffled, manifest).unwrap();
+
+    let a = vpack_engine::serialize(&first).unwrap();
+    assert_eq!(a, vpack_engine::serialize(&second).unwrap());
+    assert_eq!(a, vpack_engine::serialize(&reordered).unwrap());
+
+    let restored = vpack_engine::deserialize(&a).unwrap();
+    assert_eq!(vpack_engine::serialize(&restored).unwrap(), a);
+}
@@ -0,0 +1,56 @@
+# Type stubs for the `vpack` extension module (src/python_bindings.rs).
+
+from os import PathLike
+from typing import Any, Sequence
+
+import numpy as np
+import numpy.typing as npt
+
+class VPackError(Exception):
+    """Base class of vpack errors. `code` is the VPackError code."""
+
+    code: str
+
+class DimensionMismatchError(VPackError): ...
+class ModelMismatchError(VPackError): ...
+class EmptyIndexError(VPackError): ...
+class ModelHashMismatchError(VPackError): ...
+class SerializeError(VPackError): ...
+class UnknownModelError(VPackError): ...
+class DeserializeError(VPackError): ...
+class ManifestInvalidError(VPackError): ...
+class DuplicateChunkIdError(VPackError): ...
+class PatchBaseMismatchError(VPackError): ...
+class PatchFailedError(VPackError): ...
+class InvalidQueryError(VPackError): ...
+
+class VPackIndex:
+    @staticmethod
+    def build(
+        chunks: Sequence[dict[str, Any]],
+        manifest: dict[str, Any],
+        vectors: npt.NDArray[np.float32] | None = None,
+        options: dict[str, Any] | None = None,
+    ) -> VPackIndex: ...
+    @staticmethod
+    def deserialize(
+        data: bytes, model: str | None = None, model_hash: str | None = None
+    ) -> VPackIndex: ...
+    def serialize(self) -> bytes: ...
+    def query(
+        self,
+        input: str | npt.NDArray[np.float32] | Sequence[float],
+        **options: Any,
+    ) -> list[dict[str, Any]]: ...
+    def manifest(self) -> dict[str, Any]: ...
+    def chunk_count(self) -> int: ...
+    def dimensions(self) -> int: ...
+    def __len__(self) -> int: ...
+
+def load(
+    path: str | PathLike[str],
+    model: str | None = None,
+    model_hash: str | None = None,
+) -> VPackIndex: ...
+def embed(texts: Sequence[str], config: dict[str, Any]) -> npt.NDArray[np.float32]: ...
+def model_hash(config: dict[str, Any]) -> str: ...
@@ -44,7 +44,9 @@ export interface RawDocument {
 
 export type ChunkStrategy = 'fixed' | 'sentence' | 'paragraph' | 'semantic'
 export type DistanceMetric = 'cosine' | 'euclidean' | 'dot'
-export type IndexType = 'hnsw' | 'flat'
+export type IndexType = 'hnsw' | 'flat' | 'det-hnsw' | 'ivf-pq'
+export type QuantizationType = 'none' | 'int8' | 'binary'
+export type HistoryMode = 'include' | 'exclude' | 'only'
 export type EmbedProvider = 'local' | 'huggingface' | 'openai' | 'custom'
 export type PackTier = 'verified' | 'hosted'
 export type PluginKind = 'source' | 'transformer' | 'chunker' | 'embedder' | 'output' | 'middleware'
@@ -69,12 +71,33 @@ export interface ChunkConfig {
 export interface HnswConfig {
   m: number                     // connections per node (default: 16)
   ef_construction: number       // build-time search width (default: 200)
+  ef_search?: number            // query-time search width (default: 64)
+}
+
+export interface IvfPqConfig {
+  nlist: number                 // coarse clusters (default: 256)
+  nprobe: number                // clusters probed per query (default: 16)
+  m: number                     // PQ subspaces, must divide dimensions (default: 16)
+  nbits: number                 // bits per PQ code, 1–8 (default: 8)
+  kmeans_iters?: number         // Lloyd iterations (default: 20)
+  rerank?: number               // candidates rescored exactly; 0 = store codes only (default: 0)
+}
+
+export type VectorPrecision = 'f32' | 'f16' | 'bf16'
+
+export interface QuantizationConfig {
+  type: QuantizationType        // default: none
+  rescore?: number              // candidates rescored with f32 vectors; 0 = store codes only (default: 0)
 }
 
 export interface OutputConfig {
   metric: DistanceMetric
   index: IndexType
   hnsw?: HnswConfig
+  ivf_pq?: IvfPqConfig
+  metadata_index?: string[]     // metadata fields (dotted paths) with inverted indexes
+  quantization?: QuantizationConfig
+  precision?: VectorPrecision   // stored vector type (default: f32)
 }
 
 export interface VPackPluginConfig {
@@ -125,10 +148,30 @@ export interface BuildContext {
   readonly manifest: PackManifest
   readonly previousBuildHash?: string
   readonly changedChunkIds: Set<string>
+  readonly diff?: ChunkDiff     // against the previous build, when one is available (RFC-0004 §5.2)
   readonly buildId: string
   readonly dryRun: boolean
 }
 
+// ── Chunk diff (RFC-0004 §5) ─────────────────────────────────────────────────
+
+export interface ChunkDiff {
+  /** Chunks in this build whose ID was not in the previous build. */
+  added: EmbeddedChunk[]
+  /** Chunks in both builds whose text, metadata or vector changed. */
+  modified: Array<{
+    before: Chunk               // previous version, without its vector
+    after: EmbeddedChunk
+  }>
+  /** Chunks in the previous build whose ID is not in this one. */
+  removed: Array<{
+    id: string
+    text: string
+    metadata: ChunkMetadata
+  }>
+  base_version?: string         // version of the previous build
+}
+
 // ── Transformer plugin interface ─────────────────────────────────────────────
 
 export interface VPackTransformer<TConfig = unknown> {
@@ -193,18 +236,44 @@ export interface MetadataFilter {
   value?: unknown
 }
 
+/** Boolean combination of filters. A bare MetadataFilter is an expression too. */
+export type FilterExpression =
+  | MetadataFilter
+  | { and: FilterExpression[] }   // empty list matches everything
+  | { or: FilterExpression[] }    // empty list matches nothing
+  | { not: FilterExpression }
+
 export interface QueryOptions {
   topK?: number                 // default: 10
-  minScore?: number             // minimum similarity score 0–1
-  filter?: MetadataFilter
+  minScore?: number             // minimum score in the pack's metric (see QueryResult.score)
+  filter?: FilterExpression
   includeVectors?: boolean      // return vectors in results (default: false)
+  efSearch?: number             // HNSW search width for this query (default: pack's ef_search)
+  nprobe?: number               // IVF-PQ clusters probed for this query (default: pack's nprobe)
+  bruteForceSelectivity?: number // filters matching less than this fraction are scored exactly (default: 0.05)
+  model?: string                // expected embedding model; MODEL_MISMATCH if the pack pins another
+  modelHash?: string            // weights hash of that model; MODEL_HASH_MISMATCH if the pack pins another
+  history?: HistoryMode         // changelog chunks (RFC-0004): ranked with content, skipped, or searched alone (default: include)
+  temporalWeight?: number       // scale changelog chunk scores by 1 - temporalWeight, in [0, 1]
+  recency?: RecencyDecay        // halve scores for every half-life of chunk age
+}
+
+export interface LoadOptions {
+  model?: string                // expected embedding model; MODEL_MISMATCH if the pack pins another
+  modelHash?: string            // weights hash of that model; MODEL_HASH_MISMATCH if the pack pins another
+}
+
+export interface RecencyDecay {
+  halfLifeDays: number
+  now?: string                  // ISO 8601 reference time (default: now)
 }
 
 export interface QueryResult {
   chunk: Chunk
-  score: number                 // cosine similarity, 0–1
+  score: number                 // similarity after temporalWeight and recency; results are ranked by it
+  similarity: number            // higher is better — cosine: similarity; dot: dot product; euclidean: 1 / (1 + distance)
   rank: number                  // 0-indexed position
-  vector?: number[]             // only present if includeVectors: true
+  vector?: number[]             // only present if includeVectors: true; unit length for f32 cosine packs
 }
 
 // ── Engine interface ──────────────────────────────────────────────────────────
@@ -213,7 +282,12 @@ export interface QueryResult {
 
 export interface BuildOptions {
   metric?: DistanceMetric       // default: cosine
-  hnsw?: HnswConfig
+  index?: IndexType             // default: hnsw
+  hnsw?: Partial<HnswConfig>
+  ivf_pq?: Partial<IvfPqConfig>
+  metadata_index?: string[]     // metadata fields (dotted paths) with inverted indexes
+  quantization?: Partial<QuantizationConfig>
+  precision?: VectorPrecision   // default: f32
 }
 
 export interface VPackEngineAdapter {
@@ -231,7 +305,7 @@ export interface VPackEngineAdapter {
   /**
    * Deserialize a .vpack binary back into a queryable index.
    */
-  deserialize(bytes: Uint8Array): VPackIndex
+  deserialize(bytes: Uint8Array, options?: LoadOptions): VPackIndex
 }
 
 // ── Errors ───────────────────────────────────────────────────────────────────
@@ -256,6 +330,10 @@ export type VPackErrorCode =
   | 'MANIFEST_INVALID'
   | 'REGISTRY_ERROR'
   | 'MODEL_HASH_MISMATCH'      // build-time: model weights don't match pinned hash
+  | 'DUPLICATE_CHUNK_ID'       // insert of a chunk id already in the index
+  | 'PATCH_BASE_MISMATCH'      // .vpack.patch made against another artifact — catch up
+  | 'PATCH_FAILED'             // .vpack.patch cannot be applied — catch up
+  | 'INVALID_QUERY'            // query option out of range, e.g. temporalWeight > 1
 
 export const Errors = {
   dimensionMismatch: (expected: number, got: number) =>
@@ -115,11 +115,18 @@ describe('engine.query', () => {
     await expect(index.query([1, 0])).rejects.toThrowError(VPackError)
   })
 
-  it('throws on string input (TS engine requires pre-embedded vectors)', async () => {
+  it('throws on string input when the pinned embedder cannot run natively', async () => {
     const index = engine.build(CHUNKS_3D, makeManifest())
     await expect(index.query('what is our pricing?')).rejects.toThrow()
   })
 
+  it('throws MODEL_MISMATCH when the caller names another model', async () => {
+    const index = engine.build(CHUNKS_3D, makeManifest())
+    await expect(index.query([1, 0, 0], { model: 'BAAI/bge-small-en-v1.5' })).rejects.toThrowError(
+      VPackError,
+    )
+  })
+
   it('includes vectors when includeVectors: true', async () => {
     const index = engine.build(CHUNKS_3D, makeManifest())
     const results = await index.query([1, 0, 0], { includeVectors: true })
@@ -4,5 +4,5 @@
 // All callers are unaffected by the swap.
 
 export { RustEngine as engine } from './rust-engine.js'
-export { embedTexts } from './rust-engine.js'
+export { diff, embedTexts, modelHash, openMmap } from './rust-engine.js'
 export type { SerializedIndex } from './format.js'
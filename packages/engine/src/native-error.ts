@@ -0,0 +1,35 @@
+// native-error.ts — "CODE|message" errors from the napi addon and the wasm
+// module, mapped to VPackError.
+
+import type { VPackErrorCode } from '@vpack/core'
+import { VPackError } from '@vpack/core'
+
+export function mapNativeError(err: unknown): never {
+  const message = err instanceof Error ? err.message : String(err)
+  const separatorIndex = message.indexOf('|')
+  if (separatorIndex !== -1) {
+    const code = message.slice(0, separatorIndex) as VPackErrorCode
+    const detail = message.slice(separatorIndex + 1)
+    if (isVPackErrorCode(code)) {
+      throw new VPackError(code, detail)
+    }
+  }
+  throw err
+}
+
+function isVPackErrorCode(code: string): code is VPackErrorCode {
+  return (
+    code === 'DIMENSION_MISMATCH' ||
+    code === 'MODEL_MISMATCH' ||
+    code === 'EMPTY_INDEX' ||
+    code === 'UNKNOWN_MODEL' ||
+    code === 'SERIALIZE_FAILED' ||
+    code === 'DESERIALIZE_FAILED' ||
+    code === 'MANIFEST_INVALID' ||
+    code === 'MODEL_HASH_MISMATCH' ||
+    code === 'DUPLICATE_CHUNK_ID' ||
+    code === 'PATCH_BASE_MISMATCH' ||
+    code === 'PATCH_FAILED' ||
+    code === 'INVALID_QUERY'
+  )
+}
@@ -6,19 +6,24 @@ import type {
   QueryOptions,
   QueryResult,
   BuildOptions,
-  VPackErrorCode,
+  ChunkDiff,
+  LoadOptions,
 } from '@vpack/core'
-import { VPackError } from '@vpack/core'
 import { createRequire } from 'node:module'
 import { resolve } from 'node:path'
+import { mapNativeError } from './native-error.js'
 
 type NativeIndex = object
 type NativeModule = {
-  buildIndex: (chunksJson: string, manifestJson: string) => NativeIndex | Error
+  buildIndex: (chunksJson: string, manifestJson: string, optionsJson?: string) => NativeIndex | Error
   serializeIndex: (index: NativeIndex) => Buffer | Error
-  deserializeIndex: (bytes: Buffer) => NativeIndex | Error
+  deserializeIndex: (bytes: Buffer, optionsJson?: string) => NativeIndex | Error
+  openIndexMmap: (path: string, optionsJson?: string) => NativeIndex | Error
   embedTextsJson: (configJson: string, textsJson: string) => string | Error
+  modelHashJson: (configJson: string) => string | Error
   queryIndex: (index: NativeIndex, vector: number[], optionsJson?: string) => string | Error
+  queryIndexText: (index: NativeIndex, text: string, optionsJson?: string) => string | Error
+  diffIndexes: (oldIndex: NativeIndex, newIndex: NativeIndex) => string | Error
   manifestJson: (index: NativeIndex) => string
   chunkCount: (index: NativeIndex) => number
   dimensions: (index: NativeIndex) => number
@@ -48,15 +53,12 @@ class RustIndex implements VPackIndex {
   }
 
   async query(input: string | number[], options: QueryOptions = {}): Promise<QueryResult[]> {
-    if (typeof input === 'string') {
-      throw new Error(
-        'RustEngine.query() requires a pre-embedded vector. ' +
-          'Use @vpack/client which handles embedding automatically.',
-      )
-    }
-
     const optionsJson = options && Object.keys(options).length > 0 ? JSON.stringify(options) : undefined
-    const resultJson = native.queryIndex(this.nativeIndex, input, optionsJson)
+    // Text is embedded natively with the model pinned in the pack's manifest.
+    const resultJson =
+      typeof input === 'string'
+        ? native.queryIndexText(this.nativeIndex, input, optionsJson)
+        : native.queryIndex(this.nativeIndex, input, optionsJson)
     if (resultJson instanceof Error) {
       mapNativeError(resultJson)
     }
@@ -75,9 +77,10 @@ export const RustEngine: VPackEngineAdapter = {
   build(
     chunks: EmbeddedChunk[],
     manifest: PackManifest,
-    _options: BuildOptions = {},
+    options: BuildOptions = {},
   ): VPackIndex {
-    const nativeIndex = native.buildIndex(JSON.stringify(chunks), JSON.stringify(manifest))
+    const optionsJson = Object.keys(options).length > 0 ? JSON.stringify(options) : undefined
+    const nativeIndex = native.buildIndex(JSON.stringify(chunks), JSON.stringify(manifest), optionsJson)
     if (nativeIndex instanceof Error) {
       mapNativeError(nativeIndex)
     }
@@ -92,8 +95,8 @@ export const RustEngine: VPackEngineAdapter = {
     return new Uint8Array(bytes)
   },
 
-  deserialize(bytes: Uint8Array): VPackIndex {
-    const nativeIndex = native.deserializeIndex(Buffer.from(bytes))
+  deserialize(bytes: Uint8Array, options: LoadOptions = {}): VPackIndex {
+    const nativeIndex = native.deserializeIndex(Buffer.from(bytes), JSON.stringify(options))
     if (nativeIndex instanceof Error) {
       mapNativeError(nativeIndex)
     }
@@ -101,6 +104,31 @@ export const RustEngine: VPackEngineAdapter = {
   },
 }
 
+/**
+ * Open a .vpack file through a memory map: vectors stay on disk and chunk
+ * text is decoded only for returned results. The file must not change while
+ * the index is open. `options` rejects a pack pinning another model.
+ */
+export function openMmap(path: string, options: LoadOptions = {}): VPackIndex {
+  const nativeIndex = native.openIndexMmap(path, JSON.stringify(options))
+  if (nativeIndex instanceof Error) {
+    mapNativeError(nativeIndex)
+  }
+  return new RustIndex(nativeIndex)
+}
+
+/**
+ * Chunks added, modified and removed from `oldIndex` to `newIndex`, matched by
+ * chunk ID (RFC-0004 §5). Lists are in chunk-ID order.
+ */
+export function diff(oldIndex: VPackIndex, newIndex: VPackIndex): ChunkDiff {
+  const result = native.diffIndexes(requireNativeIndex(oldIndex), requireNativeIndex(newIndex))
+  if (result instanceof Error) {
+    mapNativeError(result)
+  }
+  return JSON.parse(result) as ChunkDiff
+}
+
 export async function embedTexts(
   texts: string[],
   config: Record<string, unknown>,
@@ -119,6 +147,18 @@ export async function embedTexts(
   }
 }
 
+/**
+ * `sha256:<hex>` of the local ONNX weights fastembed uses for `config.model`,
+ * the value to pin as `model_hash` in the manifest.
+ */
+export async function modelHash(config: Record<string, unknown>): Promise<string> {
+  const result = native.modelHashJson(JSON.stringify(config))
+  if (result instanceof Error) {
+    mapNativeError(result)
+  }
+  return result
+}
+
 function loadNative(): NativeModule {
   const require = createRequire(__filename)
   const nativePath = resolve(__dirname, '../native/index.node')
@@ -132,34 +172,9 @@ function loadNative(): NativeModule {
   }
 }
 
-function mapNativeError(err: unknown): never {
-  const message = err instanceof Error ? err.message : String(err)
-  const separatorIndex = message.indexOf('|')
-  if (separatorIndex !== -1) {
-    const code = message.slice(0, separatorIndex) as VPackErrorCode
-    const detail = message.slice(separatorIndex + 1)
-    if (isVPackErrorCode(code)) {
-      throw new VPackError(code, detail)
-    }
-  }
-  throw err
-}
-
 function requireNativeIndex(index: VPackIndex): NativeIndex {
   if (index instanceof RustIndex) {
     return (index as RustIndex).nativeIndex
   }
   throw new Error('RustEngine received a non-Rust index instance')
 }
-
-function isVPackErrorCode(code: string): code is VPackErrorCode {
-  return (
-    code === 'DIMENSION_MISMATCH' ||
-    code === 'MODEL_MISMATCH' ||
-    code === 'EMPTY_INDEX' ||
-    code === 'UNKNOWN_MODEL' ||
-    code === 'SERIALIZE_FAILED' ||
-    code === 'DESERIALIZE_FAILED' ||
-    code === 'MODEL_HASH_MISMATCH'
-  )
-}
@@ -7,6 +7,7 @@ import type {
   QueryResult,
   BuildOptions,
   MetadataFilter,
+  FilterExpression,
   Chunk,
 } from '@vpack/core'
 import { Errors } from '@vpack/core'
@@ -37,7 +38,7 @@ class TsVPackIndex implements VPackIndex {
   async query(input: string | number[], options: QueryOptions = {}): Promise<QueryResult[]> {
     if (this.chunks.length === 0) throw Errors.emptyIndex()
 
-    const { topK = 10, minScore, filter, includeVectors = false } = options
+    const { topK = 10, minScore, filter, includeVectors = false, history = 'include' } = options
 
     // input must be a pre-embedded vector in the TS impl.
     // The CLI/client layers are responsible for embedding text before calling query().
@@ -58,6 +59,7 @@ class TsVPackIndex implements VPackIndex {
 
     // Score all chunks
     let scored = this.chunks
+      .filter((chunk) => history === 'include' || isChangelog(chunk) === (history === 'only'))
       .filter((chunk) => (filter ? matchesFilter(chunk, filter) : true))
       .map((chunk) => ({
         chunk,
@@ -75,6 +77,7 @@ class TsVPackIndex implements VPackIndex {
     return scored.slice(0, topK).map((r, rank) => ({
       chunk: r.chunk as Chunk,
       score: r.score,
+      similarity: r.score,
       rank,
       ...(includeVectors ? { vector: r.chunk.vector } : {}),
     }))
@@ -141,7 +144,18 @@ function getEmbedderDimensions(manifest: PackManifest): number {
   return dims
 }
 
-function matchesFilter(chunk: EmbeddedChunk, filter: MetadataFilter): boolean {
+function isChangelog(chunk: EmbeddedChunk): boolean {
+  return chunk.metadata['chunk_type'] === 'changelog'
+}
+
+function matchesFilter(chunk: EmbeddedChunk, filter: FilterExpression): boolean {
+  if ('and' in filter) return filter.and.every((expr) => matchesFilter(chunk, expr))
+  if ('or' in filter) return filter.or.some((expr) => matchesFilter(chunk, expr))
+  if ('not' in filter) return !matchesFilter(chunk, filter.not)
+  return matchesLeaf(chunk, filter)
+}
+
+function matchesLeaf(chunk: EmbeddedChunk, filter: MetadataFilter): boolean {
   const value = getNestedValue(chunk.metadata, filter.field)
   switch (filter.op) {
     case 'eq':
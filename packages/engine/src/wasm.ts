@@ -0,0 +1,78 @@
+// @vpack/engine/wasm — read-only engine on the wasm-bindgen build
+//
+// For browsers and edge runtimes without the napi addon. Packs are
+// deserialized and queried with pre-embedded vectors; there is no embedder in
+// the wasm build, so text queries must be embedded by the caller.
+
+import type { LoadOptions, PackManifest, QueryOptions, QueryResult, VPackIndex } from '@vpack/core'
+import { mapNativeError } from './native-error.js'
+
+type WasmIndex = object
+type WasmModule = {
+  default: (input?: unknown) => Promise<unknown>
+  deserializeIndex: (bytes: Uint8Array, options?: LoadOptions) => WasmIndex
+  queryIndex: (index: WasmIndex, vector: Float32Array, options?: QueryOptions) => QueryResult[]
+  manifest: (index: WasmIndex) => PackManifest
+  chunkCount: (index: WasmIndex) => number
+  dimensions: (index: WasmIndex) => number
+}
+
+let loaded: Promise<WasmModule> | undefined
+
+/** Load and instantiate the wasm-pack output once. */
+function loadWasm(): Promise<WasmModule> {
+  loaded ??= (async () => {
+    // Built by `pnpm run build:wasm`; a variable path keeps bundlers and tsc
+    // from resolving it at build time.
+    const modulePath = '../wasm/vpack_engine.js'
+    const mod = (await import(modulePath)) as WasmModule
+    await mod.default()
+    return mod
+  })()
+  return loaded
+}
+
+class WasmPackIndex implements VPackIndex {
+  private readonly manifestValue: PackManifest
+
+  constructor(
+    private readonly wasm: WasmModule,
+    private readonly wasmIndex: WasmIndex,
+  ) {
+    this.manifestValue = wasm.manifest(wasmIndex)
+  }
+
+  manifest(): PackManifest {
+    return this.manifestValue
+  }
+
+  chunkCount(): number {
+    return this.wasm.chunkCount(this.wasmIndex)
+  }
+
+  dimensions(): number {
+    return this.wasm.dimensions(this.wasmIndex)
+  }
+
+  async query(input: string | number[], options: QueryOptions = {}): Promise<QueryResult[]> {
+    if (typeof input === 'string') {
+      throw new Error('The wasm engine has no embedder; pass a pre-embedded query vector.')
+    }
+    try {
+      return this.wasm.queryIndex(this.wasmIndex, Float32Array.from(input), options)
+    } catch (err) {
+      mapNativeError(err)
+    }
+  }
+}
+
+export const engine = {
+  async deserialize(bytes: Uint8Array, options: LoadOptions = {}): Promise<VPackIndex> {
+    const wasm = await loadWasm()
+    try {
+      return new WasmPackIndex(wasm, wasm.deserializeIndex(bytes, options))
+    } catch (err) {
+      mapNativeError(err)
+    }
+  },
+}
@@ -1,12 +1,14 @@
 import type { VPackEmbedder, BuildContext } from '@vpack/core'
-import { embedTexts } from '@vpack/engine'
+import { embedTexts, modelHash } from '@vpack/engine'
 
 export interface FastembedEmbedderConfig {
   model: string
+  model_hash?: string           // sha256 of the ONNX weights — embedding fails on mismatch
   dimensions: number
   provider?: 'fastembed'
   batch_size?: number
   max_length?: number
+  cache_dir?: string            // model cache (default: FASTEMBED_CACHE_DIR or .fastembed_cache)
 }
 
 export const FastembedEmbedder: VPackEmbedder<FastembedEmbedderConfig> = {
@@ -22,8 +24,8 @@ export const FastembedEmbedder: VPackEmbedder<FastembedEmbedderConfig> = {
     return config.model
   },
 
-  async modelHash(_config: FastembedEmbedderConfig): Promise<string> {
-    throw new Error('fastembed model hashes are not supported yet')
+  async modelHash(config: FastembedEmbedderConfig): Promise<string> {
+    return modelHash(config as unknown as Record<string, unknown>)
   },
 }
 